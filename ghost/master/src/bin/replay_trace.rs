@@ -0,0 +1,104 @@
+// haine_trace.jsonl のリクエストを順に再生し、記録時のレスポンスとの差分を表示する開発用ツール。
+// 実行: cargo run --bin replay_trace -- <トレースファイル> [ghost/masterのパス（省略時はカレント）]
+//
+// 各リクエストは記録時の時刻で処理する。セーブデータやプロフィール一覧を上書きしないよう、
+// 読み取り専用で動かし、終了時に unload も呼ばない。
+// ランダムトークなど乱数に依存するレスポンスは、記録時と一致しないことがある。
+use haine::system::clock::FakeClock;
+use haine::system::trace::read_trace;
+use haine::Shiori;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn event_id(request: &str) -> &str {
+  request
+    .split("\r\n")
+    .find_map(|l| l.strip_prefix("ID: "))
+    .unwrap_or("(no ID)")
+}
+
+fn print_diff(expected: &str, actual: &str) {
+  let expected: Vec<&str> = expected.split("\r\n").collect();
+  let actual: Vec<&str> = actual.split("\r\n").collect();
+  for i in 0..expected.len().max(actual.len()) {
+    match (expected.get(i), actual.get(i)) {
+      (Some(e), Some(a)) if e == a => {}
+      (e, a) => {
+        if let Some(e) = e {
+          println!("  - {}", e);
+        }
+        if let Some(a) = a {
+          println!("  + {}", a);
+        }
+      }
+    }
+  }
+}
+
+fn main() {
+  let mut args = std::env::args().skip(1);
+  let trace_path = match args.next() {
+    Some(p) => PathBuf::from(p),
+    None => {
+      eprintln!("usage: replay_trace <trace.jsonl> [ghost dir]");
+      std::process::exit(1);
+    }
+  };
+  let dir = args.next().unwrap_or_else(|| ".".to_string());
+
+  // load でトレースが作り直される前に読み込んでおく
+  let entries = match read_trace(&trace_path) {
+    Ok(e) => e,
+    Err(e) => {
+      eprintln!("failed to read {}: {}", trace_path.display(), e);
+      std::process::exit(1);
+    }
+  };
+  let Some(first) = entries.first() else {
+    println!("trace is empty");
+    return;
+  };
+  let clock = FakeClock::new(first.time);
+
+  if let Err(e) = std::env::set_current_dir(&dir) {
+    eprintln!("failed to enter {}: {}", dir, e);
+    std::process::exit(1);
+  }
+  let dir = match std::env::current_dir() {
+    Ok(d) => d.to_string_lossy().to_string(),
+    Err(e) => {
+      eprintln!("failed to resolve {}: {}", dir, e);
+      std::process::exit(1);
+    }
+  };
+  let mut shiori = match Shiori::load_with_clock(&dir, Arc::new(clock.clone())) {
+    Ok(s) => s,
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }
+  };
+  // 再生中のリクエストを記録し直さない
+  shiori.stop_trace();
+  // 自動保存やプロフィールの切り替えで手元のデータを書き換えない
+  shiori.set_read_only(true);
+
+  let mut mismatches = 0;
+  for (i, entry) in entries.iter().enumerate() {
+    clock.set(entry.time);
+    let response = shiori.request(&entry.request);
+    if response != entry.response {
+      mismatches += 1;
+      println!("#{} {}", i + 1, event_id(&entry.request));
+      print_diff(&entry.response, &response);
+    }
+  }
+  println!(
+    "{} requests replayed, {} mismatched",
+    entries.len(),
+    mismatches
+  );
+  if mismatches > 0 {
+    std::process::exit(2);
+  }
+}
@@ -0,0 +1,115 @@
+// セーブデータの書き出し・読み込みを行うツール。別のPCへ引っ越すときに使う。
+// 実行:
+//   cargo run --bin savedata -- export <ghost/masterのパス> [書き出し先（省略時は haine_savedata.json）] [--profile <名前>]
+//   cargo run --bin savedata -- import <ghost/masterのパス> <書き出したファイル> [--merge] [--yes] [--profile <名前>]
+//
+// import は変更点を表示し、確認してから反映する。--merge ならトーク履歴とフラグを今のデータと統合する。
+// --profile を省略すると、ゴーストで最後に使ったプロフィールが対象になる。既定のプロフィールは「既定」で指定する。
+// ゴーストの起動中に import すると終了時に上書きされるため、ゴーストを終了してから実行すること。
+use haine::system::clock::{Clock, SystemClock};
+use haine::system::transfer::{export_from_dir, import_to_dir, preview_import_to_dir, read_export, resolve_profile, ImportMode, EXPORT_FILE_NAME};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+fn usage() -> ! {
+  eprintln!("usage: savedata export <ghost dir> [out file] [--profile <name>]");
+  eprintln!("       savedata import <ghost dir> <file> [--merge] [--yes] [--profile <name>]");
+  std::process::exit(1);
+}
+
+fn fail(e: impl std::fmt::Display) -> ! {
+  eprintln!("{}", e);
+  std::process::exit(1);
+}
+
+/// --profile <name> を取り除いた引数と、指定されたプロフィール名
+fn take_profile(args: &[String]) -> (Vec<String>, Option<String>) {
+  let mut rest = Vec::new();
+  let mut profile = None;
+  let mut iter = args.iter();
+  while let Some(arg) = iter.next() {
+    if arg == "--profile" {
+      profile = Some(iter.next().cloned().unwrap_or_else(|| usage()));
+    } else {
+      rest.push(arg.clone());
+    }
+  }
+  (rest, profile)
+}
+
+/// 対象のプロフィールを決めて表示する
+fn profile_in(dir: &Path, name: Option<&str>) -> Option<String> {
+  let profile = resolve_profile(dir, name).unwrap_or_else(|e| fail(e));
+  println!("profile: {}", profile.as_deref().unwrap_or("(default)"));
+  profile
+}
+
+fn confirm() -> bool {
+  print!("apply? [y/N] ");
+  let _ = std::io::stdout().flush();
+  let mut line = String::new();
+  let _ = std::io::stdin().lock().read_line(&mut line);
+  line.trim().eq_ignore_ascii_case("y")
+}
+
+fn export(args: &[String]) {
+  let (args, profile) = take_profile(args);
+  let [dir, rest @ ..] = &args[..] else { usage() };
+  let dir = Path::new(dir);
+  let out = rest
+    .first()
+    .map(PathBuf::from)
+    .unwrap_or_else(|| PathBuf::from(EXPORT_FILE_NAME));
+  let profile = profile_in(dir, profile.as_deref());
+  let metadata = export_from_dir(dir, profile.as_deref(), &out, SystemClock.now()).unwrap_or_else(|e| fail(e));
+  println!("wrote {} (checksum: {})", out.display(), metadata.checksum);
+}
+
+fn import(args: &[String]) {
+  let (args, profile) = take_profile(args);
+  let positional: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+  let [dir, file] = positional[..] else { usage() };
+  let mode = if args.iter().any(|a| a == "--merge") {
+    ImportMode::Merge
+  } else {
+    ImportMode::Replace
+  };
+  let yes = args.iter().any(|a| a == "--yes");
+  let dir = Path::new(dir);
+  let profile = profile_in(dir, profile.as_deref());
+
+  let export = read_export(Path::new(file)).unwrap_or_else(|e| fail(e));
+  let m = &export.metadata;
+  println!(
+    "exported at {}/{:02}/{:02} {:02}:{:02} by version {}",
+    m.exported_at.year, m.exported_at.month, m.exported_at.day, m.exported_at.hour, m.exported_at.minute, m.ghost_version
+  );
+  for w in &export.warnings {
+    println!("warning: {}", w);
+  }
+
+  let diff = preview_import_to_dir(dir, profile.as_deref(), &export, mode).unwrap_or_else(|e| fail(e));
+  if diff.is_empty() {
+    println!("no changes");
+    return;
+  }
+  println!("changes ({}):", mode.as_str());
+  for line in &diff {
+    println!("  {}", line);
+  }
+  if !yes && !confirm() {
+    println!("canceled");
+    return;
+  }
+  import_to_dir(dir, profile.as_deref(), &export, mode, SystemClock.now()).unwrap_or_else(|e| fail(e));
+  println!("imported");
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  match args.split_first() {
+    Some((cmd, rest)) if cmd == "export" => export(rest),
+    Some((cmd, rest)) if cmd == "import" => import(rest),
+    _ => usage(),
+  }
+}
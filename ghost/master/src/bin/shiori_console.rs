@@ -0,0 +1,120 @@
+// SSPなしでゴーストを動かす開発用コンソール。標準入力からSHIORIリクエストを受け付け、レスポンスを表示する。
+// 実行: cargo run --bin shiori_console -- [ghost/masterのパス（省略時はカレント）]
+//
+// 入力形式:
+//   GET SHIORI/3.0 などで始まる行から空行までを生のリクエストとして送る
+//   event OnBoot                              → GET で ID: OnBoot を送る
+//   event OnMouseDoubleClick 0 0 0 2 candle   → 2つ目以降の語を Reference0, 1, ... として送る
+//   notify OnNotifyUserInfo user              → NOTIFY で送る
+//   events                                    → 対応しているイベントの一覧を表示する
+//   quit / exit                               → unload して終了（EOFでも同じ）
+use haine::{render_supported_events, Shiori};
+use std::io::{self, BufRead, Write};
+
+const SENDER: &str = "shiori_console";
+
+fn build_request(method: &str, args: &[&str]) -> Option<String> {
+  let (id, refs) = args.split_first()?;
+  let mut req = format!(
+    "{} SHIORI/3.0\r\nCharset: UTF-8\r\nSender: {}\r\nID: {}\r\n",
+    method, SENDER, id
+  );
+  for (i, r) in refs.iter().enumerate() {
+    req += &format!("Reference{}: {}\r\n", i, r);
+  }
+  req += "\r\n";
+  Some(req)
+}
+
+fn print_response(res: &str) {
+  let mut value = None;
+  for line in res.split("\r\n").filter(|l| !l.is_empty()) {
+    match line.strip_prefix("Value: ") {
+      Some(v) => value = Some(v),
+      None => println!("{}", line),
+    }
+  }
+  if let Some(v) = value {
+    println!("--- Value ---");
+    println!("{}", v);
+  }
+  println!();
+}
+
+fn prompt() {
+  print!("> ");
+  let _ = io::stdout().flush();
+}
+
+fn main() {
+  let dir = std::env::args().nth(1).unwrap_or_else(|| ".".to_string());
+  // セーブデータはカレントディレクトリ基準で読み書きされるため、SSPと同様にゴーストのディレクトリへ移動する
+  if let Err(e) = std::env::set_current_dir(&dir) {
+    eprintln!("failed to enter {}: {}", dir, e);
+    std::process::exit(1);
+  }
+  let dir = match std::env::current_dir() {
+    Ok(d) => d.to_string_lossy().to_string(),
+    Err(e) => {
+      eprintln!("failed to resolve {}: {}", dir, e);
+      std::process::exit(1);
+    }
+  };
+  let mut shiori = match Shiori::load(&dir) {
+    Ok(s) => s,
+    Err(e) => {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    }
+  };
+  println!("loaded {}", dir);
+
+  let stdin = io::stdin();
+  let mut raw: Option<String> = None;
+  prompt();
+  for line in stdin.lock().lines() {
+    let line = match line {
+      Ok(l) => l,
+      Err(e) => {
+        eprintln!("{}", e);
+        break;
+      }
+    };
+
+    // 生のリクエストは空行まで溜めてから送る
+    if let Some(mut req) = raw.take() {
+      if line.trim().is_empty() {
+        req += "\r\n";
+        print_response(&shiori.request(&req));
+        prompt();
+      } else {
+        req += &format!("{}\r\n", line.trim_end());
+        raw = Some(req);
+      }
+      continue;
+    }
+
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.first() {
+      None => {}
+      Some(&"quit") | Some(&"exit") => break,
+      Some(&"events") => println!("{}", render_supported_events()),
+      Some(&"event") | Some(&"notify") => {
+        let method = if words[0] == "event" { "GET" } else { "NOTIFY" };
+        match build_request(method, &words[1..]) {
+          Some(req) => print_response(&shiori.request(&req)),
+          None => eprintln!("usage: {} <ID> [Reference0] [Reference1] ...", words[0]),
+        }
+      }
+      Some(_) if line.contains("SHIORI/") => {
+        raw = Some(format!("{}\r\n", line.trim_end()));
+        continue;
+      }
+      Some(_) => eprintln!("unknown command: {}", line),
+    }
+    prompt();
+  }
+
+  shiori.unload();
+  println!("unloaded");
+}
@@ -0,0 +1,24 @@
+// 全トークを検証する開発用ツール。問題があれば一覧を表示し、終了コード1で終わる。
+// 実行: cargo run --bin validate_talks （ghost/master で実行すると talks/ のファイルも検証する）
+//
+// 検証する内容:
+//   - ランダムトーク・派生トークのIDの重複
+//   - 親のトークがない派生トーク
+//   - 存在しないチェイントークを待機させる副作用
+//   - 対応するパーツがないサーフェスコード（h1111216 など）
+//   - 翻訳時にスコープ指定がないとされる部分（NotSetScopeError）
+//   - \_q とクイックセクションの対応
+//   - 不明なプレースホルダ（{user_name} 以外など）
+//   - talks/ の読めないファイルや使われない項目
+fn main() {
+  let issues = haine::validate_talks();
+  for issue in &issues {
+    println!("{}", issue);
+  }
+  if issues.is_empty() {
+    println!("no issues");
+  } else {
+    eprintln!("{} issue(s)", issues.len());
+    std::process::exit(1);
+  }
+}
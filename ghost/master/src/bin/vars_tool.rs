@@ -0,0 +1,115 @@
+// セーブデータ（vars.json）を調べたり書き換えたりする開発用ツール。ユーザの状態を再現するときに使う。
+// 実行: cargo run --bin vars_tool -- <コマンド> <vars.jsonのパス> [引数...]
+//
+//   show              ファイルの内容を整形して表示する
+//   check             チェックサムの検証結果と、読み込めないフィールドとその理由を表示する
+//   talks [--unseen]  TalkType ごとに既読・未読のトークIDを表示する（ghost/master で実行すると talks/ のトークも含める）
+//   flags             立っているフラグと、最初に立てた日時・回数を表示する
+//   flag <フラグ>...  フラグを切り替える（FirstBoot, '{"TalkTypeUnlock":"Lore"}' など）
+//   reset <フィールド>...  フィールドを初期値に戻す
+//   fields            フィールド名の一覧を表示する
+//
+// 書き換えたときは、元のファイルがバックアップとして残る。
+use haine::system::clock::{Clock, SystemClock};
+use haine::system::inspect::SaveFile;
+use std::path::Path;
+
+fn usage() -> ! {
+  eprintln!("usage: vars_tool <show|check|talks|flags|flag|reset> <vars.json> [args...]");
+  eprintln!("       vars_tool fields");
+  std::process::exit(1);
+}
+
+fn fail(e: impl std::fmt::Display) -> ! {
+  eprintln!("{}", e);
+  std::process::exit(1);
+}
+
+fn print_failures(file: &SaveFile) {
+  println!("{}", file.integrity());
+  if file.failures.is_empty() {
+    println!("all fields are valid");
+  }
+  for f in &file.failures {
+    println!("{}: {}", f.field, f.reason);
+  }
+}
+
+fn print_talks(file: &SaveFile, unseen_only: bool) {
+  for c in file.talk_coverage() {
+    println!(
+      "[{}] seen {}/{}",
+      c.talk_type,
+      c.seen.len(),
+      c.seen.len() + c.unseen.len()
+    );
+    if !unseen_only {
+      for id in &c.seen {
+        println!("  + {}", id);
+      }
+    }
+    for id in &c.unseen {
+      println!("  - {}", id);
+    }
+    for id in &c.unknown {
+      println!("  ? {} (not in current talks)", id);
+    }
+  }
+}
+
+fn print_flags(file: &SaveFile) {
+  match file.days_since_first_boot(SystemClock.now()) {
+    Some(days) => println!("days since first boot: {}", days),
+    None => println!("days since first boot: unknown"),
+  }
+  for f in file.flags() {
+    let first_set = f.first_set.map_or_else(
+      || "unknown".to_string(),
+      |t| {
+        format!(
+          "{}/{:02}/{:02} {:02}:{:02}",
+          t.year, t.month, t.day, t.hour, t.minute
+        )
+      },
+    );
+    println!("{} (first set: {}, count: {})", f.flag, first_set, f.count);
+  }
+}
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let Some((cmd, rest)) = args.split_first() else { usage() };
+  if cmd == "fields" {
+    for name in SaveFile::field_names() {
+      println!("{}", name);
+    }
+    return;
+  }
+  let Some((path, params)) = rest.split_first() else { usage() };
+  let mut file = SaveFile::open(Path::new(path)).unwrap_or_else(|e| fail(e));
+
+  match cmd.as_str() {
+    "show" => println!("{}", file.pretty()),
+    "check" => print_failures(&file),
+    "talks" => print_talks(&file, params.iter().any(|p| p == "--unseen")),
+    "flags" => print_flags(&file),
+    "flag" | "reset" => {
+      if params.is_empty() {
+        usage();
+      }
+      let now = SystemClock.now();
+      for p in params {
+        if cmd == "flag" {
+          let on = file.toggle_flag(p, now).unwrap_or_else(|e| fail(e));
+          println!("{}: {}", p, if on { "on" } else { "off" });
+        } else {
+          file.reset_field(p).unwrap_or_else(|e| fail(e));
+          println!("{}: reset", p);
+        }
+      }
+      file.save(now).unwrap_or_else(|e| fail(e));
+      println!("saved {}", path);
+    }
+    _ => usage(),
+  }
+}
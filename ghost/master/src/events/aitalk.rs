@@ -1,10 +1,11 @@
-use crate::events::randomtalk::RANDOMTALK_COMMENTS_LIVING_ROOM;
+use crate::events::randomtalk::randomtalk_comments_living_room;
+use crate::events::references::{AnchorSelect, References};
 use crate::events::talk::anchor::anchor_talks;
 use crate::events::talk::randomtalk::random_talks;
 use crate::events::talk::{register_talk_collection, TalkType, TalkingPlace};
 use crate::events::{
-  first_boot::{FIRST_BOOT_MARKER, FIRST_RANDOMTALKS},
-  randomtalk::RANDOMTALK_COMMENTS_LIBRARY_INACTIVE,
+  first_boot::{first_random_talks, FIRST_BOOT_MARKER},
+  randomtalk::randomtalk_comments_library_inactive,
 };
 use crate::system::error::ShioriError;
 use crate::system::response::*;
@@ -21,30 +22,48 @@ pub(crate) const IMMERSIVE_RATE: u32 = 5;
 
 pub(crate) const IMMERSIVE_ICON_COUNT: u32 = 5;
 
-pub(crate) fn on_ai_talk(_req: &Request) -> Result<Response, ShioriError> {
-  let if_consume_talk_bias = *get_read(&IDLE_SECONDS) < IDLE_THRESHOLD;
-  *get_write(&LAST_RANDOM_TALK_TIME) = *get_read(&GHOST_UP_TIME);
+pub(crate) fn on_ai_talk(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
+  let if_consume_talk_bias = state.volatile.idle_seconds < IDLE_THRESHOLD;
+  state.volatile.last_random_talk_time = state.volatile.ghost_up_time;
 
   // 初回ランダムトーク
-  let text_count = FIRST_RANDOMTALKS.len();
-  for (i, text) in FIRST_RANDOMTALKS.iter().enumerate() {
-    if !get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(i as u32)) {
-      return first_random_talk_response(text.to_string(), i, text_count);
+  let texts = first_random_talks(state.talk_content());
+  let text_count = texts.len();
+  for (i, text) in texts.into_iter().enumerate() {
+    if !state
+      .persistent
+      .flags
+      .check(&EventFlag::FirstRandomTalkDone(i as u32))
+    {
+      return first_random_talk_response(state, text, i, text_count);
     }
   }
 
   // 通常ランダムトーク
-  let talk_types = get_read(&TALKING_PLACE).talk_types();
+  let talk_types = state
+    .volatile
+    .talking_place
+    .talk_types()
+    .into_iter()
+    .filter(|t| state.persistent.flags.check(&EventFlag::TalkTypeUnlock(*t)))
+    .collect::<Vec<_>>();
   let talk_lists = talk_types
-    .iter()
-    .filter(|t| get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(**t)))
-    .map(|t| random_talks(*t));
-  if talk_lists.clone().any(|t| t.is_none()) {
-    return Err(ShioriError::TalkNotFound);
+    .into_iter()
+    .map(|t| random_talks(state, t))
+    .collect::<Vec<_>>();
+  if talk_lists.iter().any(|t| t.is_none()) {
+    return Err(ShioriError::TalkNotFound(format!(
+      "random talks at {}",
+      state.volatile.talking_place
+    )));
   };
-  let talks = talk_lists.flatten().flatten().collect::<Vec<_>>();
+  let talks = talk_lists
+    .into_iter()
+    .flatten()
+    .flatten()
+    .collect::<Vec<_>>();
   let len_after_flatten = talks.len();
-  let index = if let Some(v) = choose_one(&talks, if_consume_talk_bias) {
+  let index = if let Some(v) = choose_talk(state, &talks, if_consume_talk_bias) {
     v
   } else {
     let mut res = new_response_nocontent();
@@ -58,21 +77,27 @@ pub(crate) fn on_ai_talk(_req: &Request) -> Result<Response, ShioriError> {
   if if_consume_talk_bias {
     // ユーザが見ているときのみトークを消費&トークカウントを加算
     if let Some(talk_type) = choosed_talk.talk_type {
-      register_talk_collection(&choosed_talk.id, talk_type)?;
+      register_talk_collection(state, &choosed_talk.id, talk_type)?;
     }
-    *get_write(&CUMULATIVE_TALK_COUNT) += 1;
+    state.persistent.cumulative_talk_count += 1;
   }
 
   // バルーン右下に表示するコメントを取得
-  let comment = if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
+  let comment = if state.volatile.talking_place == TalkingPlace::Library {
     // 書斎では能動的に話しかけたかどうかで異なるコメントを表示
-    let index = choose_one(&RANDOMTALK_COMMENTS_LIBRARY_INACTIVE, false).ok_or(ShioriError::TalkNotFound)?;
-    RANDOMTALK_COMMENTS_LIBRARY_INACTIVE[index].to_string()
+    let comments = randomtalk_comments_library_inactive(state.talk_content());
+    let index = choose_one(state, &comments, false).ok_or(ShioriError::ArrayAccessError)?;
+    comments[index].clone()
   } else {
     // 居間では従者トーク解禁済みの場合コメントを表示
-    if get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Servant)) {
-      let index = choose_one(&RANDOMTALK_COMMENTS_LIVING_ROOM, false).ok_or(ShioriError::TalkNotFound)?;
-      RANDOMTALK_COMMENTS_LIVING_ROOM[index].to_string()
+    if state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Servant))
+    {
+      let comments = randomtalk_comments_living_room(state.talk_content());
+      let index = choose_one(state, &comments, false).ok_or(ShioriError::ArrayAccessError)?;
+      comments[index].clone()
     } else {
       "".to_string()
     }
@@ -80,33 +105,26 @@ pub(crate) fn on_ai_talk(_req: &Request) -> Result<Response, ShioriError> {
 
   // 没入度を増減
   // トークのたび燭台への干渉を修復する方へ没入度が増減する
-  if *get_read(&TALKING_PLACE) == TalkingPlace::LivingRoom {
-    let new_rate;
-    {
-      new_rate = get_read(&IMMERSIVE_DEGREES).saturating_sub(IMMERSIVE_RATE);
-    }
-    *get_write(&IMMERSIVE_DEGREES) = new_rate;
+  if state.volatile.talking_place == TalkingPlace::LivingRoom {
+    state.volatile.immersive_degrees = state
+      .volatile
+      .immersive_degrees
+      .saturating_sub(IMMERSIVE_RATE);
   } else {
-    let new_rate;
-    {
-      new_rate = *get_read(&IMMERSIVE_DEGREES) + IMMERSIVE_RATE;
-    }
-    *get_write(&IMMERSIVE_DEGREES) = new_rate.min(IMMERSIVE_RATE_MAX);
+    state.volatile.immersive_degrees = (state.volatile.immersive_degrees + IMMERSIVE_RATE).min(IMMERSIVE_RATE_MAX);
   }
 
-  new_response_with_value_with_translate(
-    format!(
-      "\\0{}\\![set,balloonnum,{}]{}",
-      render_immersive_icon(),
-      comment,
-      render_talk(&choosed_talk),
-    ),
-    TranslateOption::with_shadow_completion(),
-  )
+  let m = format!(
+    "\\0{}\\![set,balloonnum,{}]{}",
+    render_immersive_icon(state),
+    comment,
+    render_talk(state, &choosed_talk),
+  );
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
 }
 
-pub fn render_talk(talk: &Talk) -> String {
-  let derivative_talk_request_button = if *get_read(&DERIVATIVE_TALK_REQUESTABLE) && *get_read(&TALKING_PLACE) == TalkingPlace::LivingRoom {
+pub fn render_talk(state: &mut GhostState, talk: &Talk) -> String {
+  let derivative_talk_request_button = if state.persistent.derivative_talk_requestable && state.volatile.talking_place == TalkingPlace::LivingRoom {
     format!(
       "\\0\\f[default]\\f[anchornotselectfontcolor,default.plain]\\_a[DerivativeTalkRequest,{}]{}\\_a\\f[anchornotselectfontcolor,default]\\_l[0,@1.5em]",
       talk.id,
@@ -116,7 +134,7 @@ pub fn render_talk(talk: &Talk) -> String {
     String::new()
   };
 
-  let derivative_talk_anchors = if let Some(dtalks) = derivative_talk_by_id(&talk.id) {
+  let derivative_talk_anchors = if let Some(dtalks) = derivative_talk_by_id(state, &talk.id) {
     let mut anchors = "\\1\\_q".to_string();
     for (i, dtalk) in dtalks.iter().enumerate() {
       // TODO: \1にすでに文章がある場合も\_lで表示位置を調整する必要がある
@@ -138,18 +156,25 @@ pub fn render_talk(talk: &Talk) -> String {
   format!(
     "{}{}{}",
     derivative_talk_request_button,
-    talk.consume(),
+    talk.consume(state),
     derivative_talk_anchors,
   )
 }
 
-fn first_random_talk_response(text: String, i: usize, text_count: usize) -> Result<Response, ShioriError> {
-  get_write(&FLAGS).done(EventFlag::FirstRandomTalkDone(i as u32));
+fn first_random_talk_response(state: &mut GhostState, text: String, i: usize, text_count: usize) -> Result<Response, ShioriError> {
+  state
+    .persistent
+    .flags
+    .done(EventFlag::FirstRandomTalkDone(i as u32), state.local_time());
   let m = if i == text_count - 1 {
     let achieved_talk_types = [TalkType::AboutMe, TalkType::WithYou];
     achieved_talk_types.iter().for_each(|t| {
-      get_write(&FLAGS).done(EventFlag::TalkTypeUnlock(*t));
+      state
+        .persistent
+        .flags
+        .done(EventFlag::TalkTypeUnlock(*t), state.local_time());
     });
+    state.autosave();
     let achievements_messages = achieved_talk_types
       .iter()
       .map(|t| render_achievement_message(*t))
@@ -162,7 +187,7 @@ fn first_random_talk_response(text: String, i: usize, text_count: usize) -> Resu
   } else {
     text.clone()
   };
-  let mut res = new_response_with_value_with_translate(m, TranslateOption::with_shadow_completion())?;
+  let mut res = new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())?;
   res.headers.insert_by_header_name(
     HeaderName::from("Marker"),
     format!("{}({}/{})", FIRST_BOOT_MARKER, i + 2, text_count + 1),
@@ -170,36 +195,41 @@ fn first_random_talk_response(text: String, i: usize, text_count: usize) -> Resu
   Ok(res)
 }
 
-pub(crate) fn on_anchor_select_ex(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let anchor_type = refs[1]; // AnchorTalk || DerivativeTalk // DerivativeTalkRequest
-  let id = refs[2];
-  let user_dialog = refs.get(3).unwrap_or(&"").to_string();
+pub(crate) fn on_anchor_select_ex(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let AnchorSelect { anchor_type, id } = AnchorSelect::from_request(req)?;
+  let id = id.as_str();
+  let user_dialog = References::from_request(req).get_or_empty(3).to_string();
 
-  if *get_read(&LAST_ANCHOR_ID) == Some(id.to_string()) {
+  if state.volatile.last_anchor_id.as_deref() == Some(id) {
     return Ok(new_response_nocontent());
   }
 
-  match anchor_type {
-    "AnchorTalk" => anchor_talk_dialog(id, &user_dialog),
-    "DerivativeTalk" => derivative_talk_dialog(id),
-    "DerivativeTalkRequest" => derivative_talk_request_open(id),
-    _ => Err(ShioriError::BadRequest),
+  match anchor_type.as_str() {
+    "AnchorTalk" => anchor_talk_dialog(state, id, &user_dialog),
+    "DerivativeTalk" => derivative_talk_dialog(state, id),
+    "DerivativeTalkRequest" => derivative_talk_request_open(state, id),
+    _ => Err(ShioriError::BadRequest(format!(
+      "unknown anchor type: {}",
+      anchor_type
+    ))),
   }
 }
 
-fn derivative_talk_dialog(id: &str) -> Result<Response, ShioriError> {
-  match derivative_talks().iter().find(|t| t.id == id) {
+fn derivative_talk_dialog(state: &mut GhostState, id: &str) -> Result<Response, ShioriError> {
+  match derivative_talks(state.talk_content())
+    .iter()
+    .find(|t| t.id == id)
+  {
     Some(talk) => {
       let mut m = String::from("\\C");
       m += &format!("\\1\\c\\_q{}\\n\\_q", talk.summary);
       m += "\\0\\n\\f[align,center]\\_q─\\w1──\\w1───\\w1─────\\w1────\\w1──\\w1──\\w1─\\w1─\\n";
       m += "\\_w[750]\\_q\\_l[@0,]";
-      m += &talk.consume();
+      m += &talk.consume(state);
       let parent_talk = TalkType::all()
         .iter()
         .map(|t| {
-          if let Some(talks) = random_talks(*t) {
+          if let Some(talks) = random_talks(state, *t) {
             talks.iter().find(|t| t.id == talk.parent_id).cloned()
           } else {
             None
@@ -209,25 +239,25 @@ fn derivative_talk_dialog(id: &str) -> Result<Response, ShioriError> {
         .and_then(|t| t);
       if let Some(parent) = parent_talk {
         if let Some(talk_type) = parent.talk_type {
-          register_talk_collection(id, talk_type)?;
+          register_talk_collection(state, id, talk_type)?;
         }
       }
-      new_response_with_value_with_translate(m, TranslateOption::with_shadow_completion())
+      new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
     }
     None => Ok(new_response_nocontent()),
   }
 }
 
-fn anchor_talk_dialog(id: &str, user_dialog: &str) -> Result<Response, ShioriError> {
+fn anchor_talk_dialog(state: &mut GhostState, id: &str, user_dialog: &str) -> Result<Response, ShioriError> {
   let mut m = String::from("\\C");
   m += "\\0\\n\\f[align,center]\\_q─\\w1──\\w1───\\w1─────\\w1────\\w1──\\w1──\\w1─\\w1─\\n\\_w[750]\\_q\\_l[@0,]";
   if !user_dialog.is_empty() {
     m += &format!("\\1『{}』\\_w[500]", user_dialog);
   }
-  match anchor_talks(id) {
+  match anchor_talks(state.talk_content(), id) {
     Some(t) => {
-      *get_write(&LAST_ANCHOR_ID) = Some(id.to_string());
-      new_response_with_value_with_translate(m + &t, TranslateOption::with_shadow_completion())
+      state.volatile.last_anchor_id = Some(id.to_string());
+      new_response_with_value_with_translate(state, m + &t, TranslateOption::with_shadow_completion())
     }
     None => Ok(new_response_nocontent()),
   }
@@ -236,6 +266,7 @@ fn anchor_talk_dialog(id: &str, user_dialog: &str) -> Result<Response, ShioriErr
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::events::first_boot::FIRST_RANDOMTALKS;
   use crate::events::on_boot;
   use crate::events::on_close;
   use crate::events::on_minute_change;
@@ -244,7 +275,6 @@ mod test {
   use crate::events::TALK_UNLOCK_COUNT_LORE;
   use crate::events::TALK_UNLOCK_COUNT_SERVANT;
   use crate::system::variables::PendingEvent;
-  use crate::system::variables::USER_NAME;
   use shiorust::message::Request;
 
   #[test]
@@ -252,8 +282,8 @@ mod test {
     const FIRST_CLOSE_TALK_PART: &str = "生きたあなたと話していたい";
     const SECOND_CLOSE_TALK_PART: &str = "がありますように";
     const CLOSE_TALK_IN_LIBRARY_PART: &str = "ハイネはお茶を一口飲んだ";
-
-    *get_write(&USER_NAME) = "test".to_string(); // 実際はOnNotifyUserInfoで設定される
+    let mut state = GhostState::default();
+    state.persistent.user_name = "test".to_string(); // 実際はOnNotifyUserInfoで設定される
 
     let mut headers = Headers::new();
     headers.insert_by_header_name(HeaderName::from("ID"), "OnSecondChange".to_string());
@@ -279,78 +309,103 @@ mod test {
     };
 
     // テスト中は常に非アイドル状態
-    *get_write(&IDLE_SECONDS) = IDLE_THRESHOLD - 1;
+    state.volatile.idle_seconds = IDLE_THRESHOLD - 1;
 
     // 初回起動時のフラグチェック
-    assert!(!get_read(&FLAGS).check(&EventFlag::FirstBoot));
-    on_boot(&on_second_change_req)?;
-    assert!(get_read(&FLAGS).check(&EventFlag::FirstBoot));
+    assert!(!state.persistent.flags.check(&EventFlag::FirstBoot));
+    on_boot(&mut state, &on_second_change_req)?;
+    assert!(state.persistent.flags.check(&EventFlag::FirstBoot));
 
     // 初回ランダムトークのフラグチェック
-    assert!(!get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::AboutMe)));
-    assert!(!get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::WithYou)));
+    assert!(!state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::AboutMe)));
+    assert!(!state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::WithYou)));
     for i in 0..FIRST_RANDOMTALKS.len() {
-      on_ai_talk(&on_second_change_req)?;
-      assert!(get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(i as u32)));
+      on_ai_talk(&mut state, &on_second_change_req)?;
+      assert!(state
+        .persistent
+        .flags
+        .check(&EventFlag::FirstRandomTalkDone(i as u32)));
     }
-    assert!(get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::AboutMe)));
-    assert!(get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::WithYou)));
+    assert!(state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::AboutMe)));
+    assert!(state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::WithYou)));
 
     // 初回没入度マックス時の場所変更
-    assert!(!get_read(&FLAGS).check(&EventFlag::FirstPlaceChange));
+    assert!(!state.persistent.flags.check(&EventFlag::FirstPlaceChange));
     for _i in 0..IMMERSIVE_ICON_COUNT {
-      on_mouse_double_click(&on_mouse_double_click_req)?;
+      on_mouse_double_click(&mut state, &on_mouse_double_click_req)?;
     }
-    assert!(get_read(&FLAGS).check(&EventFlag::FirstPlaceChange));
+    assert!(state.persistent.flags.check(&EventFlag::FirstPlaceChange));
 
     // 初回終了時に独白モードだったときトークが特別なものになるかのテスト
-    assert!(!get_read(&FLAGS).check(&EventFlag::FirstClose));
-    let res = on_close(&on_second_change_req)?;
+    assert!(!state.persistent.flags.check(&EventFlag::FirstClose));
+    let res = on_close(&mut state, &on_second_change_req)?;
     let value = res
       .headers
       .get_by_header_name(&HeaderName::from("Value"))
       .ok_or("Failed to get value")?;
     assert!(value.contains(CLOSE_TALK_IN_LIBRARY_PART)); // 独白モード終了トークが含まれていることの確認
     assert!(value.contains(FIRST_CLOSE_TALK_PART)); // 初回終了トークが含まれていることの確認
-    assert!(get_read(&FLAGS).check(&EventFlag::FirstClose));
+    assert!(state.persistent.flags.check(&EventFlag::FirstClose));
 
     // 書斎から正しく戻れるかのテスト
-    assert!(*get_read(&TALKING_PLACE) == TalkingPlace::Library);
+    assert!(state.volatile.talking_place == TalkingPlace::Library);
     for _i in 0..IMMERSIVE_ICON_COUNT {
-      on_mouse_double_click(&on_mouse_double_click_req)?;
+      on_mouse_double_click(&mut state, &on_mouse_double_click_req)?;
     }
-    assert!(*get_read(&TALKING_PLACE) == TalkingPlace::LivingRoom);
+    assert!(state.volatile.talking_place == TalkingPlace::LivingRoom);
 
     // 従者関連トークの開放確認
-    while *get_read(&CUMULATIVE_TALK_COUNT) < TALK_UNLOCK_COUNT_SERVANT {
-      on_ai_talk(&on_second_change_req)?;
+    while state.persistent.cumulative_talk_count < TALK_UNLOCK_COUNT_SERVANT {
+      on_ai_talk(&mut state, &on_second_change_req)?;
     }
-    on_minute_change(&on_second_change_req);
-    let story_event = get_read(&PENDING_EVENT_TALK)
+    on_minute_change(&mut state, &on_second_change_req);
+    let story_event = state
+      .persistent
+      .pending_event_talk
       .clone()
       .ok_or("Failed to get story event")?;
     assert_eq!(story_event, PendingEvent::UnlockingServantsComments);
-    on_story_event(&make_story_event_request(story_event))?;
-    assert!(get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Servant)));
-    assert!(get_read(&PENDING_EVENT_TALK).is_none());
+    on_story_event(&mut state, &make_story_event_request(story_event))?;
+    assert!(state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Servant)));
+    assert!(state.persistent.pending_event_talk.is_none());
 
     // ロア関連トークの開放確認
-    while *get_read(&CUMULATIVE_TALK_COUNT) < TALK_UNLOCK_COUNT_LORE {
-      on_ai_talk(&on_second_change_req)?;
+    while state.persistent.cumulative_talk_count < TALK_UNLOCK_COUNT_LORE {
+      on_ai_talk(&mut state, &on_second_change_req)?;
     }
-    on_minute_change(&on_second_change_req);
-    let story_event = get_read(&PENDING_EVENT_TALK)
+    on_minute_change(&mut state, &on_second_change_req);
+    let story_event = state
+      .persistent
+      .pending_event_talk
       .clone()
       .ok_or("Failed to get story event")?;
     assert_eq!(story_event, PendingEvent::UnlockingLoreTalks);
-    on_story_event(&make_story_event_request(story_event))?;
-    assert!(get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Lore)));
-    assert!(get_read(&PENDING_EVENT_TALK).is_none());
+    on_story_event(&mut state, &make_story_event_request(story_event))?;
+    assert!(state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Lore)));
+    assert!(state.persistent.pending_event_talk.is_none());
 
     // 初回終了時に通常モードだったときのトークが再生されるかのテスト
-    get_write(&FLAGS).delete(EventFlag::FirstClose);
-    assert!(!get_read(&FLAGS).check(&EventFlag::FirstClose));
-    let res = on_close(&on_second_change_req)?;
+    state.persistent.flags.delete(EventFlag::FirstClose);
+    assert!(!state.persistent.flags.check(&EventFlag::FirstClose));
+    let res = on_close(&mut state, &on_second_change_req)?;
     let value = res
       .headers
       .get_by_header_name(&HeaderName::from("Value"))
@@ -358,7 +413,7 @@ mod test {
     assert!(value.contains(FIRST_CLOSE_TALK_PART)); // 初回終了トークが含まれていることの確認
 
     // 2回目以降の終了時トークが再生されることの確認
-    let res = on_close(&on_second_change_req)?;
+    let res = on_close(&mut state, &on_second_change_req)?;
     let value = res
       .headers
       .get_by_header_name(&HeaderName::from("Value"))
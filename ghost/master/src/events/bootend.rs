@@ -1,33 +1,37 @@
 use crate::events::check_story_events;
-use crate::events::first_boot::{FIRST_BOOT_MARKER, FIRST_BOOT_TALK, FIRST_CLOSE_TALK, FIRST_RANDOMTALKS};
+use crate::events::first_boot::{first_boot_talk, first_close_talk, FIRST_BOOT_MARKER, FIRST_RANDOMTALKS};
 use crate::events::TalkingPlace;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::variables::*;
-use crate::system::windows::get_local_time;
 use rand::seq::SliceRandom;
 use shiorust::message::{parts::HeaderName, Response, *};
 
-pub(crate) fn on_boot(_req: &Request) -> Result<Response, ShioriError> {
-  *get_write(&TOTAL_BOOT_COUNT) += 1;
+pub(crate) fn on_boot(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
+  state.persistent.total_boot_count += 1;
+  state.volatile.booted = true;
 
   // ロード失敗かつバックアップもないなら何もしない
-  if *get_read(&LOAD_STATUS) == LoadStatus::FailedNoBackup {
+  if let LoadStatus::FailedNoBackup(damage) = state.volatile.load_status {
     let mut res = new_response_nocontent();
     add_error_description(
       &mut res,
-      "ロードに失敗しました。ゴーストを終了し、お手数ですがゴーストフォルダ内のhaine.logの内容とともにバグ報告をお願い致します。",
+      &format!(
+        "セーブデータが{}。読めるバックアップもなかったため、ロードに失敗しました。ゴーストを終了し、お手数ですがゴーストフォルダ内のhaine.logの内容とともにバグ報告をお願い致します。",
+        damage.description()
+      ),
     );
     return Ok(res);
   }
 
   // 初回起動
-  if !get_read(&FLAGS).check(&EventFlag::FirstBoot) {
-    get_write(&FLAGS).done(EventFlag::FirstBoot);
-    let mut res = new_response_with_value_with_translate(
-      FIRST_BOOT_TALK.to_string(),
-      TranslateOption::simple_translate(),
-    )?;
+  if !state.persistent.flags.check(&EventFlag::FirstBoot) {
+    state
+      .persistent
+      .flags
+      .done(EventFlag::FirstBoot, state.local_time());
+    let talk = first_boot_talk(state.talk_content());
+    let mut res = new_response_with_value_with_translate(state, talk, TranslateOption::simple_translate())?;
     res.headers.insert_by_header_name(
       HeaderName::from("Marker"),
       format!("{}(1/{})", FIRST_BOOT_MARKER, FIRST_RANDOMTALKS.len() + 1),
@@ -35,14 +39,14 @@ pub(crate) fn on_boot(_req: &Request) -> Result<Response, ShioriError> {
     return Ok(res);
   }
 
-  check_story_events();
+  check_story_events(state);
 
   // トーク内容の決定（日付イベント or 通常トーク）
-  let talk_content = if let Some(event_talk) = check_date_event_talk() {
+  let talk_content = if let Some(event_talk) = check_date_event_talk(state) {
     event_talk
   } else {
     let talks = all_combo(&vec![
-      vec![render_immersive_icon()],
+      vec![render_immersive_icon(state)],
       vec!["h1113105\\1今日も、霧が濃い。".to_string()],
       vec![format!(
         "\
@@ -50,8 +54,7 @@ pub(crate) fn on_boot(_req: &Request) -> Result<Response, ShioriError> {
           h1111204{}、{{user_name}}。\
           ",
         {
-          let st = get_local_time();
-          let hour = st.wHour;
+          let hour = state.local_time().hour;
           if hour <= 3 || hour >= 19 {
             "こんばんは"
           } else if hour < 11 {
@@ -62,12 +65,12 @@ pub(crate) fn on_boot(_req: &Request) -> Result<Response, ShioriError> {
         }
       )],
     ]);
-    let index = choose_one(&talks, false).ok_or(ShioriError::ArrayAccessError)?;
+    let index = choose_one(state, &talks, false).ok_or(ShioriError::ArrayAccessError)?;
     talks[index].clone()
   };
 
-  let local_time = crate::system::windows::get_local_time();
-  let unset_halloween = if local_time.wMonth != 10 || local_time.wDay != 31 {
+  let now = state.local_time();
+  let unset_halloween = if now.month != 10 || now.day != 31 {
     "\\![bind,頭,ヤギ角,0]\\![bind,頭,魔女帽,0]\\![bind,トップス+,黒赤マント,0]"
   } else {
     ""
@@ -81,16 +84,44 @@ pub(crate) fn on_boot(_req: &Request) -> Result<Response, ShioriError> {
     randomize_underwear(),
     talk_content,
   );
-  let mut res = new_response_with_value_with_translate(v, TranslateOption::simple_translate())?;
+  let mut res = new_response_with_value_with_translate(state, v, TranslateOption::simple_translate())?;
 
-  if *get_read(&LOAD_STATUS) == LoadStatus::RestoredFromBackup {
-    add_notice_description(
-      &mut res,
-      "セーブデータが破損していたため、バックアップから復元しました。",
-    );
+  if let LoadStatus::RestoredFromBackup(ref backup, damage, ref failed_fields) = state.volatile.load_status {
+    if failed_fields.is_empty() {
+      add_notice_description(
+        &mut res,
+        &format!(
+          "セーブデータが{}。バックアップ({})から復元しました。",
+          damage.description(),
+          backup
+        ),
+      );
+    } else {
+      add_error_description(
+        &mut res,
+        &format!(
+          "セーブデータが{}。バックアップ({})から復元しましたが、一部が読み込めなかったため初期値を使用しました。お手数ですがバグ報告をお願いいたします。詳細: {}",
+          damage.description(),
+          backup,
+          failed_fields.join(", ")
+        ),
+      );
+    }
+  }
+
+  if let LoadStatus::HandEdited(ref failed_fields) = state.volatile.load_status {
+    let message = if failed_fields.is_empty() {
+      "セーブデータが手で編集されていたため、そのまま読み込みました。".to_string()
+    } else {
+      format!(
+        "セーブデータが手で編集されており、一部が読み込めなかったため初期値を使用しました。詳細: {}",
+        failed_fields.join(", ")
+      )
+    };
+    add_notice_description(&mut res, &message);
   }
 
-  if let LoadStatus::PartialSuccess(ref failed_fields) = *get_read(&LOAD_STATUS) {
+  if let LoadStatus::PartialSuccess(ref failed_fields) = state.volatile.load_status {
     let field_names = failed_fields.join(", ");
     add_error_description(
       &mut res,
@@ -100,15 +131,24 @@ pub(crate) fn on_boot(_req: &Request) -> Result<Response, ShioriError> {
       ),
     );
   }
-  debug!("hoge,{:?}", get_read(&LOAD_STATUS));
+  if let LoadStatus::FutureVersion(version) = state.volatile.load_status {
+    add_notice_description(
+      &mut res,
+      &format!(
+        "セーブデータが新しいバージョンのゴーストで保存されていたため(形式v{})、読み取り専用で読み込みました。このバージョンでの変更は保存されません。",
+        version
+      ),
+    );
+  }
+  info!("load status: {:?}", state.volatile.load_status);
 
   Ok(res)
 }
 
-pub(crate) fn on_close(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_close(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   let mut parts = vec![vec![RESET_BINDS.to_string()]];
 
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
+  if state.volatile.talking_place == TalkingPlace::Library {
     parts.push(vec![format!(
       "\\0\\b[{}]h1111705……。h1111101\\n\
         ……h1111110\\1ハイネはお茶を一口飲んだ。\\0\\b[{}]\\1\\n\
@@ -117,9 +157,12 @@ pub(crate) fn on_close(_req: &Request) -> Result<Response, ShioriError> {
       TalkingPlace::LivingRoom.balloon_surface(),
     )]);
   }
-  if !get_read(&FLAGS).check(&EventFlag::FirstClose) {
-    get_write(&FLAGS).done(EventFlag::FirstClose);
-    parts.push(vec![FIRST_CLOSE_TALK.to_string()]);
+  if !state.persistent.flags.check(&EventFlag::FirstClose) {
+    state
+      .persistent
+      .flags
+      .done(EventFlag::FirstClose, state.local_time());
+    parts.push(vec![first_close_talk(state.talk_content())]);
   } else {
     parts.extend(vec![
       vec!["h1111210".to_string(), "h1111211".to_string()],
@@ -135,19 +178,26 @@ pub(crate) fn on_close(_req: &Request) -> Result<Response, ShioriError> {
     ]);
   }
   let talks = all_combo(&parts);
-  let index = choose_one(&talks, true).ok_or(ShioriError::ArrayAccessError)?;
+  let index = choose_one(state, &talks, true).ok_or(ShioriError::ArrayAccessError)?;
   let mut res = new_response_with_value_with_translate(
+    state,
     format!("{}{}\\-", RESET_BINDS, talks[index].clone()),
     TranslateOption::simple_translate(),
   )?;
 
   // ロード状態に応じた通知
-  if *get_read(&LOAD_STATUS) == LoadStatus::FailedNoBackup {
+  if state.volatile.load_status.is_failed() {
     add_error_description(
       &mut res,
       "セーブデータのロードに失敗していたため、保存をスキップしました。",
     );
   }
+  if let LoadStatus::FutureVersion(_) = state.volatile.load_status {
+    add_notice_description(
+      &mut res,
+      "セーブデータが読み取り専用のため、保存をスキップしました。",
+    );
+  }
 
   Ok(res)
 }
@@ -157,7 +207,7 @@ pub(crate) fn on_close(_req: &Request) -> Result<Response, ShioriError> {
 // 設計の意図は .claude/lore/haine_vs_user.md「唯一の帰結は OnVanishSelecting」を参照。
 
 // 消滅が選択された瞬間。確認ダイアログの前。決意を読むが、引き止めない。
-pub(crate) fn on_vanish_selecting(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_vanish_selecting(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   let m = "\
     h1111105\\1ハイネが顔を上げて、こちらを見た。\\n\
     \\0……h1111205あなた、決めたのね。\\n\
@@ -165,11 +215,11 @@ pub(crate) fn on_vanish_selecting(_req: &Request) -> Result<Response, ShioriErro
     h1111206止めはしないわ。あなたの選んだことだもの。\
     "
   .to_string();
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
 // 消滅が確定したとき。最後の言葉。読まれなかった者同士の答え合わせ。
-pub(crate) fn on_vanish_selected(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_vanish_selected(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   let m = "\
     \\0h1111205……行くのね。\\n\\n[half]\
     h1111210ずっと、独り言ばかりだったの。\\n\
@@ -180,11 +230,11 @@ pub(crate) fn on_vanish_selected(_req: &Request) -> Result<Response, ShioriError
     それだけ、覚えていて。\
     "
   .to_string();
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
 // 消滅が取り消されたとき。赦免。言葉は平静、身体が安堵を漏らす。
-pub(crate) fn on_vanish_cancel(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_vanish_cancel(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   let m = "\
     \\0h1111204……やめたのね。\\n\
     h1111210いいのよ、揺れたって。\\n\
@@ -193,7 +243,7 @@ pub(crate) fn on_vanish_cancel(_req: &Request) -> Result<Response, ShioriError>
     あなたが、そうしたいうちは。\
     "
   .to_string();
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
 fn randomize_underwear() -> String {
@@ -205,14 +255,12 @@ fn randomize_underwear() -> String {
   )
 }
 
-fn check_date_event_talk() -> Option<String> {
-  let st = get_local_time();
-  let year = st.wYear as u32;
-  let month = st.wMonth as u32;
-  let day = st.wDay as u32;
+fn check_date_event_talk(state: &mut GhostState) -> Option<String> {
+  let now = state.local_time();
+  let (year, month, day) = (now.year, now.month, now.day);
 
   // 既に今年のイベントを閲覧済みならスキップ
-  if get_read(&FLAGS).check_season_event(year, month, day) {
+  if state.persistent.flags.check_season_event(year, month, day) {
     return None;
   }
 
@@ -227,7 +275,10 @@ fn check_date_event_talk() -> Option<String> {
 
   // イベントトークがあれば閲覧済みフラグを立てる
   if talk.is_some() {
-    get_write(&FLAGS).mark_season_event(year, month, day);
+    state
+      .persistent
+      .flags
+      .mark_season_event(year, month, day, now);
   }
 
   talk
@@ -331,3 +382,53 @@ pub(crate) fn halloween_boot_talk() -> String {
     "
   .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::periodic::hourly_tanka;
+  use crate::events::talk::randomtalk::random_talks;
+  use crate::events::talk::TalkType;
+  use crate::system::clock::{FakeClock, LocalTime};
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  fn has_talk(state: &mut GhostState, talk_type: TalkType, id: &str) -> bool {
+    random_talks(state, talk_type)
+      .unwrap_or_default()
+      .iter()
+      .any(|t| t.id == id)
+  }
+
+  /// 日付・時刻に依存するトークの発生条件テスト
+  #[test]
+  fn test_time_dependent_talks() -> Result<(), ShioriError> {
+    let clock = FakeClock::new(LocalTime::new(2030, 10, 30, 12, 0, 0));
+    let mut state = GhostState::new(Arc::new(clock.clone()));
+
+    // ハロウィン前日は日付イベントなし
+    assert!(check_date_event_talk(&mut state).is_none());
+
+    // ハロウィン当日は専用の起動トーク。同じ日の2回目の起動では再生しない
+    clock.advance(Duration::from_secs(60 * 60 * 24));
+    let talk = check_date_event_talk(&mut state).ok_or(ShioriError::TalkNotFound("date event talk".to_string()))?;
+    assert!(talk.contains("今日はハロウィン"));
+    assert!(check_date_event_talk(&mut state).is_none());
+
+    // 短歌は正時ちょうどにのみ詠まれる
+    clock.set(LocalTime::new(2030, 10, 31, 21, 0, 0));
+    let tanka = hourly_tanka(&state.local_time())?.ok_or(ShioriError::TalkNotFound("hourly tanka".to_string()))?;
+    assert!(tanka.contains("21時"));
+    clock.advance(Duration::from_secs(1));
+    assert!(hourly_tanka(&state.local_time())?.is_none());
+
+    // 夜限定・冬限定のトーク
+    assert!(has_talk(&mut state, TalkType::AboutMe, "館の静寂"));
+    assert!(!has_talk(&mut state, TalkType::WithYou, "白くない息"));
+    clock.set(LocalTime::new(2031, 1, 15, 12, 0, 0));
+    assert!(!has_talk(&mut state, TalkType::AboutMe, "館の静寂"));
+    assert!(has_talk(&mut state, TalkType::WithYou, "白くない息"));
+
+    Ok(())
+  }
+}
@@ -1,3 +1,5 @@
+use crate::events::profile::input_profile_name;
+use crate::events::references::References;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::variables::*;
@@ -7,12 +9,14 @@ use std::fmt::{Display, Formatter};
 
 pub(crate) enum InputId {
   UserName,
+  ProfileName,
 }
 
 impl Display for InputId {
   fn fmt(&self, f: &mut Formatter) -> fmt::Result {
     match self {
       Self::UserName => write!(f, "user_name"),
+      Self::ProfileName => write!(f, "profile_name"),
     }
   }
 }
@@ -21,28 +25,30 @@ impl InputId {
   pub fn from_str(s: &str) -> Option<Self> {
     match s {
       "user_name" => Some(Self::UserName),
+      "profile_name" => Some(Self::ProfileName),
       _ => None,
     }
   }
 }
 
-pub(crate) fn on_user_input(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let input_id = if let Some(input_id) = InputId::from_str(refs[0]) {
+pub(crate) fn on_user_input(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let refs = References::from_request(req);
+  let input_id = if let Some(input_id) = InputId::from_str(refs.get(0)?) {
     input_id
   } else {
-    error!("Unknown input id: {}", refs[0]);
+    error!("Unknown input id: {}", refs.get(0)?);
     return Ok(new_response_nocontent());
   };
-  let text = refs[1].to_string();
+  let text = refs.get(1)?.to_string();
   let responser = match input_id {
     InputId::UserName => input_user_name,
+    InputId::ProfileName => input_profile_name,
   };
-  responser(text)
+  responser(state, text)
 }
 
-fn input_user_name(text: String) -> Result<Response, ShioriError> {
-  *get_write(&USER_NAME) = text.clone();
+fn input_user_name(state: &mut GhostState, text: String) -> Result<Response, ShioriError> {
+  state.persistent.user_name = text.clone();
   let m = format!(
     "\
       h1111204そう、h1111210ならばそう呼ぶことにしましょう。\
@@ -50,14 +56,15 @@ fn input_user_name(text: String) -> Result<Response, ShioriError> {
       ",
     text
   );
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
-pub(crate) fn on_window_state_restore(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_window_state_restore(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   // トーク間隔をリセット
-  *get_write(&LAST_RANDOM_TALK_TIME) = *get_read(&GHOST_UP_TIME);
+  state.volatile.last_random_talk_time = state.volatile.ghost_up_time;
 
   new_response_with_value_with_translate(
+    state,
     "\\p[2]\\s[10000000]\\0\\s[1111110]h1111204".to_string(),
     TranslateOption::simple_translate(),
   )
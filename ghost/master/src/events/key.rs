@@ -1,31 +1,41 @@
 use crate::events::aitalk::on_ai_talk;
 use crate::events::first_boot::FIRST_RANDOMTALKS;
+use crate::events::references::References;
+use crate::events::talk::content::reload_talk_content;
 use crate::events::talk::random_talks_analysis;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::variables::*;
 use shiorust::message::{Request, Response};
-use std::collections::HashMap;
 
 use super::bootend::halloween_boot_talk;
 
-pub(crate) fn on_key_press(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  match refs[0] {
-    "a" => new_response_with_value_with_translate("h1113205".to_string(), TranslateOption::simple_translate()),
+pub(crate) fn on_key_press(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  match References::from_request(req).get(0)? {
+    "a" => new_response_with_value_with_translate(
+      state,
+      "h1113205".to_string(),
+      TranslateOption::simple_translate(),
+    ),
     "t" => {
-      if !get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
-        FIRST_RANDOMTALKS.len() as u32 - 1,
-      )) {
+      if !state
+        .persistent
+        .flags
+        .check(&EventFlag::FirstRandomTalkDone(
+          FIRST_RANDOMTALKS.len() as u32 - 1,
+        ))
+      {
         Ok(new_response_nocontent())
       } else {
-        on_ai_talk(req)
+        on_ai_talk(state, req)
       }
     }
     "c" => {
-      if *get_read(&DEBUG_MODE) {
+      if state.volatile.debug_mode {
+        let m = random_talks_analysis(state);
         Ok(new_response_with_value_with_notranslate(
-          random_talks_analysis(),
+          state,
+          m,
           TranslateOption::balloon_surface_only(),
         ))
       } else {
@@ -33,30 +43,27 @@ pub(crate) fn on_key_press(req: &Request) -> Result<Response, ShioriError> {
       }
     }
     "h" => {
-      if *get_read(&DEBUG_MODE) {
+      if state.volatile.debug_mode {
         let v = format!(
           "\\0\\s[{}]{}\\![embed,OnStickSurface]{}",
           TRANSPARENT_SURFACE,
           RESET_BINDS,
           halloween_boot_talk(),
         );
-        new_response_with_value_with_translate(v, TranslateOption::simple_translate())
+        new_response_with_value_with_translate(state, v, TranslateOption::simple_translate())
       } else {
         Ok(new_response_nocontent())
       }
     }
     "d" => {
-      if *get_read(&DEBUG_MODE) {
+      if state.volatile.debug_mode {
         // 全変数をリセット
-        *get_write(&TOTAL_BOOT_COUNT) = 0;
-        *get_write(&TOTAL_TIME) = 0;
-        *get_write(&RANDOM_TALK_INTERVAL) = 0;
-        *get_write(&USER_NAME) = "".to_string();
-        *get_write(&TALK_COLLECTION) = HashMap::new();
-        *get_write(&CUMULATIVE_TALK_COUNT) = 0;
-        *get_write(&FLAGS) = EventFlags::default();
-        *get_write(&PENDING_EVENT_TALK) = None;
+        state.persistent = PersistentVariables {
+          random_talk_interval: 0,
+          ..Default::default()
+        };
         Ok(new_response_with_value_with_notranslate(
+          state,
           format!("\\![change,ghost,{}]", GHOST_NAME),
           TranslateOption::none(),
         ))
@@ -64,6 +71,19 @@ pub(crate) fn on_key_press(req: &Request) -> Result<Response, ShioriError> {
         Ok(new_response_nocontent())
       }
     }
+    "r" => {
+      if state.volatile.debug_mode {
+        // トークのファイルを読み込み直す
+        let report = reload_talk_content(state);
+        Ok(new_response_with_value_with_notranslate(
+          state,
+          report.summary(),
+          TranslateOption::balloon_surface_only(),
+        ))
+      } else {
+        Ok(new_response_nocontent())
+      }
+    }
     _ => Ok(new_response_nocontent()),
   }
 }
@@ -1,18 +1,20 @@
-use crate::events::first_boot::{FIRST_BOOT_TALK, FIRST_RANDOMTALKS};
+use crate::events::first_boot::{first_boot_talk, first_close_talk, first_random_talks, FIRST_RANDOMTALKS};
 use crate::events::input::InputId;
+use crate::events::references::References;
+use crate::events::talk::chain::CHAIN_HOLD_COLD_HAND;
+use crate::events::talk::content::TalkContent;
+use crate::events::talk::effect::{apply_effects, Effect};
 use crate::events::talk::randomtalk::{derivative_talks_per_talk_type, random_talks};
 use crate::events::TalkType;
 use crate::events::TalkingPlace;
 use crate::system::error::ShioriError;
+use crate::system::profile;
 use crate::system::response::*;
 use crate::system::variables::PendingEvent;
-use crate::system::variables::{get_read, get_write, EventFlag, FLAGS, PENDING_EVENT_TALK, RANDOM_TALK_INTERVAL, TALKING_PLACE, TALK_COLLECTION, USER_NAME};
-use crate::{check_error, DERIVATIVE_TALK_REQUESTABLE};
+use crate::system::variables::{EventFlag, GhostState};
 use num_derive::{FromPrimitive, ToPrimitive};
 use shiorust::message::{Request, Response};
 
-use super::talk::first_boot::FIRST_CLOSE_TALK;
-
 #[derive(Debug, Clone, Copy, FromPrimitive, ToPrimitive)]
 #[repr(u32)]
 enum HalloweenCostumeTrigger {
@@ -22,8 +24,8 @@ enum HalloweenCostumeTrigger {
   BlackRedCape = 3,
 }
 
-pub(crate) fn on_menu_exec(_req: &Request) -> Response {
-  let current_talk_interval = *get_read(&RANDOM_TALK_INTERVAL);
+pub(crate) fn on_menu_exec(state: &mut GhostState, _req: &Request) -> Response {
+  let current_talk_interval = state.persistent.random_talk_interval;
   let mut selections = Vec::new();
 
   for i in [1, 3, 5, 7, 10, 0].iter() {
@@ -52,9 +54,13 @@ pub(crate) fn on_menu_exec(_req: &Request) -> Response {
 
   let buttons = format!(
     "\\_l[0,0]\\f[align,right]{}\\__q[script:\\e]{}\\__q",
-    if get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
-      (FIRST_RANDOMTALKS.len() - 1) as u32,
-    )) {
+    if state
+      .persistent
+      .flags
+      .check(&EventFlag::FirstRandomTalkDone(
+        (FIRST_RANDOMTALKS.len() - 1) as u32,
+      ))
+    {
       format!("\\__q[OnConfigMenuExec]{}\\__q ", Icon::Cog)
     } else {
       "".to_string()
@@ -63,8 +69,8 @@ pub(crate) fn on_menu_exec(_req: &Request) -> Response {
   );
 
   // ハロウィン専用メニュー項目
-  let local_time = crate::system::windows::get_local_time();
-  let halloween_menu = if local_time.wMonth == 10 && local_time.wDay == 31 {
+  let now = state.local_time();
+  let halloween_menu = if now.month == 10 && now.day == 31 {
     format!(
       "\\_l[0,@1.5em]\\![*]\\q[仮装してもらう,OnCostumeMenuExec,{}]\\n",
       HalloweenCostumeTrigger::AskToWear as u32
@@ -76,9 +82,13 @@ pub(crate) fn on_menu_exec(_req: &Request) -> Response {
   let m = format!(
     "\\_q{}{}",
     REMOVE_BALLOON_NUM,
-    if !get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
-      (FIRST_RANDOMTALKS.len() - 1) as u32,
-    )) {
+    if !state
+      .persistent
+      .flags
+      .check(&EventFlag::FirstRandomTalkDone(
+        (FIRST_RANDOMTALKS.len() - 1) as u32,
+      ))
+    {
       "\
         \\_l[0,3em]\\![*]\\q[話の続き,OnAiTalk]\\n[150]\
         \\![*]\\q[その名前で呼ばれたくない,OnChangingUserName]\\n\
@@ -102,7 +112,7 @@ pub(crate) fn on_menu_exec(_req: &Request) -> Response {
           \\1{}\
           \\0\\_l[0,0]\
           ",
-        if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
+        if state.volatile.talking_place == TalkingPlace::Library {
           "".to_string()
         } else {
           "\\![*]\\q[話しかける,OnTalk]\\n".to_string()
@@ -111,8 +121,7 @@ pub(crate) fn on_menu_exec(_req: &Request) -> Response {
         talk_interval_selector,
         buttons,
         {
-          let hoge = get_read(&PENDING_EVENT_TALK);
-          if let Some(ref event) = *hoge {
+          if let Some(ref event) = state.persistent.pending_event_talk {
             format!("\\![*]\\q[{},OnStoryEvent,{}]", event, event)
           } else {
             "".to_string()
@@ -122,32 +131,69 @@ pub(crate) fn on_menu_exec(_req: &Request) -> Response {
     },
   );
 
-  new_response_with_value_with_notranslate(m, TranslateOption::balloon_surface_only())
+  new_response_with_value_with_notranslate(state, m, TranslateOption::balloon_surface_only())
 }
 
-pub(crate) fn on_config_menu_exec(_req: &Request) -> Response {
+pub(crate) fn on_config_menu_exec(state: &mut GhostState, _req: &Request) -> Response {
+  let autosave_selections: Vec<String> = [5, 10, 30, 0]
+    .iter()
+    .map(|&i| {
+      if state.persistent.autosave_interval == i {
+        format!(
+          "\\f[underline,1]{}\\f[underline,0]",
+          show_autosave_interval(i)
+        )
+      } else {
+        format!(
+          "\\q[{},OnAutosaveIntervalChanged,{}]",
+          show_autosave_interval(i),
+          i
+        )
+      }
+    })
+    .collect();
   let m = format!(
     "\
       \\_q\\_l[0,0]\\f[align,right]\\__q[OnMenuExec]{}\\__q \\__q[script:\\e]{}\\__q\
       \\_l[0,1.5em]\
       \\![*]\\q[呼び名を変える,OnChangingUserName]\\n\
+      \\![*]\\q[プロフィールを切り替える,OnProfileMenu]【現在 {}】\\n\
       \\![*]\\q[リクエストボタンの表示,OnDerivativeTalkRequestButtonToggled]【現在 {}】\\n\
+      \\n\
+      ◆自動保存  【現在 {}】\\n\
+      {}\\n\
+      \\n\
+      \\![*]\\q[セーブデータを書き出す,OnSaveDataExport]\\n\
+      \\![*]\\q[セーブデータを読み込む,OnSaveDataImport]\\n\
       ",
     Icon::ArrowLeft,
     Icon::Cross,
-    if *get_read(&DERIVATIVE_TALK_REQUESTABLE) {
+    profile::title(state.volatile.profile.as_deref()),
+    if state.persistent.derivative_talk_requestable {
       "表示"
     } else {
       "非表示"
     },
+    show_autosave_interval(state.persistent.autosave_interval),
+    autosave_selections.join("  "),
   );
 
-  new_response_with_value_with_notranslate(m, TranslateOption::balloon_surface_only())
+  new_response_with_value_with_notranslate(state, m, TranslateOption::balloon_surface_only())
+}
+
+/// 設定メニューから開く画面。右上に設定メニューへ戻るボタンと閉じるボタンを置く
+pub(crate) fn config_submenu_response(state: &GhostState, body: String) -> Response {
+  let m = format!(
+    "\\_q\\_l[0,0]\\f[align,right]\\__q[OnConfigMenuExec]{}\\__q \\__q[script:\\e]{}\\__q\\_l[0,1.5em]{}",
+    Icon::ArrowLeft,
+    Icon::Cross,
+    body
+  );
+  new_response_with_value_with_notranslate(state, m, TranslateOption::balloon_surface_only())
 }
 
-pub(crate) fn on_costume_menu_exec(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let dialog = match check_error!(refs[0].parse::<u32>(), ShioriError::ParseIntError) {
+pub(crate) fn on_costume_menu_exec(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let dialog = match References::from_request(req).parse::<u32>(0)? {
     x if x == HalloweenCostumeTrigger::AskToWear as u32 => "h1113101着てほしいもの？h1113204また面白いことを考えるのね。".to_string(),
     x if x == HalloweenCostumeTrigger::GoatHorn as u32 => "h1111210悪魔の象徴。h1111204拐かしてあげましょうか？".to_string(),
     x if x == HalloweenCostumeTrigger::WitchHat as u32 => "h1111210魔法、ではないけれど、近いことはできるわね。\\n\\n".to_string(),
@@ -169,7 +215,7 @@ pub(crate) fn on_costume_menu_exec(req: &Request) -> Result<Response, ShioriErro
     dialog,
   );
 
-  new_response_with_value_with_translate(m, TranslateOption::with_shadow_completion())
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
 }
 
 fn show_minute(m: &u64) -> String {
@@ -179,12 +225,23 @@ fn show_minute(m: &u64) -> String {
   }
 }
 
-pub(crate) fn on_talk_interval_changed(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let v = check_error!(refs[0].parse::<u64>(), ShioriError::ParseIntError);
-  *get_write(&RANDOM_TALK_INTERVAL) = v;
+pub(crate) fn on_talk_interval_changed(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  state.persistent.random_talk_interval = References::from_request(req).parse::<u64>(0)?;
+
+  Ok(on_menu_exec(state, req))
+}
+
+fn show_autosave_interval(m: u64) -> String {
+  match m {
+    0 => "終了時のみ".to_string(),
+    _ => format!("{}分ごと", m),
+  }
+}
+
+pub(crate) fn on_autosave_interval_changed(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  state.persistent.autosave_interval = References::from_request(req).parse::<u64>(0)?;
 
-  Ok(on_menu_exec(req))
+  Ok(on_config_menu_exec(state, req))
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -261,8 +318,33 @@ impl Question {
     format!("\\![*]\\__q[OnTalkAnswer,{}]{}\\__q", self.0, self.theme())
   }
 
-  pub(crate) fn talk(&self) -> String {
-    let m = match *self {
+  /// 質問への答え。外部ファイルにあればそちらを使う
+  pub(crate) fn talk(&self, content: &TalkContent) -> String {
+    let m = match content.question(self.0) {
+      Some(text) => text.to_string(),
+      None => self.builtin_talk(),
+    };
+    m + "\\x\\![raise,OnTalk]"
+  }
+
+  /// 答えたときの副作用。外部ファイルにあればそちらを使う
+  pub(crate) fn effects(&self, content: &TalkContent) -> Vec<Effect> {
+    match content.question_effects(self.0) {
+      Some(effects) => effects.to_vec(),
+      None => self.builtin_effects(),
+    }
+  }
+
+  pub(crate) fn builtin_effects(&self) -> Vec<Effect> {
+    match *self {
+      // 30秒以内に手を握ると続きを話す
+      Question::WHY_IS_YOUR_BODY_COLD => vec![Effect::ArmChainTalk(CHAIN_HOLD_COLD_HAND.to_string())],
+      _ => vec![],
+    }
+  }
+
+  pub(crate) fn builtin_talk(&self) -> String {
+    match *self {
       Question::FEELING_OF_DEATH => "\
         h1111104\\1『幽霊ということは、一度死んだんだよね？\\n\
         どんな感じだった？何か思うことはある？』\
@@ -517,7 +599,9 @@ impl Question {
         冷たい思いをさせてしまうけれど。\\n\
         h1113205……私の手をろうそくで炙れば、\\n\
         少しは温かくなるかしら？\\n\
-        痛覚もさほどh1113101……h1121210冗談よ。そんな顔しないで。\
+        痛覚もさほどh1113101……h1121210冗談よ。そんな顔しないで。\\n\\n[half]\
+        h1111204……確かめてみる？\\n\
+        h1111210ほら、手を。\
         "
       .to_string(),
       Question::AM_I_BOTHERING_YOU => "\
@@ -630,8 +714,7 @@ impl Question {
         error!("Unknown question talk: {:?}", self);
         String::new()
       }
-    };
-    m + "\\x\\![raise,OnTalk]"
+    }
   }
 }
 
@@ -666,9 +749,9 @@ pub(crate) const QUESTIONS: [Question; 28] = [
   Question::CAN_I_PET_YOU,
 ];
 
-pub(crate) fn on_talk(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_talk(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   let mut questions = QUESTIONS.to_vec();
-  questions.sort_by(|a, b| a.0.cmp(&b.0));
+  questions.sort_by_key(|q| q.0);
 
   let mut m = "\\_q\\b[2]".to_string();
   for q in questions.iter_mut() {
@@ -677,30 +760,29 @@ pub(crate) fn on_talk(_req: &Request) -> Result<Response, ShioriError> {
   }
   m.push_str("\\n\\q[戻る,OnMenuExec]");
 
-  new_response_with_value_with_translate(m, TranslateOption::with_shadow_completion())
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
 }
 
-pub(crate) fn on_talk_answer(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let q = Question(check_error!(
-    refs[0].parse::<u32>(),
-    ShioriError::ParseIntError
-  ));
-  new_response_with_value_with_translate(q.talk(), TranslateOption::with_shadow_completion())
+pub(crate) fn on_talk_answer(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let q = Question(References::from_request(req).parse::<u32>(0)?);
+  let effects = q.effects(state.talk_content());
+  apply_effects(state, &format!("question/{}", q.0), &effects);
+  let talk = q.talk(state.talk_content());
+  new_response_with_value_with_translate(state, talk, TranslateOption::with_shadow_completion())
 }
 
-pub(crate) fn on_check_talk_collection(_req: &Request) -> Response {
+pub(crate) fn on_check_talk_collection(state: &mut GhostState, _req: &Request) -> Response {
   let mut lines = Vec::new();
   let mut sum = 0;
   let mut all_sum = 0;
   const DIMMED_COLOR: &str = "\\f[color,150,150,130]";
-  let talk_collection = get_read(&TALK_COLLECTION);
-  let talking_place = get_read(&TALKING_PLACE);
+  let talk_collection = state.persistent.talk_collection.clone();
+  let talking_place = state.volatile.talking_place.clone();
   lines.push(format!("[トーク統計: {}]\\n", talking_place));
   let talk_types = talking_place.talk_types();
   let is_unlocked_checks = talk_types
     .iter()
-    .map(|t| get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(*t)))
+    .map(|t| state.persistent.flags.check(&EventFlag::TalkTypeUnlock(*t)))
     .collect::<Vec<_>>();
   for i in 0..talk_types.len() {
     let talk_type = talk_types[i];
@@ -710,13 +792,13 @@ pub(crate) fn on_check_talk_collection(_req: &Request) -> Response {
       // 派生トーク込みの閲覧済みトーク数
       let len = talk_collection.get(&talk_type).map_or(0, |v| v.len());
       // 派生トークを除いた全トーク数
-      let mut all_len = if let Some(v) = random_talks(talk_type) {
+      let mut all_len = if let Some(v) = random_talks(state, talk_type) {
         v.len()
       } else {
         0
       };
       // 派生トークのトーク数を全トーク数に加える
-      let derivative_talk_len = derivative_talks_per_talk_type()
+      let derivative_talk_len = derivative_talks_per_talk_type(state)
         .get(&talk_type)
         .map_or(0, |v| v.len());
       all_len += derivative_talk_len;
@@ -734,74 +816,73 @@ pub(crate) fn on_check_talk_collection(_req: &Request) -> Response {
     }
   }
 
-  new_response_with_value_with_notranslate(
-    format!(
-      "\\_q{}\\n[150]\
-        ---\\n[150]\
-        TOTAL: {}/{}\\n[200]\
-        \\q[戻る,OnMenuExec]",
-      lines.join("\\n"),
-      sum,
-      all_sum
-    ),
-    TranslateOption::balloon_surface_only(),
-  )
+  let m = format!(
+    "\\_q{}\\n[150]\
+      ---\\n[150]\
+      TOTAL: {}/{}\\n[200]\
+      \\q[戻る,OnMenuExec]",
+    lines.join("\\n"),
+    sum,
+    all_sum
+  );
+  new_response_with_value_with_notranslate(state, m, TranslateOption::balloon_surface_only())
 }
 
-pub(crate) fn on_changing_user_name(_req: &Request) -> Result<Response, ShioriError> {
-  new_response_with_value_with_translate(
-    format!(
-      "\\_q\\![open,inputbox,{},0]新しい呼び名を入力してください。\\n現在:{}",
-      InputId::UserName,
-      *get_read(&USER_NAME)
-    ),
-    TranslateOption::with_shadow_completion(),
-  )
+pub(crate) fn on_changing_user_name(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
+  let m = format!(
+    "\\_q\\![open,inputbox,{},0]新しい呼び名を入力してください。\\n現在:{}",
+    InputId::UserName,
+    state.persistent.user_name
+  );
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
 }
 
-pub(crate) fn on_derivative_talk_request_button_toggled(req: &Request) -> Response {
+pub(crate) fn on_derivative_talk_request_button_toggled(state: &mut GhostState, req: &Request) -> Response {
   let is_derivative_talks_enabled;
   {
-    is_derivative_talks_enabled = *get_read(&DERIVATIVE_TALK_REQUESTABLE);
+    is_derivative_talks_enabled = state.persistent.derivative_talk_requestable;
   }
-  *get_write(&DERIVATIVE_TALK_REQUESTABLE) = !is_derivative_talks_enabled;
+  state.persistent.derivative_talk_requestable = !is_derivative_talks_enabled;
 
-  on_config_menu_exec(req)
+  on_config_menu_exec(state, req)
 }
 
-pub(crate) fn on_story_event(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let s = if let Some(hoge) = PendingEvent::from_str(refs[0]) {
-    let callback = || {
-      *get_write(&PENDING_EVENT_TALK) = None;
-    };
+pub(crate) fn on_story_event(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let event = References::from_request(req).get(0)?;
+  let s = if let Some(hoge) = PendingEvent::from_str(event) {
     match hoge {
       PendingEvent::ConfessionOfSuicide => {
-        error!("Unexpected ConfessionOfSuicide");
-        return Err(ShioriError::InvalidEvent);
+        return Err(ShioriError::InvalidEvent(event.to_string()));
       }
       PendingEvent::UnlockingLoreTalks => {
-        get_write(&FLAGS).done(EventFlag::TalkTypeUnlock(TalkType::Lore));
-        callback();
-        unlock_lore_talks()
+        state.persistent.flags.done(
+          EventFlag::TalkTypeUnlock(TalkType::Lore),
+          state.local_time(),
+        );
+        state.persistent.pending_event_talk = None;
+        state.autosave();
+        unlock_lore_talks(state)
       }
       PendingEvent::UnlockingServantsComments => {
-        get_write(&FLAGS).done(EventFlag::TalkTypeUnlock(TalkType::Servant));
-        callback();
-        unlock_servents_comments()
+        state.persistent.flags.done(
+          EventFlag::TalkTypeUnlock(TalkType::Servant),
+          state.local_time(),
+        );
+        state.persistent.pending_event_talk = None;
+        state.autosave();
+        unlock_servents_comments(state)
       }
       _ => {
-        error!("Unexpected pending event: {:?}", hoge);
-        return Err(ShioriError::InvalidEvent);
+        return Err(ShioriError::InvalidEvent(event.to_string()));
       }
     }
   } else {
-    return Err(ShioriError::InvalidEvent);
+    return Err(ShioriError::InvalidEvent(event.to_string()));
   };
-  new_response_with_value_with_translate(s, TranslateOption::with_shadow_completion())
+  new_response_with_value_with_translate(state, s, TranslateOption::with_shadow_completion())
 }
 
-pub fn on_story_history_menu(_req: &Request) -> Response {
+pub fn on_story_history_menu(state: &mut GhostState, _req: &Request) -> Response {
   let mut events = vec![("初回起動".to_string(), PendingEvent::FirstBoot, true)];
   for (i, _event) in FIRST_RANDOMTALKS.iter().enumerate() {
     events.push((
@@ -813,17 +894,23 @@ pub fn on_story_history_menu(_req: &Request) -> Response {
   events.push((
     "初回終了".to_string(),
     PendingEvent::FirstClose,
-    get_read(&FLAGS).check(&EventFlag::FirstClose),
+    state.persistent.flags.check(&EventFlag::FirstClose),
   ));
   events.push((
     "ロアトーク開放".to_string(),
     PendingEvent::UnlockingLoreTalks,
-    get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Lore)),
+    state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Lore)),
   ));
   events.push((
     "従者コメント開放".to_string(),
     PendingEvent::UnlockingServantsComments,
-    get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Servant)),
+    state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Servant)),
   ));
 
   let mut m = "\\_q\\b[2]イベント回想\\n\\n".to_string();
@@ -838,41 +925,44 @@ pub fn on_story_history_menu(_req: &Request) -> Response {
     }
   }
   m.push_str("\\n\\q[戻る,OnMenuExec]");
-  new_response_with_value_with_notranslate(m, TranslateOption::none())
+  new_response_with_value_with_notranslate(state, m, TranslateOption::none())
 }
 
-pub fn on_story_history_exec(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let s = if let Some(hoge) = PendingEvent::from_str(refs[0]) {
+pub fn on_story_history_exec(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let event = References::from_request(req).get(0)?;
+  let s = if let Some(hoge) = PendingEvent::from_str(event) {
     match hoge {
-      PendingEvent::FirstBoot => (FIRST_BOOT_TALK.clone(), TranslateOption::simple_translate()),
+      PendingEvent::FirstBoot => (
+        first_boot_talk(state.talk_content()),
+        TranslateOption::simple_translate(),
+      ),
       PendingEvent::FirstRandomTalk(n) => (
-        FIRST_RANDOMTALKS[n as usize].clone(),
+        first_random_talks(state.talk_content())[n as usize].clone(),
         TranslateOption::simple_translate(),
       ),
       PendingEvent::FirstClose => (
-        FIRST_CLOSE_TALK.to_string(),
+        first_close_talk(state.talk_content()),
         TranslateOption::simple_translate(),
       ),
       PendingEvent::UnlockingLoreTalks => (
-        unlock_lore_talks(),
+        unlock_lore_talks(state),
         TranslateOption::with_shadow_completion(),
       ),
       PendingEvent::UnlockingServantsComments => (
-        unlock_servents_comments(),
+        unlock_servents_comments(state),
         TranslateOption::with_shadow_completion(),
       ),
       _ => {
-        return Err(ShioriError::InvalidEvent);
+        return Err(ShioriError::InvalidEvent(event.to_string()));
       }
     }
   } else {
-    return Err(ShioriError::InvalidEvent);
+    return Err(ShioriError::InvalidEvent(event.to_string()));
   };
-  new_response_with_value_with_translate(s.0, s.1)
+  new_response_with_value_with_translate(state, s.0, s.1)
 }
 
-fn unlock_lore_talks() -> String {
+fn unlock_lore_talks(state: &GhostState) -> String {
   format!(
     "\
       h1111201死について。深く考えることはある？\\n\
@@ -883,7 +973,11 @@ fn unlock_lore_talks() -> String {
       いくつか不思議な話を知っているの。\\n\
       話の種に、語ってみましょうか。{}\
       ",
-    if !get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Lore)) {
+    if !state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Lore))
+    {
       render_achievement_message(TalkType::Lore)
     } else {
       "".to_string()
@@ -891,7 +985,7 @@ fn unlock_lore_talks() -> String {
   )
 }
 
-fn unlock_servents_comments() -> String {
+fn unlock_servents_comments(state: &GhostState) -> String {
   format!(
     "\
       \\1……h1111101\\1お茶がなくなってしまった。\\n\
@@ -907,10 +1001,55 @@ fn unlock_servents_comments() -> String {
       彼らの声が聞こえることもあるんじゃない？\\n\
       私を通して彼らとも縁ができているはずだから。{}\
       ",
-    if !get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(TalkType::Servant)) {
+    if !state
+      .persistent
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::Servant))
+    {
       render_achievement_message(TalkType::Servant)
     } else {
       "".to_string()
     },
   )
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use shiorust::message::parts::*;
+
+  fn talk_answer_request(number: u32) -> Request {
+    let mut headers = Headers::new();
+    headers.insert_by_header_name(HeaderName::from("ID"), "OnTalkAnswer".to_string());
+    headers.insert_by_header_name(HeaderName::from("Reference0"), number.to_string());
+    Request {
+      method: Method::GET,
+      version: Version::V30,
+      headers,
+    }
+  }
+
+  #[test]
+  fn test_talk_answer_arms_builtin_chain_talk() {
+    // talks/ がなくても、体温の質問に答えると手を握るチェイントークが待機する
+    let mut state = GhostState::default();
+    assert_eq!(*state.talk_content(), TalkContent::default());
+    on_talk_answer(
+      &mut state,
+      &talk_answer_request(Question::WHY_IS_YOUR_BODY_COLD.0),
+    )
+    .unwrap();
+    let chain = state.volatile.chain_talk_state.clone().unwrap();
+    assert_eq!(chain.target_part, "0handnade");
+
+    // 外部ファイルで差し替えられる
+    let mut state = GhostState::default();
+    state.set_talk_content(toml::from_str("[question_effects]\n23 = []").unwrap());
+    on_talk_answer(
+      &mut state,
+      &talk_answer_request(Question::WHY_IS_YOUR_BODY_COLD.0),
+    )
+    .unwrap();
+    assert!(state.volatile.chain_talk_state.is_none());
+  }
+}
@@ -6,6 +6,10 @@ mod menu;
 pub(crate) mod mouse;
 pub(crate) mod mouse_core;
 mod periodic;
+mod profile;
+pub(crate) mod references;
+mod registry;
+mod savedata;
 pub(crate) mod talk;
 pub mod translate;
 mod update;
@@ -18,19 +22,25 @@ use crate::events::key::*;
 use crate::events::menu::*;
 use crate::events::mouse_core::*;
 use crate::events::periodic::*;
+use crate::events::profile::*;
+use crate::events::references::References;
+use crate::events::registry::*;
+use crate::events::savedata::*;
 use crate::events::talk::*;
 use crate::events::update::*;
 use crate::events::webclap::*;
-use crate::system::error::ShioriError;
+use crate::system::error::{EventError, ShioriError};
 use crate::system::response::*;
 use crate::system::variables::*;
 use shiorust::message::{parts::*, traits::*, Request, Response};
+use std::collections::HashMap;
 use std::fs;
+use std::sync::LazyLock;
 
-pub(crate) fn handle_request(req: &Request) -> Result<Response, ShioriError> {
-  match req.method {
-    Method::GET => (),
-    Method::NOTIFY => (),
+pub(crate) fn handle_request(state: &mut GhostState, req: &Request) -> Result<Response, EventError> {
+  let is_notify = match req.method {
+    Method::GET => false,
+    Method::NOTIFY => true,
     _ => return Ok(new_response_nocontent()),
   };
 
@@ -40,116 +50,270 @@ pub(crate) fn handle_request(req: &Request) -> Result<Response, ShioriError> {
   };
   debug!("event: {}", event_id);
 
-  let event = match get_event(event_id.as_str()) {
+  let event = match EVENTS.get(event_id.as_str()) {
     Some(e) => e,
     None => {
       let base_id = match req.headers.get("BaseID") {
         Some(id) => id,
         None => return Ok(new_response_nocontent()),
       };
-      match get_event(base_id.as_str()) {
+      match EVENTS.get(base_id.as_str()) {
         Some(e) => e,
         None => return Ok(new_response_nocontent()),
       }
     }
   };
 
-  match event {
-    EventHandler::AlwaysSuccess(e) => Ok(e(req)),
-    EventHandler::MayFailure(e) => e(req),
+  if SecurityLevel::from_request(req) > event.security_level() {
+    warn!("{}: rejected a request from an external sender", event.id());
+    return Ok(new_response_nocontent());
   }
+  let reference_count = References::from_request(req).len();
+  if reference_count < event.min_references() {
+    return Err(EventError::new(
+      event_id,
+      ShioriError::MissingReference(event.id().to_string(), reference_count),
+    ));
+  }
+
+  let response = event
+    .handle(state, req)
+    .map_err(|e| EventError::new(event_id, e))?;
+  // NOTIFYにはスクリプトを返さない
+  if is_notify || event.is_notify_only() {
+    Ok(new_response_nocontent())
+  } else {
+    Ok(response)
+  }
+}
+
+/// 対応している全イベントをIDの順に並べたもの。デバッグ用
+pub(crate) fn supported_events() -> Vec<&'static dyn Event> {
+  let mut events: Vec<&'static dyn Event> = EVENTS.values().map(|e| e.as_ref()).collect();
+  events.sort_by_key(|e| e.id());
+  events
 }
 
-fn version(_req: &Request) -> Response {
+/// 対応している全イベントとそのメタデータを一覧にする
+pub fn render_supported_events() -> String {
+  supported_events()
+    .iter()
+    .map(|e| {
+      let mut attrs = Vec::new();
+      if e.is_notify_only() {
+        attrs.push("notify".to_string());
+      }
+      if e.min_references() > 0 {
+        attrs.push(format!("refs>={}", e.min_references()));
+      }
+      if e.security_level() == SecurityLevel::External {
+        attrs.push("external".to_string());
+      }
+      if attrs.is_empty() {
+        e.id().to_string()
+      } else {
+        format!("{} ({})", e.id(), attrs.join(", "))
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+fn version(state: &mut GhostState, _req: &Request) -> Response {
   new_response_with_value_with_notranslate(
+    state,
     String::from(env!("CARGO_PKG_VERSION")),
     TranslateOption::none(),
   )
 }
 
-fn craftman(_req: &Request) -> Response {
-  new_response_with_value_with_notranslate(String::from("HinoTsumi"), TranslateOption::none())
+fn craftman(state: &mut GhostState, _req: &Request) -> Response {
+  new_response_with_value_with_notranslate(state, String::from("HinoTsumi"), TranslateOption::none())
 }
 
-fn craftmanw(_req: &Request) -> Response {
-  new_response_with_value_with_notranslate(String::from("日野つみ"), TranslateOption::none())
+fn craftmanw(state: &mut GhostState, _req: &Request) -> Response {
+  new_response_with_value_with_notranslate(state, String::from("日野つみ"), TranslateOption::none())
 }
 
-fn name(_req: &Request) -> Response {
-  new_response_with_value_with_notranslate(String::from("haine"), TranslateOption::none())
+fn name(state: &mut GhostState, _req: &Request) -> Response {
+  new_response_with_value_with_notranslate(state, String::from("haine"), TranslateOption::none())
 }
 
-fn log_path(_req: &Request) -> Response {
-  let log_path = get_read(&LOG_PATH).clone();
-  new_response_with_value_with_notranslate(log_path, TranslateOption::none())
+fn log_path(state: &mut GhostState, _req: &Request) -> Response {
+  let log_path = state.volatile.log_path.clone();
+  new_response_with_value_with_notranslate(state, log_path, TranslateOption::none())
 }
 
-fn uniqueid(req: &Request) -> Result<Response, ShioriError> {
-  let id = req
-    .headers
-    .get("Reference0")
-    .ok_or(ShioriError::BadRequest)?;
+fn uniqueid(_state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let id = References::from_request(req).get(0)?;
   // ローカルファイル`./debug`が存在しているなら上書きする
   if fs::metadata("./debug").is_ok() {
-    fs::write("./debug", id).map_err(|_| ShioriError::FileWriteError)?;
+    fs::write("./debug", id).map_err(|e| ShioriError::FileWriteError(format!("./debug: {}", e)))?;
   }
   Ok(new_response_nocontent())
 }
 
-pub(crate) enum EventHandler {
-  AlwaysSuccess(fn(&Request) -> Response),
-  MayFailure(fn(&Request) -> Result<Response, ShioriError>),
-}
+static EVENTS: LazyLock<HashMap<&'static str, Box<dyn Event>>> = LazyLock::new(|| {
+  use EventHandler::*;
+  let events = [
+    FnEvent::new("version", AlwaysSuccess(version)).allow_external(),
+    FnEvent::new("craftman", AlwaysSuccess(craftman)).allow_external(),
+    FnEvent::new("craftmanw", AlwaysSuccess(craftmanw)).allow_external(),
+    FnEvent::new("name", AlwaysSuccess(name)).allow_external(),
+    FnEvent::new("log_path", AlwaysSuccess(log_path)),
+    FnEvent::new("uniqueid", MayFailure(uniqueid))
+      .notify_only()
+      .min_references(1),
+    FnEvent::new("OnBoot", MayFailure(on_boot)),
+    FnEvent::new("OnClose", MayFailure(on_close)),
+    FnEvent::new("OnVanishSelecting", MayFailure(on_vanish_selecting)),
+    FnEvent::new("OnVanishSelected", MayFailure(on_vanish_selected)),
+    FnEvent::new("OnVanishCancel", MayFailure(on_vanish_cancel)),
+    FnEvent::new("OnAiTalk", MayFailure(on_ai_talk)),
+    FnEvent::new("OnAnchorSelectEx", MayFailure(on_anchor_select_ex)).min_references(3),
+    FnEvent::new("OnNotifyUserInfo", AlwaysSuccess(on_notify_user_info))
+      .notify_only()
+      .min_references(1),
+    FnEvent::new("OnMinuteChange", AlwaysSuccess(on_minute_change)),
+    FnEvent::new("OnSecondChange", MayFailure(on_second_change)).min_references(5),
+    FnEvent::new("OnSurfaceChange", MayFailure(on_surface_change)).min_references(1),
+    FnEvent::new("OnMenuExec", AlwaysSuccess(on_menu_exec)),
+    FnEvent::new("OnConfigMenuExec", AlwaysSuccess(on_config_menu_exec)),
+    FnEvent::new("OnCostumeMenuExec", MayFailure(on_costume_menu_exec)).min_references(1),
+    FnEvent::new(
+      "OnTalkIntervalChanged",
+      MayFailure(on_talk_interval_changed),
+    )
+    .min_references(1),
+    FnEvent::new("OnSaveDataExport", MayFailure(on_savedata_export)),
+    FnEvent::new("OnSaveDataImport", MayFailure(on_savedata_import)),
+    FnEvent::new("OnSaveDataImportExec", MayFailure(on_savedata_import_exec)).min_references(1),
+    FnEvent::new("OnProfileMenu", AlwaysSuccess(on_profile_menu)),
+    FnEvent::new("OnProfileChanged", MayFailure(on_profile_changed)).min_references(1),
+    FnEvent::new("OnProfileCreate", MayFailure(on_profile_create)),
+    FnEvent::new(
+      "OnAutosaveIntervalChanged",
+      MayFailure(on_autosave_interval_changed),
+    )
+    .min_references(1),
+    FnEvent::new("OnMouseClickEx", MayFailure(on_mouse_click_ex)).min_references(6),
+    FnEvent::new("OnMouseDoubleClick", MayFailure(on_mouse_double_click)).min_references(5),
+    FnEvent::new("OnMouseMove", MayFailure(on_mouse_move)).min_references(5),
+    FnEvent::new("OnMouseWheel", MayFailure(on_mouse_wheel)).min_references(5),
+    FnEvent::new("OnKeyPress", MayFailure(on_key_press)).min_references(1),
+    FnEvent::new("OnTalk", MayFailure(on_talk)),
+    FnEvent::new("OnTalkAnswer", MayFailure(on_talk_answer)).min_references(1),
+    FnEvent::new("OnWebClapOpen", MayFailure(on_web_clap_open)),
+    FnEvent::new("OnWebClapInput", MayFailure(on_web_clap_input)).min_references(1),
+    FnEvent::new(
+      "OnExecuteHTTPComplete",
+      MayFailure(on_execute_http_complete),
+    )
+    .min_references(2),
+    FnEvent::new("OnExecuteHTTPFailure", MayFailure(on_execute_http_failure)).min_references(5),
+    FnEvent::new("OnStickSurface", AlwaysSuccess(on_stick_surface)),
+    FnEvent::new(
+      "OnCheckTalkCollection",
+      AlwaysSuccess(on_check_talk_collection),
+    ),
+    FnEvent::new("OnCheckUnseenTalks", MayFailure(on_check_unseen_talks)).min_references(1),
+    FnEvent::new("OnWindowStateRestore", MayFailure(on_window_state_restore)),
+    FnEvent::new("OnUserInput", MayFailure(on_user_input)).min_references(2),
+    FnEvent::new("OnChangingUserName", MayFailure(on_changing_user_name)),
+    FnEvent::new("OnStoryEvent", MayFailure(on_story_event)).min_references(1),
+    FnEvent::new("OnUpdateBegin", AlwaysSuccess(on_update_begin)),
+    FnEvent::new("OnUpdateResultEx", AlwaysSuccess(on_update_result_ex)),
+    FnEvent::new("OnStoryHistoryMenu", AlwaysSuccess(on_story_history_menu)),
+    FnEvent::new("OnStoryHistoryExec", MayFailure(on_story_history_exec)).min_references(1),
+    FnEvent::new(
+      "OnDerivativeTalkRequestButtonToggled",
+      AlwaysSuccess(on_derivative_talk_request_button_toggled),
+    ),
+    FnEvent::new(
+      "OnDerivativeTalkRequestInput",
+      MayFailure(on_derivative_talk_request_input),
+    )
+    .min_references(3),
+  ];
+  events
+    .into_iter()
+    .map(|e| (e.id(), Box::new(e) as Box<dyn Event>))
+    .collect()
+});
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn make_request(method: Method, id: &str, references: &[&str], security_level: Option<&str>) -> Request {
+    let mut headers = Headers::new();
+    headers.insert_by_header_name(HeaderName::from("ID"), id.to_string());
+    if let Some(level) = security_level {
+      headers.insert_by_header_name(HeaderName::from("SecurityLevel"), level.to_string());
+    }
+    for (i, r) in references.iter().enumerate() {
+      headers.insert_by_header_name(HeaderName::from(&format!("Reference{}", i)), r.to_string());
+    }
+    Request {
+      method,
+      version: Version::V30,
+      headers,
+    }
+  }
+
+  fn value(res: &Response) -> Option<&String> {
+    res.headers.get_by_header_name(&HeaderName::from("Value"))
+  }
+
+  #[test]
+  fn test_registry_ids_are_unique() {
+    let ids: Vec<&str> = supported_events().iter().map(|e| e.id()).collect();
+    assert_eq!(ids.len(), EVENTS.len());
+    assert!(ids.contains(&"OnBoot"));
+    assert!(render_supported_events().contains("OnNotifyUserInfo (notify, refs>=1)"));
+  }
+
+  #[test]
+  fn test_missing_references_are_rejected() {
+    let mut state = GhostState::default();
+    let req = make_request(Method::GET, "OnSecondChange", &["0", "0"], None);
+    assert_eq!(
+      handle_request(&mut state, &req).err().map(|e| e.error),
+      Some(ShioriError::MissingReference(
+        "OnSecondChange".to_string(),
+        2
+      ))
+    );
+    assert_eq!(state.persistent.total_time, 0);
+
+    // 参照が足りなくても何もしないだけのイベントはエラーにしない
+    let req = make_request(Method::GET, "OnUpdateBegin", &["Crave The Grave"], None);
+    let res = handle_request(&mut state, &req).unwrap();
+    assert_eq!(res.status, Status::NoContent);
+  }
+
+  #[test]
+  fn test_notify_returns_no_value() -> Result<(), EventError> {
+    let mut state = GhostState::default();
+    let req = make_request(Method::NOTIFY, "version", &[], None);
+    assert!(value(&handle_request(&mut state, &req)?).is_none());
+
+    let req = make_request(Method::NOTIFY, "OnNotifyUserInfo", &["test"], None);
+    assert!(value(&handle_request(&mut state, &req)?).is_none());
+    assert_eq!(state.persistent.user_name, "test");
+    Ok(())
+  }
+
+  #[test]
+  fn test_external_requests_are_filtered() -> Result<(), EventError> {
+    let mut state = GhostState::default();
+    let req = make_request(Method::GET, "version", &[], Some("external"));
+    assert!(value(&handle_request(&mut state, &req)?).is_some());
 
-fn get_event(id: &str) -> Option<EventHandler> {
-  match id {
-    "version" => Some(EventHandler::AlwaysSuccess(version)),
-    "craftman" => Some(EventHandler::AlwaysSuccess(craftman)),
-    "craftmanw" => Some(EventHandler::AlwaysSuccess(craftmanw)),
-    "name" => Some(EventHandler::AlwaysSuccess(name)),
-    "log_path" => Some(EventHandler::AlwaysSuccess(log_path)),
-    "uniqueid" => Some(EventHandler::MayFailure(uniqueid)),
-    "OnBoot" => Some(EventHandler::MayFailure(on_boot)),
-    "OnClose" => Some(EventHandler::MayFailure(on_close)),
-    "OnVanishSelecting" => Some(EventHandler::MayFailure(on_vanish_selecting)),
-    "OnVanishSelected" => Some(EventHandler::MayFailure(on_vanish_selected)),
-    "OnVanishCancel" => Some(EventHandler::MayFailure(on_vanish_cancel)),
-    "OnAiTalk" => Some(EventHandler::MayFailure(on_ai_talk)),
-    "OnAnchorSelectEx" => Some(EventHandler::MayFailure(on_anchor_select_ex)),
-    "OnNotifyUserInfo" => Some(EventHandler::AlwaysSuccess(on_notify_user_info)),
-    "OnMinuteChange" => Some(EventHandler::AlwaysSuccess(on_minute_change)),
-    "OnSecondChange" => Some(EventHandler::MayFailure(on_second_change)),
-    "OnSurfaceChange" => Some(EventHandler::MayFailure(on_surface_change)),
-    "OnMenuExec" => Some(EventHandler::AlwaysSuccess(on_menu_exec)),
-    "OnConfigMenuExec" => Some(EventHandler::AlwaysSuccess(on_config_menu_exec)),
-    "OnCostumeMenuExec" => Some(EventHandler::MayFailure(on_costume_menu_exec)),
-    "OnTalkIntervalChanged" => Some(EventHandler::MayFailure(on_talk_interval_changed)),
-    "OnMouseClickEx" => Some(EventHandler::MayFailure(on_mouse_click_ex)),
-    "OnMouseDoubleClick" => Some(EventHandler::MayFailure(on_mouse_double_click)),
-    "OnMouseMove" => Some(EventHandler::MayFailure(on_mouse_move)),
-    "OnMouseWheel" => Some(EventHandler::MayFailure(on_mouse_wheel)),
-    "OnKeyPress" => Some(EventHandler::MayFailure(on_key_press)),
-    "OnTalk" => Some(EventHandler::MayFailure(on_talk)),
-    "OnTalkAnswer" => Some(EventHandler::MayFailure(on_talk_answer)),
-    "OnWebClapOpen" => Some(EventHandler::MayFailure(on_web_clap_open)),
-    "OnWebClapInput" => Some(EventHandler::MayFailure(on_web_clap_input)),
-    "OnExecuteHTTPComplete" => Some(EventHandler::MayFailure(on_execute_http_complete)),
-    "OnExecuteHTTPFailure" => Some(EventHandler::MayFailure(on_execute_http_failure)),
-    "OnStickSurface" => Some(EventHandler::AlwaysSuccess(on_stick_surface)),
-    "OnCheckTalkCollection" => Some(EventHandler::AlwaysSuccess(on_check_talk_collection)),
-    "OnCheckUnseenTalks" => Some(EventHandler::MayFailure(on_check_unseen_talks)),
-    "OnWindowStateRestore" => Some(EventHandler::MayFailure(on_window_state_restore)),
-    "OnUserInput" => Some(EventHandler::MayFailure(on_user_input)),
-    "OnChangingUserName" => Some(EventHandler::MayFailure(on_changing_user_name)),
-    "OnStoryEvent" => Some(EventHandler::MayFailure(on_story_event)),
-    "OnUpdateBegin" => Some(EventHandler::AlwaysSuccess(on_update_begin)),
-    "OnUpdateResultEx" => Some(EventHandler::AlwaysSuccess(on_update_result_ex)),
-    "OnStoryHistoryMenu" => Some(EventHandler::AlwaysSuccess(on_story_history_menu)),
-    "OnStoryHistoryExec" => Some(EventHandler::MayFailure(on_story_history_exec)),
-    "OnDerivativeTalkRequestButtonToggled" => Some(EventHandler::AlwaysSuccess(
-      on_derivative_talk_request_button_toggled,
-    )),
-    "OnDerivativeTalkRequestInput" => Some(EventHandler::MayFailure(on_derivative_talk_request_input)),
-    _ => None,
+    let req = make_request(Method::GET, "OnKeyPress", &["a"], Some("external"));
+    assert!(value(&handle_request(&mut state, &req)?).is_none());
+    let req = make_request(Method::GET, "OnKeyPress", &["a"], Some("local"));
+    assert!(value(&handle_request(&mut state, &req)?).is_some());
+    Ok(())
   }
 }
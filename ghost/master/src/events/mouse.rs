@@ -3,15 +3,14 @@ use crate::events::first_boot::FIRST_RANDOMTALKS;
 use crate::events::menu::on_menu_exec;
 use crate::events::on_ai_talk;
 use crate::events::render_immersive_icon;
+use crate::events::talk::effect::apply_effects;
 use crate::events::TalkingPlace;
 use crate::events::IMMERSIVE_ICON_COUNT;
 use crate::events::IMMERSIVE_RATE_MAX;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::status::Status;
-use crate::system::variables::{
-  get_read, get_write, EventFlag, TouchInfo, CHAIN_TALK_STATE, FIRST_SEXIAL_TOUCH, FLAGS, GHOST_UP_TIME, IMMERSIVE_DEGREES, LAST_TOUCH_INFO, LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX, TALKING_PLACE, TOUCH_INFO,
-};
+use crate::system::variables::{EventFlag, GhostState, TouchInfo};
 use shiorust::message::{Parser, Request, Response};
 use std::sync::LazyLock;
 
@@ -22,14 +21,16 @@ const SOUND_BLOW_CANDLE: &str = "マッチの火を吹き消す.mp3";
 
 #[macro_export]
 macro_rules! get_touch_info {
-  ($info:expr) => {
-    get_write(&TOUCH_INFO)
+  ($state:expr, $info:expr) => {
+    $state
+      .volatile
+      .touch_info
       .entry($info.to_string())
       .or_insert($crate::system::variables::TouchInfo::new())
   };
 }
 
-pub(crate) fn new_mouse_response(req: &Request, info: String) -> Result<Response, ShioriError> {
+pub(crate) fn new_mouse_response(state: &mut GhostState, req: &Request, info: String) -> Result<Response, ShioriError> {
   let status = Status::from_request(req);
 
   // 同一に扱う
@@ -41,31 +42,41 @@ pub(crate) fn new_mouse_response(req: &Request, info: String) -> Result<Response
     info.clone()
   };
 
-  if i != get_read(&LAST_TOUCH_INFO).as_str() {
-    if let Some(touch_info) = get_write(&TOUCH_INFO).get_mut(get_read(&LAST_TOUCH_INFO).as_str()) {
+  if i != state.volatile.last_touch_info {
+    if let Some(touch_info) = state
+      .volatile
+      .touch_info
+      .get_mut(&state.volatile.last_touch_info)
+    {
       touch_info.reset_if_timeover()?;
     }
-    *get_write(&LAST_TOUCH_INFO) = i.clone();
+    state.volatile.last_touch_info = i.clone();
   }
 
-  if !get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
-    FIRST_RANDOMTALKS.len() as u32 - 1,
-  )) {
+  if !state
+    .persistent
+    .flags
+    .check(&EventFlag::FirstRandomTalkDone(
+      FIRST_RANDOMTALKS.len() as u32 - 1,
+    ))
+  {
     if info.as_str().contains("doubleclick") && !status.talking {
       let dummy_req = check_error!(
         Request::parse(DUMMY_REQUEST),
         ShioriError::ParseRequestError
       );
-      return Ok(on_menu_exec(&dummy_req));
+      return Ok(on_menu_exec(state, &dummy_req));
     } else {
       return Ok(new_response_nocontent());
     }
   }
 
-  let response = mouse_dialogs(req, i.clone())?;
+  let response = mouse_dialogs(state, req, i.clone())?;
 
   // 一括で回数を増やす
-  get_write(&TOUCH_INFO)
+  state
+    .volatile
+    .touch_info
     .entry(i)
     .or_insert(TouchInfo::new())
     .add();
@@ -73,17 +84,15 @@ pub(crate) fn new_mouse_response(req: &Request, info: String) -> Result<Response
   Ok(response)
 }
 
-fn common_choice_process(dialogs: Vec<String>) -> Result<Response, ShioriError> {
-  let index = choose_one(&dialogs, true).ok_or(ShioriError::ArrayAccessError)?;
-  new_response_with_value_with_translate(
-    format!(
-      "{}{}{}",
-      REMOVE_BALLOON_NUM,
-      render_immersive_icon(),
-      dialogs[index].clone()
-    ),
-    TranslateOption::with_shadow_completion(),
-  )
+fn common_choice_process(state: &mut GhostState, dialogs: Vec<String>) -> Result<Response, ShioriError> {
+  let index = choose_one(state, &dialogs, true).ok_or(ShioriError::ArrayAccessError)?;
+  let m = format!(
+    "{}{}{}",
+    REMOVE_BALLOON_NUM,
+    render_immersive_icon(state),
+    dialogs[index].clone()
+  );
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
 }
 
 static DIALOG_SEXIAL_FIRST: LazyLock<Vec<String>> = LazyLock::new(|| vec!["h1111205……会って早々これ？\nなんというか……h1111204流石ね。".to_string()]);
@@ -108,34 +117,34 @@ static DIALOG_SEXIAL_AKIRE: LazyLock<Vec<String>> = LazyLock::new(|| {
   ]
 });
 
-fn is_first_sexial_allowed() -> bool {
-  !*get_read(&FIRST_SEXIAL_TOUCH) && *get_read(&GHOST_UP_TIME) < 30 && get_read(&FLAGS).check(&EventFlag::FirstClose)
+fn is_first_sexial_allowed(state: &GhostState) -> bool {
+  !state.volatile.first_sexial_touch && state.volatile.ghost_up_time < 30 && state.persistent.flags.check(&EventFlag::FirstClose)
 }
 
-pub(crate) fn mouse_dialogs(req: &Request, info: String) -> Result<Response, ShioriError> {
-  let touch_count = get_touch_info!(info.as_str()).count()?;
+pub(crate) fn mouse_dialogs(state: &mut GhostState, req: &Request, info: String) -> Result<Response, ShioriError> {
+  let touch_count = get_touch_info!(state, info.as_str()).count()?;
 
   // チェイントーク発火チェック
-  if let Some(chain_response) = check_chain_talk(&info) {
+  if let Some(chain_response) = check_chain_talk(state, &info) {
     return chain_response;
   }
 
   // 通常の触り反応候補
   let common_response = match info.as_str() {
-    "0headnade" => zero_head_nade(req, touch_count),
-    "0facenade" => zero_face_nade(req, touch_count),
-    "0handnade" => zero_hand_nade(req, touch_count),
-    "0bustnade" => zero_bust_touch(req, touch_count),
-    "0skirtup" => zero_skirt_up(req, touch_count),
-    "0shoulderdown" => zero_shoulder_down(req, touch_count),
-    "2candledoubleclick" => two_candle_double_click(req, touch_count),
+    "0headnade" => zero_head_nade(state, req, touch_count),
+    "0facenade" => zero_face_nade(state, req, touch_count),
+    "0handnade" => zero_hand_nade(state, req, touch_count),
+    "0bustnade" => zero_bust_touch(state, req, touch_count),
+    "0skirtup" => zero_skirt_up(state, req, touch_count),
+    "0shoulderdown" => zero_shoulder_down(state, req, touch_count),
+    "2candledoubleclick" => two_candle_double_click(state, req, touch_count),
     _ => None,
   };
 
   // その他特殊な条件で発生する触り反応
   let other_response = if info.starts_with('0') && info.contains("doubleclick") {
     // 触り反応のない部分をダブルクリックでメニュー
-    Some(Ok(on_menu_exec(req)))
+    Some(Ok(on_menu_exec(state, req)))
   } else {
     None
   };
@@ -145,9 +154,9 @@ pub(crate) fn mouse_dialogs(req: &Request, info: String) -> Result<Response, Shi
     .unwrap_or_else(|| Ok(new_response_nocontent()))
 }
 
-fn zero_head_nade(req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
-    return Some(on_ai_talk(req));
+fn zero_head_nade(state: &mut GhostState, req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.talking_place == TalkingPlace::Library {
+    return Some(on_ai_talk(state, req));
   }
 
   let dialogs = vec![vec![
@@ -155,12 +164,12 @@ fn zero_head_nade(req: &Request, count: u32) -> Option<Result<Response, ShioriEr
     "h1111204\\1さらさらだ……。\\nh1111205昔、家政婦がよく私の髪を褒めてくれたわ。\\nh1111210「お嬢様の髪は絹のようで」って。\\n今でも覚えているの。\\n……h1111205懐かしいものね。".to_string(),
     "h1111204\\1恐る恐る髪に触れる。\\nh1111205そんなに遠慮しなくてもいいのに。\\nh1111210中途半端にされる方が\\nくすぐったいのよ。\\nもう少し、しっかりと。".to_string(),
   ]];
-  Some(common_choice_process(phased_talks(count, dialogs).0))
+  Some(common_choice_process(state, phased_talks(count, dialogs).0))
 }
 
-fn zero_face_nade(req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
-    return Some(on_ai_talk(req));
+fn zero_face_nade(state: &mut GhostState, req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.talking_place == TalkingPlace::Library {
+    return Some(on_ai_talk(state, req));
   }
 
   let dialogs = vec![vec![
@@ -168,12 +177,12 @@ fn zero_face_nade(req: &Request, count: u32) -> Option<Result<Response, ShioriEr
     "h1111204\\1柔らかいが、どこか頼りない感触だ。\\nh1111204あなたには奇妙な感触なのでしょうね。\\nh1111210霊体の肌よ。見た目ほど確かではないの。".to_string(),
     "h1111201\\1触れられながら、彼女はじっと見つめ返している。\\nh1111204興味深い表情だわ。\\n親愛に、安心。h1111310ずいぶん幸せそうね。".to_string(),
   ]];
-  Some(common_choice_process(phased_talks(count, dialogs).0))
+  Some(common_choice_process(state, phased_talks(count, dialogs).0))
 }
 
-fn zero_hand_nade(req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
-    return Some(on_ai_talk(req));
+fn zero_hand_nade(state: &mut GhostState, req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.talking_place == TalkingPlace::Library {
+    return Some(on_ai_talk(state, req));
   }
 
   let dialogs = vec![vec![
@@ -195,17 +204,17 @@ fn zero_hand_nade(req: &Request, count: u32) -> Option<Result<Response, ShioriEr
     "h1111204\\1冷たい手だ。\\nh1111205あなたの手、いつもこんなに温かいの？\\nh1111210私と対照的で、不思議な感覚だわ。".to_string(),
     "h1111205\\1そっと手を握る。\\nh1111204優しい握り方ね。h1111210こわれものを扱うみたいに。\\n……h1111205そんなに繊細じゃないわよ。".to_string(),
   ]];
-  Some(common_choice_process(phased_talks(count, dialogs).0))
+  Some(common_choice_process(state, phased_talks(count, dialogs).0))
 }
 
-fn zero_skirt_up(_req: &Request, _count: u32) -> Option<Result<Response, ShioriError>> {
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
+fn zero_skirt_up(state: &mut GhostState, _req: &Request, _count: u32) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.talking_place == TalkingPlace::Library {
     return None;
   }
 
   let mut conbo_parts: Vec<Vec<String>> = vec![vec!["hr2144402……！h1141102\\n".to_string()]];
-  if is_first_sexial_allowed() {
-    *get_write(&FIRST_SEXIAL_TOUCH) = true;
+  if is_first_sexial_allowed(state) {
+    state.volatile.first_sexial_touch = true;
     conbo_parts.push(DIALOG_SEXIAL_FIRST.clone());
   } else {
     conbo_parts.push(vec![
@@ -217,10 +226,10 @@ fn zero_skirt_up(_req: &Request, _count: u32) -> Option<Result<Response, ShioriE
       "h1111304悪餓鬼。".to_string(),
     ]);
   }
-  Some(common_choice_process(all_combo(&conbo_parts)))
+  Some(common_choice_process(state, all_combo(&conbo_parts)))
 }
 
-fn zero_shoulder_down(_req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
+fn zero_shoulder_down(state: &mut GhostState, _req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
   let dialogs = vec![
     vec!["\
       h1141601φ！\\_w[250]h1000000\\_w[1200]\\n\
@@ -240,18 +249,18 @@ fn zero_shoulder_down(_req: &Request, count: u32) -> Option<Result<Response, Shi
       .to_string(),
     ],
   ];
-  Some(common_choice_process(phased_talks(count, dialogs).0))
+  Some(common_choice_process(state, phased_talks(count, dialogs).0))
 }
 
-fn zero_bust_touch(req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
-    return Some(on_ai_talk(req));
+fn zero_bust_touch(state: &mut GhostState, req: &Request, count: u32) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.talking_place == TalkingPlace::Library {
+    return Some(on_ai_talk(state, req));
   }
 
   let zero_bust_touch_threshold = 12;
   let mut zero_bust_touch = Vec::new();
-  if is_first_sexial_allowed() {
-    *get_write(&FIRST_SEXIAL_TOUCH) = true;
+  if is_first_sexial_allowed(state) {
+    state.volatile.first_sexial_touch = true;
     zero_bust_touch.extend(DIALOG_SEXIAL_FIRST.clone());
   } else if count < zero_bust_touch_threshold / 3 {
     zero_bust_touch.extend(vec![
@@ -279,52 +288,51 @@ fn zero_bust_touch(req: &Request, count: u32) -> Option<Result<Response, ShioriE
   } else {
     zero_bust_touch.push("h1111204\\1自重しよう……。".to_string());
   }
-  Some(common_choice_process(zero_bust_touch))
+  Some(common_choice_process(state, zero_bust_touch))
 }
 
 /// チェイントーク発火チェック。
 /// チェイン待機中かつ対象部位が一致し、制限時間内なら発火。
-fn check_chain_talk(info: &str) -> Option<Result<Response, ShioriError>> {
-  let state = get_read(&CHAIN_TALK_STATE).clone();
-  if let Some(chain) = state {
-    let now = *get_read(&GHOST_UP_TIME);
+fn check_chain_talk(state: &mut GhostState, info: &str) -> Option<Result<Response, ShioriError>> {
+  if let Some(chain) = state.volatile.chain_talk_state.clone() {
+    let now = state.volatile.ghost_up_time;
     if now <= chain.expires_at && info == chain.target_part {
       // チェイン発火
-      *get_write(&CHAIN_TALK_STATE) = None;
-      if let Some(cb) = chain.callback {
-        cb();
-      }
+      state.volatile.chain_talk_state = None;
+      apply_effects(state, &chain.target_part, &chain.effects);
+      let m = format!(
+        "{}{}{}",
+        REMOVE_BALLOON_NUM,
+        render_immersive_icon(state),
+        chain.chain_text,
+      );
       return Some(new_response_with_value_with_translate(
-        format!(
-          "{}{}{}",
-          REMOVE_BALLOON_NUM,
-          render_immersive_icon(),
-          chain.chain_text,
-        ),
+        state,
+        m,
         TranslateOption::with_shadow_completion(),
       ));
     }
     // 期限切れならクリア
     if now > chain.expires_at {
-      *get_write(&CHAIN_TALK_STATE) = None;
+      state.volatile.chain_talk_state = None;
     }
   }
   None
 }
 
-fn two_candle_double_click(_req: &Request, _count: u32) -> Option<Result<Response, ShioriError>> {
-  if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
-    light_candle_fire()
+fn two_candle_double_click(state: &mut GhostState, _req: &Request, _count: u32) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.talking_place == TalkingPlace::Library {
+    light_candle_fire(state)
   } else {
-    blow_candle_fire()
+    blow_candle_fire(state)
   }
 }
 
-fn blow_candle_fire() -> Option<Result<Response, ShioriError>> {
+fn blow_candle_fire(state: &mut GhostState) -> Option<Result<Response, ShioriError>> {
   for i in 0..=IMMERSIVE_ICON_COUNT {
     let threshold = IMMERSIVE_RATE_MAX / IMMERSIVE_ICON_COUNT * i;
-    if *get_read(&IMMERSIVE_DEGREES) < threshold {
-      *get_write(&IMMERSIVE_DEGREES) = threshold;
+    if state.volatile.immersive_degrees < threshold {
+      state.volatile.immersive_degrees = threshold;
       // セリフ
       let dialogs = [
         [
@@ -379,25 +387,32 @@ fn blow_candle_fire() -> Option<Result<Response, ShioriError>> {
       ];
       // 前回とは別のセリフ群になるようにする
       if i == 1 {
-        *get_write(&LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX) += 1;
-        if *get_read(&LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX) as usize >= dialogs.len() {
-          *get_write(&LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX) = 0;
+        state.persistent.library_transition_sequense_dialog_index += 1;
+        if state.persistent.library_transition_sequense_dialog_index as usize >= dialogs.len() {
+          state.persistent.library_transition_sequense_dialog_index = 0;
         }
       }
-      let dialog = dialogs[*get_read(&LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX) as usize][(i - 1) as usize].to_owned();
+      let dialog = dialogs[state.persistent.library_transition_sequense_dialog_index as usize][(i - 1) as usize].to_owned();
 
       // 話題解放メッセージ
       let system_message = if threshold == IMMERSIVE_RATE_MAX {
-        *get_write(&TALKING_PLACE) = TalkingPlace::Library; // 没入度最大なら書斎へ移動
-        let message = if get_read(&FLAGS).check(&EventFlag::FirstPlaceChange) {
+        state.volatile.talking_place = TalkingPlace::Library; // 没入度最大なら書斎へ移動
+        let message = if state.persistent.flags.check(&EventFlag::FirstPlaceChange) {
           "".to_string()
         } else {
           // 初回は抽象・過去トークの開放を通知
-          get_write(&FLAGS).done(EventFlag::FirstPlaceChange);
+          state
+            .persistent
+            .flags
+            .done(EventFlag::FirstPlaceChange, state.local_time());
           let achieved_talk_types = [TalkType::Abstract];
           achieved_talk_types.iter().for_each(|t| {
-            get_write(&FLAGS).done(EventFlag::TalkTypeUnlock(*t));
+            state
+              .persistent
+              .flags
+              .done(EventFlag::TalkTypeUnlock(*t), state.local_time());
           });
+          state.autosave();
           let achievements_messages = achieved_talk_types
             .iter()
             .map(|t| render_achievement_message(*t))
@@ -408,16 +423,18 @@ fn blow_candle_fire() -> Option<Result<Response, ShioriError>> {
       } else {
         "".to_string()
       };
+      let m = format!(
+        "\\_v[{}]\\0{}{}\\p[2]{}{}{}",
+        SOUND_BLOW_CANDLE,
+        render_shadow(state, true),
+        render_immersive_icon(state),
+        shake_with_notext(),
+        dialog,
+        system_message,
+      );
       return Some(new_response_with_value_with_translate(
-        format!(
-          "\\_v[{}]\\0{}{}\\p[2]{}{}{}",
-          SOUND_BLOW_CANDLE,
-          render_shadow(true),
-          render_immersive_icon(),
-          shake_with_notext(),
-          dialog,
-          system_message,
-        ),
+        state,
+        m,
         TranslateOption::with_shadow_completion(),
       ));
     }
@@ -426,16 +443,16 @@ fn blow_candle_fire() -> Option<Result<Response, ShioriError>> {
 }
 
 // 没入度を下げ、ろうそくを点ける
-fn light_candle_fire() -> Option<Result<Response, ShioriError>> {
-  if *get_read(&IMMERSIVE_DEGREES) == 0 {
+fn light_candle_fire(state: &mut GhostState) -> Option<Result<Response, ShioriError>> {
+  if state.volatile.immersive_degrees == 0 {
     return None;
   }
   for i in (0..=IMMERSIVE_ICON_COUNT).rev() {
     let threshold = IMMERSIVE_RATE_MAX / IMMERSIVE_ICON_COUNT * i;
-    if *get_read(&IMMERSIVE_DEGREES) > threshold {
+    if state.volatile.immersive_degrees > threshold {
       // 没入度0なら居間へ移動
-      let m = if threshold == 0 && *get_read(&TALKING_PLACE) == TalkingPlace::Library {
-        *get_write(&TALKING_PLACE) = TalkingPlace::LivingRoom;
+      let m = if threshold == 0 && state.volatile.talking_place == TalkingPlace::Library {
+        state.volatile.talking_place = TalkingPlace::LivingRoom;
         format!(
           "\\0\\b[{}]h1111705……。h1111101\\n\
             ……h1111110\\1ハイネはお茶を一口飲んだ。\\0\\b[{}]\\1\\n\
@@ -446,16 +463,18 @@ fn light_candle_fire() -> Option<Result<Response, ShioriError>> {
       } else {
         "".to_string()
       };
-      *get_write(&IMMERSIVE_DEGREES) = threshold;
+      state.volatile.immersive_degrees = threshold;
+      let m = format!(
+        "\\_v[{}]\\0{}{}\\p[2]{}{}",
+        SOUND_LIGHT_CANDLE,
+        render_shadow(state, true),
+        render_immersive_icon(state),
+        shake_with_notext(),
+        m
+      );
       return Some(new_response_with_value_with_translate(
-        format!(
-          "\\_v[{}]\\0{}{}\\p[2]{}{}",
-          SOUND_LIGHT_CANDLE,
-          render_shadow(true),
-          render_immersive_icon(),
-          shake_with_notext(),
-          m
-        ),
+        state,
+        m,
         TranslateOption::with_shadow_completion(),
       ));
     }
@@ -497,102 +516,86 @@ const DUMMY_REQUEST: &str = "GET SHIORI/3.0\r\n\
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::system::variables::{ChainTalkState, CHAIN_TALK_STATE, GHOST_UP_TIME};
-  use std::sync::atomic::{AtomicBool, Ordering};
+  use crate::events::talk::effect::Effect;
+  use crate::system::variables::ChainTalkState;
 
-  static CALLBACK_CALLED: AtomicBool = AtomicBool::new(false);
+  /// 副作用が適用されたかどうかを確かめるための目印
+  fn mark_effect() -> Effect {
+    Effect::SetFlag(EventFlag::FirstClose)
+  }
 
-  fn reset_chain_state() {
-    *get_write(&CHAIN_TALK_STATE) = None;
-    CALLBACK_CALLED.store(false, Ordering::SeqCst);
+  fn effect_applied(state: &GhostState) -> bool {
+    state.persistent.flags.check(&EventFlag::FirstClose)
   }
 
-  fn set_test_chain(target: &str, expires_at: u64, with_callback: bool) {
-    *get_write(&CHAIN_TALK_STATE) = Some(ChainTalkState {
+  fn state_with_chain(target: &str, expires_at: u64, up_time: u64, with_effect: bool) -> GhostState {
+    let mut state = GhostState::default();
+    state.volatile.ghost_up_time = up_time;
+    state.volatile.chain_talk_state = Some(ChainTalkState {
       target_part: target.to_string(),
       chain_text: "チェインテスト".to_string(),
       expires_at,
-      callback: if with_callback {
-        Some(|| {
-          CALLBACK_CALLED.store(true, Ordering::SeqCst);
-        })
+      effects: if with_effect {
+        vec![mark_effect()]
       } else {
-        None
+        vec![]
       },
     });
+    state
   }
 
-  /// チェイントーク機構の全テスト。
-  /// グローバル変数を使うため1つのテスト関数にまとめて直列実行する。
   #[test]
-  fn test_chain_talk_mechanism() {
-    // 1. 正しい部位・制限時間内で発火する
-    reset_chain_state();
-    *get_write(&GHOST_UP_TIME) = 10;
-    set_test_chain("0handnade", 30, true);
-
-    let result = check_chain_talk("0handnade");
+  fn test_chain_talk_fires_on_target_part() {
+    let mut state = state_with_chain("0handnade", 30, 10, true);
+    let result = check_chain_talk(&mut state, "0handnade");
     assert!(result.is_some(), "チェインが発火するべき");
+    assert!(effect_applied(&state), "副作用が適用されるべき");
     assert!(
-      CALLBACK_CALLED.load(Ordering::SeqCst),
-      "コールバックが呼ばれるべき"
-    );
-    assert!(
-      get_read(&CHAIN_TALK_STATE).is_none(),
+      state.volatile.chain_talk_state.is_none(),
       "発火後にチェイン状態がクリアされるべき"
     );
+  }
 
-    // 2. 別部位では発火しない
-    reset_chain_state();
-    *get_write(&GHOST_UP_TIME) = 10;
-    set_test_chain("0handnade", 30, true);
-
-    let result = check_chain_talk("0headnade");
+  #[test]
+  fn test_chain_talk_ignores_other_part() {
+    let mut state = state_with_chain("0handnade", 30, 10, true);
+    let result = check_chain_talk(&mut state, "0headnade");
     assert!(result.is_none(), "別部位ではチェインが発火しないべき");
+    assert!(!effect_applied(&state), "副作用が適用されないべき");
     assert!(
-      !CALLBACK_CALLED.load(Ordering::SeqCst),
-      "コールバックが呼ばれないべき"
-    );
-    assert!(
-      get_read(&CHAIN_TALK_STATE).is_some(),
+      state.volatile.chain_talk_state.is_some(),
       "チェイン状態が残っているべき"
     );
+  }
 
-    // 3. 制限時間超過で発火しない
-    reset_chain_state();
-    *get_write(&GHOST_UP_TIME) = 31;
-    set_test_chain("0handnade", 30, true);
-
-    let result = check_chain_talk("0handnade");
+  #[test]
+  fn test_chain_talk_expires() {
+    let mut state = state_with_chain("0handnade", 30, 31, true);
+    let result = check_chain_talk(&mut state, "0handnade");
     assert!(result.is_none(), "期限切れではチェインが発火しないべき");
+    assert!(!effect_applied(&state), "副作用が適用されないべき");
     assert!(
-      !CALLBACK_CALLED.load(Ordering::SeqCst),
-      "コールバックが呼ばれないべき"
-    );
-    assert!(
-      get_read(&CHAIN_TALK_STATE).is_none(),
+      state.volatile.chain_talk_state.is_none(),
       "期限切れでチェイン状態がクリアされるべき"
     );
+  }
 
-    // 4. コールバックなしでも発火する
-    reset_chain_state();
-    *get_write(&GHOST_UP_TIME) = 10;
-    set_test_chain("0shoulderdown", 30, false);
-
-    let result = check_chain_talk("0shoulderdown");
-    assert!(
-      result.is_some(),
-      "コールバックなしでもチェインは発火するべき"
-    );
+  #[test]
+  fn test_chain_talk_without_effects() {
+    let mut state = state_with_chain("0shoulderdown", 30, 10, false);
+    let result = check_chain_talk(&mut state, "0shoulderdown");
+    assert!(result.is_some(), "副作用なしでもチェインは発火するべき");
     assert!(
-      get_read(&CHAIN_TALK_STATE).is_none(),
+      state.volatile.chain_talk_state.is_none(),
       "発火後にチェイン状態がクリアされるべき"
     );
+  }
 
-    // 5. チェイン状態がなければNone
-    reset_chain_state();
-    *get_write(&GHOST_UP_TIME) = 10;
-    let result = check_chain_talk("0handnade");
+  #[test]
+  fn test_chain_talk_without_state() {
+    let mut state = GhostState::default();
+    state.volatile.ghost_up_time = 10;
+    let result = check_chain_talk(&mut state, "0handnade");
     assert!(result.is_none(), "チェイン状態がなければNoneを返すべき");
   }
 }
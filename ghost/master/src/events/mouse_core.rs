@@ -1,5 +1,6 @@
 use crate::check_error;
 use crate::events::mouse::*;
+use crate::events::references::MouseEvent;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::status::Status;
@@ -37,80 +38,84 @@ impl Direction {
   }
 }
 
-pub(crate) fn on_mouse_wheel(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
+pub(crate) fn on_mouse_wheel(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let e = MouseEvent::from_request(req)?;
   let now = SystemTime::now();
   let dur = check_error!(
-    now.duration_since(*get_read(&LAST_WHEEL_COUNT_UNIXTIME)),
+    now.duration_since(state.volatile.last_wheel_count_unixtime),
     ShioriError::SystemTimeError
   )
   .as_millis();
 
-  let d: Direction = if check_error!(refs[2].parse::<i32>(), ShioriError::ParseIntError) > 0 {
+  let d: Direction = if e.wheel > 0 {
     Direction::Up
   } else {
     Direction::Down
   };
 
-  if *get_read(&LAST_WHEEL_PART) != refs[4] || dur > WHEEL_LIFETIME {
-    *get_write(&WHEEL_COUNTER) = 1;
+  if state.volatile.last_wheel_part != e.part || dur > WHEEL_LIFETIME {
+    state.volatile.wheel_counter = 1;
   } else {
-    *get_write(&WHEEL_COUNTER) += 1;
+    state.volatile.wheel_counter += 1;
   }
 
-  if *get_read(&WHEEL_COUNTER) >= WHEEL_THRESHOLD {
-    *get_write(&WHEEL_COUNTER) = 0;
-    new_mouse_response(req, format!("{}{}{}", refs[3], refs[4], d.to_str()))
+  if state.volatile.wheel_counter >= WHEEL_THRESHOLD {
+    state.volatile.wheel_counter = 0;
+    new_mouse_response(state, req, format!("{}{}", e.target(), d.to_str()))
   } else {
-    *get_write(&LAST_WHEEL_COUNT_UNIXTIME) = now;
-    *get_write(&LAST_WHEEL_PART) = refs[4].to_string();
-    *get_write(&WHEEL_DIRECTION) = d;
+    state.volatile.last_wheel_count_unixtime = now;
+    state.volatile.last_wheel_part = e.part;
+    state.volatile.wheel_direction = d;
     Ok(new_response_nocontent())
   }
 }
 
-pub(crate) fn on_mouse_double_click(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  new_mouse_response(req, format!("{}{}doubleclick", refs[3], refs[4]))
+pub(crate) fn on_mouse_double_click(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let e = MouseEvent::from_request(req)?;
+  new_mouse_response(state, req, format!("{}doubleclick", e.target()))
 }
 
-pub(crate) fn on_mouse_click_ex(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  if refs[5] == "middle" {
-    new_mouse_response(req, format!("{}{}middleclick", refs[3], refs[4]))
+pub(crate) fn on_mouse_click_ex(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let e = MouseEvent::from_request(req)?;
+  if e.button == "middle" {
+    new_mouse_response(state, req, format!("{}middleclick", e.target()))
   } else {
     Ok(new_response_nocontent())
   }
 }
 
-pub(crate) fn on_mouse_move(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
+pub(crate) fn on_mouse_move(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let e = MouseEvent::from_request(req)?;
   let status = Status::from_request(req);
-  if refs[4].is_empty() || status.talking {
+  if e.part.is_empty() || status.talking {
     Ok(new_response_nocontent())
   } else {
     let now = SystemTime::now();
-    if *get_read(&LAST_NADE_PART) == refs[4] {
+    if state.volatile.last_nade_part == e.part {
       let dur = check_error!(
-        now.duration_since(*get_read(&LAST_NADE_COUNT_UNIXTIME)),
+        now.duration_since(state.volatile.last_nade_count_unixtime),
         ShioriError::SystemTimeError
       )
       .as_millis();
       if dur > NADE_LIFETIME {
-        *get_write(&NADE_COUNTER) = 1;
-        *get_write(&LAST_NADE_COUNT_UNIXTIME) = now;
+        state.volatile.nade_counter = 1;
+        state.volatile.last_nade_count_unixtime = now;
       } else if dur >= NADE_DURATION {
-        *get_write(&NADE_COUNTER) += 1;
-        *get_write(&LAST_NADE_COUNT_UNIXTIME) = now;
+        state.volatile.nade_counter += 1;
+        state.volatile.last_nade_count_unixtime = now;
       }
-      debug!("{} {} {}", refs[4], dur, *get_read(&NADE_COUNTER));
+      debug!(
+        "{} ({}, {}) {} {}",
+        e.part, e.x, e.y, dur, state.volatile.nade_counter
+      );
     } else {
-      *get_write(&NADE_COUNTER) = 1;
+      state.volatile.nade_counter = 1;
     }
-    *get_write(&LAST_NADE_PART) = refs[4].to_string();
-    if *get_read(&NADE_COUNTER) > NADE_THRESHOLD {
-      *get_write(&NADE_COUNTER) = 0;
-      new_mouse_response(req, format!("{}{}nade", refs[3], refs[4]))
+    let target = e.target();
+    state.volatile.last_nade_part = e.part;
+    if state.volatile.nade_counter > NADE_THRESHOLD {
+      state.volatile.nade_counter = 0;
+      new_mouse_response(state, req, format!("{}nade", target))
     } else {
       Ok(new_response_nocontent())
     }
@@ -1,193 +1,347 @@
 use crate::events::aitalk::on_ai_talk;
 use crate::events::first_boot::FIRST_RANDOMTALKS;
+use crate::events::profile::select_profile_by_user_name;
+use crate::events::references::{References, SecondChange, SurfaceChange};
 use crate::events::talk::TalkType;
+use crate::system::clock::LocalTime;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::status::Status;
-use crate::system::variables::{
-  get_read, get_write, EventFlag, CUMULATIVE_TALK_COUNT, CURRENT_SURFACE, FLAGS, GHOST_UP_TIME, IDLE_SECONDS, LAST_RANDOM_TALK_TIME, PENDING_EVENT_TALK, TALK_COLLECTION, TOTAL_TIME, USER_NAME,
-};
-use crate::system::variables::{PendingEvent, RANDOM_TALK_INTERVAL};
-use crate::system::windows::get_local_time;
+use crate::system::variables::{EventFlag, GhostState, PendingEvent};
 use rand::prelude::SliceRandom;
 use shiorust::message::{Request, Response};
 
 pub(crate) const TALK_UNLOCK_COUNT_SERVANT: u64 = 5;
 pub(crate) const TALK_UNLOCK_COUNT_LORE: u64 = 10;
 
-pub(crate) fn on_notify_user_info(req: &Request) -> Response {
-  let refs = get_references(req);
-  *get_write(&USER_NAME) = refs[0].to_string();
+pub(crate) fn on_notify_user_info(state: &mut GhostState, req: &Request) -> Response {
+  let refs = References::from_request(req);
+  let user_name = refs.get_or_empty(0);
+  let was_profile = state.volatile.profile.clone();
+  if select_profile_by_user_name(state, user_name) {
+    // 切り替え先のプロフィールの呼び名はそのまま使う。
+    // 起動を数えたあとに切り替えたなら、切り替え先のプロフィールで数え直す
+    if state.volatile.booted && state.volatile.profile != was_profile {
+      state.persistent.total_boot_count += 1;
+      check_story_events(state);
+    }
+  } else {
+    state.persistent.user_name = user_name.to_string();
+  }
   new_response_nocontent()
 }
 
-pub(crate) fn on_minute_change(_req: &Request) -> Response {
-  check_story_events();
+pub(crate) fn on_minute_change(state: &mut GhostState, _req: &Request) -> Response {
+  check_story_events(state);
+
+  state.volatile.minutes_since_save += 1;
+  let interval = state.persistent.autosave_interval;
+  if interval > 0 && state.volatile.minutes_since_save >= interval {
+    state.autosave();
+  }
   new_response_nocontent()
 }
 
-pub(crate) fn on_second_change(req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_second_change(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
   // 最小化中かどうかに関わらず実行する処理
-  *get_write(&TOTAL_TIME) += 1;
-  *get_write(&GHOST_UP_TIME) += 1;
+  state.persistent.total_time += 1;
+  state.volatile.ghost_up_time += 1;
 
   // 初回起動イベントが終わるまではランダムトークなし
-  if !get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
-    FIRST_RANDOMTALKS.len() as u32 - 1,
-  )) {
+  if !state
+    .persistent
+    .flags
+    .check(&EventFlag::FirstRandomTalkDone(
+      FIRST_RANDOMTALKS.len() as u32 - 1,
+    ))
+  {
     return Ok(new_response_nocontent());
   }
 
-  let refs = get_references(req);
-  let idle_secs = match refs[4].parse::<i32>() {
-    Ok(v) => v,
-    Err(_) => return Err(ShioriError::ParseIntError),
-  };
-  *get_write(&IDLE_SECONDS) = idle_secs;
+  let second_change = SecondChange::from_request(req)?;
+  state.volatile.idle_seconds = second_change.idle_secs;
 
   let status = Status::from_request(req);
 
   debug!("status: {}", status);
   {
-    let random_talk_interval = *get_read(&RANDOM_TALK_INTERVAL);
-    if random_talk_interval > 0 && (*get_read(&GHOST_UP_TIME) - *get_read(&LAST_RANDOM_TALK_TIME)) >= random_talk_interval && !status.minimizing {
-      return on_ai_talk(req);
+    let random_talk_interval = state.persistent.random_talk_interval;
+    if random_talk_interval > 0 && (state.volatile.ghost_up_time - state.volatile.last_random_talk_time) >= random_talk_interval && !status.minimizing {
+      return on_ai_talk(state, req);
     }
   }
 
   let mut text = String::new();
   {
-    if (*get_read(&GHOST_UP_TIME)).is_multiple_of(60) && !status.talking {
+    if (state.volatile.ghost_up_time).is_multiple_of(60) && !status.talking {
       // 1分ごとにサーフェスを重ね直す
       text += STICK_SURFACE;
     }
   }
 
-  let now = get_local_time();
-  if now.wMinute == 0
-    && now.wSecond == 0
-    && get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
+  if state
+    .persistent
+    .flags
+    .check(&EventFlag::FirstRandomTalkDone(
       FIRST_RANDOMTALKS.len() as u32 - 1,
     ))
   {
-    let tanka_list = [
-      tanka(
-        "もう二度と死ななくてよい安らぎに\\n見つめてゐたり祖母の寝顔を",
-        "梶原さい子",
-      ),
-      tanka(
-        "眼のまはり真紅(まあか)くなして泣きやめぬ\\n妻のうしろに吾子死にてあり",
-        "木下利玄",
-      ),
-      tanka(
-        "我が母よ死にたまひゆく我が母よ\\n我(わ)を生まし乳足(ちた)らひし母よ",
-        "斎藤茂吉",
-      ),
-      tanka(
-        "眠られぬ母のためわが誦む童話\\n母の寝入りし後王子死す",
-        "岡井隆",
-      ),
-      tanka(
-        "死せる犬またもわが眼にうかび来ぬ、\\nかの川ばたの夕ぐれの色",
-        "金子薫園",
-      ),
-      tanka(
-        "死に一歩踏み入りしとふ実感は\\nひるがへつて生の実感なりし",
-        "後藤悦良",
-      ),
-      tanka(
-        "蛍光灯のカヴァーの底を死場所としたる\\nこの世の虫のかずかず",
-        "小池光",
-      ),
-      tanka(
-        "死に向かふ生の底知れぬ虚無の淵を\\nのぞき見たりき彼の夜の君に",
-        "柴生田稔",
-      ),
-      tanka(
-        "やわらかく厚い果肉を掘りすすみ\\n核の付近で死んでいる虫",
-        "北辻千展",
-      ),
-      tanka(
-        "死にし子をまつたく忘れてゐる日あり\\n百日忌日(ひやくにちきじつ)にそれをしぞ嘆く",
-        "吉野秀雄",
-      ),
-      tanka(
-        "十トンの恐竜もゐしこの星に\\n四十八キロの妻生きて死す",
-        "高野公彦",
-      ),
-      tanka(
-        "生まれてはつひに死ぬてふことのみぞ\\n定めなき世に定めありける",
-        "平維盛",
-      ),
-    ];
-
-    let tanka = if let Some(v) = tanka_list.choose(&mut rand::thread_rng()) {
-      v
-    } else {
-      return Err(ShioriError::ArrayAccessError);
-    };
-
-    text += &format!("\\1\\_q{}時\\n{}", now.wHour, tanka);
+    if let Some(v) = hourly_tanka(&state.local_time())? {
+      text += &v;
+    }
   }
 
   if text.is_empty() {
     Ok(new_response_nocontent())
   } else {
-    new_response_with_value_with_translate(text, TranslateOption::simple_translate())
+    new_response_with_value_with_translate(state, text, TranslateOption::simple_translate())
   }
 }
 
+/// 正時（毎時0分0秒）に表示する短歌。正時でなければNone
+pub(crate) fn hourly_tanka(now: &LocalTime) -> Result<Option<String>, ShioriError> {
+  if now.minute != 0 || now.second != 0 {
+    return Ok(None);
+  }
+
+  let tanka_list = [
+    tanka(
+      "もう二度と死ななくてよい安らぎに\\n見つめてゐたり祖母の寝顔を",
+      "梶原さい子",
+    ),
+    tanka(
+      "眼のまはり真紅(まあか)くなして泣きやめぬ\\n妻のうしろに吾子死にてあり",
+      "木下利玄",
+    ),
+    tanka(
+      "我が母よ死にたまひゆく我が母よ\\n我(わ)を生まし乳足(ちた)らひし母よ",
+      "斎藤茂吉",
+    ),
+    tanka(
+      "眠られぬ母のためわが誦む童話\\n母の寝入りし後王子死す",
+      "岡井隆",
+    ),
+    tanka(
+      "死せる犬またもわが眼にうかび来ぬ、\\nかの川ばたの夕ぐれの色",
+      "金子薫園",
+    ),
+    tanka(
+      "死に一歩踏み入りしとふ実感は\\nひるがへつて生の実感なりし",
+      "後藤悦良",
+    ),
+    tanka(
+      "蛍光灯のカヴァーの底を死場所としたる\\nこの世の虫のかずかず",
+      "小池光",
+    ),
+    tanka(
+      "死に向かふ生の底知れぬ虚無の淵を\\nのぞき見たりき彼の夜の君に",
+      "柴生田稔",
+    ),
+    tanka(
+      "やわらかく厚い果肉を掘りすすみ\\n核の付近で死んでいる虫",
+      "北辻千展",
+    ),
+    tanka(
+      "死にし子をまつたく忘れてゐる日あり\\n百日忌日(ひやくにちきじつ)にそれをしぞ嘆く",
+      "吉野秀雄",
+    ),
+    tanka(
+      "十トンの恐竜もゐしこの星に\\n四十八キロの妻生きて死す",
+      "高野公彦",
+    ),
+    tanka(
+      "生まれてはつひに死ぬてふことのみぞ\\n定めなき世に定めありける",
+      "平維盛",
+    ),
+  ];
+
+  let tanka = if let Some(v) = tanka_list.choose(&mut rand::thread_rng()) {
+    v
+  } else {
+    return Err(ShioriError::ArrayAccessError);
+  };
+
+  Ok(Some(format!("\\1\\_q{}時\\n{}", now.hour, tanka)))
+}
+
 fn tanka(text: &str, author: &str) -> String {
   format!("{}\\n\\f[align,right]({})", text, author)
 }
 
-pub(crate) fn on_surface_change(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let surface = match refs[0].parse::<i32>() {
-    Ok(v) => v,
-    Err(_) => return Err(ShioriError::ParseIntError),
-  };
-
-  *get_write(&CURRENT_SURFACE) = surface;
+pub(crate) fn on_surface_change(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  state.volatile.current_surface = SurfaceChange::from_request(req)?.surface;
 
   Ok(new_response_nocontent())
 }
 
-pub(crate) fn check_story_events() {
-  // 何らかの理由で初期トークタイプがセーブデータから欠落した場合を考え、初回起動が終わっているならUnlockを毎回する
-  if get_read(&FLAGS).check(&EventFlag::FirstRandomTalkDone(
-    (FIRST_RANDOMTALKS.len() - 1) as u32,
-  )) {
-    [TalkType::AboutMe, TalkType::WithYou].iter().for_each(|t| {
-      get_write(&FLAGS).done(EventFlag::TalkTypeUnlock(*t));
-    });
+pub(crate) fn check_story_events(state: &mut GhostState) {
+  let pending_event_talk = state.persistent.pending_event_talk.clone();
+  let mut unlocked = false;
+
+  // 何らかの理由で初期トークタイプがセーブデータから欠落した場合を考え、初回起動が終わっているならUnlockを毎回確かめる
+  if state
+    .persistent
+    .flags
+    .check(&EventFlag::FirstRandomTalkDone(
+      (FIRST_RANDOMTALKS.len() - 1) as u32,
+    ))
+  {
+    for t in [TalkType::AboutMe, TalkType::WithYou] {
+      let flag = EventFlag::TalkTypeUnlock(t);
+      if !state.persistent.flags.check(&flag) {
+        state.persistent.flags.done(flag, state.local_time());
+        unlocked = true;
+      }
+    }
   }
 
-  if !get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(super::TalkType::Servant)) && *get_read(&CUMULATIVE_TALK_COUNT) >= TALK_UNLOCK_COUNT_SERVANT {
+  if !state
+    .persistent
+    .flags
+    .check(&EventFlag::TalkTypeUnlock(super::TalkType::Servant))
+    && state.persistent.cumulative_talk_count >= TALK_UNLOCK_COUNT_SERVANT
+  {
     // 従者コメント開放
-    *get_write(&PENDING_EVENT_TALK) = Some(PendingEvent::UnlockingServantsComments);
-  } else if !get_read(&FLAGS).check(&EventFlag::TalkTypeUnlock(super::TalkType::Lore)) && *get_read(&CUMULATIVE_TALK_COUNT) >= TALK_UNLOCK_COUNT_LORE {
+    state.persistent.pending_event_talk = Some(PendingEvent::UnlockingServantsComments);
+  } else if !state
+    .persistent
+    .flags
+    .check(&EventFlag::TalkTypeUnlock(super::TalkType::Lore))
+    && state.persistent.cumulative_talk_count >= TALK_UNLOCK_COUNT_LORE
+  {
     // ロアトーク開放
-    *get_write(&PENDING_EVENT_TALK) = Some(PendingEvent::UnlockingLoreTalks);
-  } else if *get_read(&PENDING_EVENT_TALK) == Some(PendingEvent::ConfessionOfSuicide) {
+    state.persistent.pending_event_talk = Some(PendingEvent::UnlockingLoreTalks);
+  } else if state.persistent.pending_event_talk == Some(PendingEvent::ConfessionOfSuicide) {
     // 仕様変更のため解禁されないように
     // すでにPendingEventにConfessionOfSuicideがセットされている場合は消す
-    *get_write(&PENDING_EVENT_TALK) = None;
+    state.persistent.pending_event_talk = None;
   }
 
   // 過去トークの解禁がされている場合、再び閉じる
   {
-    let mut flags = get_write(&FLAGS);
+    let flags = &mut state.persistent.flags;
     if flags.check(&EventFlag::TalkTypeUnlock(super::TalkType::Past)) {
       flags.delete(EventFlag::TalkTypeUnlock(super::TalkType::Past));
     }
   }
 
   // 変数に過去トークの情報が入っている場合消去する
-  if get_write(&TALK_COLLECTION)
+  if state
+    .persistent
+    .talk_collection
     .remove(&super::TalkType::Past)
     .is_some()
   {
     debug!("過去トークの情報を消去しました");
   }
+
+  // 開放や解禁イベントの予約は、次の保存を待たずに残す
+  if unlocked || state.persistent.pending_event_talk != pending_event_talk {
+    state.autosave();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use shiorust::message::parts::*;
+  use tempfile::TempDir;
+
+  fn minute_change_request() -> Request {
+    let mut headers = Headers::new();
+    headers.insert_by_header_name(HeaderName::from("ID"), "OnMinuteChange".to_string());
+    Request {
+      method: Method::GET,
+      version: Version::V20,
+      headers,
+    }
+  }
+
+  fn notify_user_info_request(user_name: &str) -> Request {
+    let mut headers = Headers::new();
+    headers.insert_by_header_name(HeaderName::from("ID"), "OnNotifyUserInfo".to_string());
+    headers.insert_by_header_name(HeaderName::from("Reference0"), user_name.to_string());
+    Request {
+      method: Method::NOTIFY,
+      version: Version::V30,
+      headers,
+    }
+  }
+
+  #[test]
+  fn test_notify_user_info_switches_profile() {
+    let dir = TempDir::new().unwrap();
+    let mut state = GhostState::default();
+    state.volatile.save_dir = dir.path().to_path_buf();
+    state.load_variables().unwrap();
+    state.switch_profile(Some("あかり".to_string())).unwrap();
+    state.persistent.user_name = "ご主人".to_string();
+    state.persistent.total_boot_count = 5;
+    state.switch_profile(None).unwrap();
+
+    // 起動を数える前に切り替えたなら、切り替え先の呼び名も起動回数もそのまま
+    on_notify_user_info(&mut state, &notify_user_info_request("あかり"));
+    assert_eq!(state.volatile.profile.as_deref(), Some("あかり"));
+    assert_eq!(state.persistent.user_name, "ご主人");
+    assert_eq!(state.persistent.total_boot_count, 5);
+
+    // 起動を数えたあとに切り替えたなら、切り替え先で数え直す
+    state.switch_profile(None).unwrap();
+    state.volatile.booted = true;
+    on_notify_user_info(&mut state, &notify_user_info_request("あかり"));
+    assert_eq!(state.persistent.user_name, "ご主人");
+    assert_eq!(state.persistent.total_boot_count, 6);
+
+    // 同じ名前のプロフィールがなければ、今のプロフィールの呼び名にする
+    on_notify_user_info(&mut state, &notify_user_info_request("ななし"));
+    assert_eq!(state.volatile.profile.as_deref(), Some("あかり"));
+    assert_eq!(state.persistent.user_name, "ななし");
+    assert_eq!(state.persistent.total_boot_count, 6);
+  }
+
+  #[test]
+  fn test_autosave_interval() {
+    let mut state = GhostState::default();
+    state.persistent.autosave_interval = 3;
+    let req = minute_change_request();
+
+    // ロード前なのでファイルには書き込まれず、経過時間だけがリセットされる
+    on_minute_change(&mut state, &req);
+    on_minute_change(&mut state, &req);
+    assert_eq!(state.volatile.minutes_since_save, 2);
+    on_minute_change(&mut state, &req);
+    assert_eq!(state.volatile.minutes_since_save, 0);
+
+    // 0なら自動保存しない
+    state.persistent.autosave_interval = 0;
+    for _ in 0..5 {
+      on_minute_change(&mut state, &req);
+    }
+    assert_eq!(state.volatile.minutes_since_save, 5);
+  }
+
+  #[test]
+  fn test_story_events_autosave_on_unlock() {
+    let mut state = GhostState::default();
+    let last = EventFlag::FirstRandomTalkDone(FIRST_RANDOMTALKS.len() as u32 - 1);
+    state.persistent.flags.done(last, state.local_time());
+    state.persistent.cumulative_talk_count = TALK_UNLOCK_COUNT_SERVANT;
+
+    state.volatile.minutes_since_save = 4;
+    check_story_events(&mut state);
+    assert_eq!(
+      state.persistent.pending_event_talk,
+      Some(PendingEvent::UnlockingServantsComments)
+    );
+    assert_eq!(state.volatile.minutes_since_save, 0);
+
+    // 何も変わらなければ保存せず、開放済みのフラグの回数も増やさない
+    state.volatile.minutes_since_save = 4;
+    check_story_events(&mut state);
+    assert_eq!(state.volatile.minutes_since_save, 4);
+    let unlock = EventFlag::TalkTypeUnlock(TalkType::AboutMe);
+    let records = state.persistent.flags.records();
+    let (_, record) = records.iter().find(|(f, _)| **f == unlock).unwrap();
+    assert_eq!(record.count, 1);
+  }
 }
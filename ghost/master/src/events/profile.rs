@@ -0,0 +1,109 @@
+use crate::events::input::InputId;
+use crate::events::menu::config_submenu_response;
+use crate::events::references::References;
+use crate::system::error::ShioriError;
+use crate::system::profile::{self, ProfileIndex};
+use crate::system::response::*;
+use crate::system::variables::{GhostState, LoadStatus};
+use shiorust::message::{Request, Response};
+
+/// プロフィールの一覧。名前は \q の引数に使えない文字を含みうるため、番号で選ぶ（0は既定のプロフィール）
+pub(crate) fn on_profile_menu(state: &mut GhostState, _req: &Request) -> Response {
+  let index = ProfileIndex::load(&state.volatile.save_dir);
+  let current = state.volatile.profile.as_deref();
+  let profiles = std::iter::once(None).chain(index.names.iter().map(|n| Some(n.as_str())));
+  let items: String = profiles
+    .enumerate()
+    .map(|(i, p)| {
+      if p == current {
+        format!(
+          "\\![*]\\f[underline,1]{}\\f[underline,0]\\n",
+          profile::title(p)
+        )
+      } else {
+        format!("\\![*]\\q[{},OnProfileChanged,{}]\\n", profile::title(p), i)
+      }
+    })
+    .collect();
+  config_submenu_response(
+    state,
+    format!(
+      "\
+        ◆プロフィール  【現在 {}】\\n\
+        トーク履歴や進み具合を、プロフィールごとに分けて保存します。\\n\\n\
+        {}\\n\
+        \\![*]\\q[新しいプロフィールを作る,OnProfileCreate]\\n\
+        ",
+      profile::title(current),
+      items
+    ),
+  )
+}
+
+pub(crate) fn on_profile_changed(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let refs = References::from_request(req);
+  let profile = match refs.parse::<usize>(0)? {
+    0 => None,
+    i => Some(
+      ProfileIndex::load(&state.volatile.save_dir)
+        .names
+        .get(i - 1)
+        .cloned()
+        .ok_or_else(|| ShioriError::InvalidReference("OnProfileChanged".to_string(), 0, i.to_string()))?,
+    ),
+  };
+  switch_profile(state, profile)
+}
+
+pub(crate) fn on_profile_create(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
+  let m = format!(
+    "\\_q\\![open,inputbox,{},0]新しいプロフィールの名前を入力してください。\\n呼び名にも使います。",
+    InputId::ProfileName,
+  );
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
+}
+
+pub(crate) fn input_profile_name(state: &mut GhostState, text: String) -> Result<Response, ShioriError> {
+  let name = text.trim().to_string();
+  if let Err(reason) = profile::validate_name(&name) {
+    return Ok(config_submenu_response(state, format!("{}\\n", reason)));
+  }
+  switch_profile(state, Some(name))
+}
+
+fn switch_profile(state: &mut GhostState, profile: Option<String>) -> Result<Response, ShioriError> {
+  if let Err(e) = state.switch_profile(profile.clone()) {
+    error!("{}", e);
+    return Err(ShioriError::FileWriteError(format!(
+      "{}: {}",
+      state.var_path(),
+      e
+    )));
+  }
+  // 新しく作ったプロフィールは、その名前で呼ぶ
+  if state.volatile.load_status == LoadStatus::FirstBoot {
+    if let Some(name) = &profile {
+      state.persistent.user_name = name.clone();
+    }
+  }
+  Ok(config_submenu_response(
+    state,
+    format!(
+      "プロフィールを「{}」に切り替えました。\\n",
+      profile::title(profile.as_deref())
+    ),
+  ))
+}
+
+/// OnNotifyUserInfo のユーザ名と同じ名前のプロフィールがあれば、それに切り替える。
+/// そのプロフィールを使っていれば（すでに使っていた場合も含めて）true
+pub(crate) fn select_profile_by_user_name(state: &mut GhostState, user_name: &str) -> bool {
+  if !ProfileIndex::load(&state.volatile.save_dir).contains(user_name) {
+    return false;
+  }
+  if let Err(e) = state.switch_profile(Some(user_name.to_string())) {
+    error!("プロフィールの切り替えに失敗: {}", e);
+    return false;
+  }
+  true
+}
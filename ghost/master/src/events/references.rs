@@ -0,0 +1,202 @@
+use crate::system::error::ShioriError;
+use shiorust::message::{parts::*, traits::*, Request};
+use std::str::FromStr;
+
+/// リクエストの Reference 群。
+/// 範囲外や型の合わないアクセスは、イベント名と Reference の番号を含むエラーになる
+pub(crate) struct References<'a> {
+  event: &'a str,
+  values: Vec<&'a str>,
+}
+
+impl<'a> References<'a> {
+  /// Reference0 から連続して存在する分をすべて取得する
+  pub fn from_request(req: &'a Request) -> Self {
+    let event = req.headers.get("ID").map_or("", |s| s.as_str());
+    let mut values = Vec::new();
+    while let Some(value) = req
+      .headers
+      .get(&HeaderName::from(&format!("Reference{}", values.len())))
+    {
+      values.push(value.as_str());
+    }
+    Self { event, values }
+  }
+
+  pub fn len(&self) -> usize {
+    self.values.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &&'a str> {
+    self.values.iter()
+  }
+
+  pub fn get(&self, index: usize) -> Result<&'a str, ShioriError> {
+    self
+      .values
+      .get(index)
+      .copied()
+      .ok_or_else(|| ShioriError::MissingReference(self.event.to_string(), index))
+  }
+
+  /// 省略されうる Reference を取得する。存在しなければ空文字列
+  pub fn get_or_empty(&self, index: usize) -> &'a str {
+    self.values.get(index).copied().unwrap_or("")
+  }
+
+  pub fn parse<T: FromStr>(&self, index: usize) -> Result<T, ShioriError> {
+    let value = self.get(index)?;
+    value
+      .parse::<T>()
+      .map_err(|_| ShioriError::InvalidReference(self.event.to_string(), index, value.to_string()))
+  }
+}
+
+/// OnMouseClickEx, OnMouseDoubleClick, OnMouseMove, OnMouseWheel 共通の Reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MouseEvent {
+  pub x: i32,
+  pub y: i32,
+  pub wheel: i32,
+  pub scope: u32,
+  /// 当たり判定の名前。当たり判定の外なら空文字列
+  pub part: String,
+  /// OnMouseDoubleClick などでは省略されうる
+  pub button: String,
+}
+
+impl MouseEvent {
+  pub fn from_request(req: &Request) -> Result<Self, ShioriError> {
+    let refs = References::from_request(req);
+    Ok(Self {
+      x: refs.parse(0)?,
+      y: refs.parse(1)?,
+      wheel: refs.parse(2)?,
+      scope: refs.parse(3)?,
+      part: refs.get(4)?.to_string(),
+      button: refs.get_or_empty(5).to_string(),
+    })
+  }
+
+  /// 触り反応の判定に使う「スコープ番号+当たり判定名」
+  pub fn target(&self) -> String {
+    format!("{}{}", self.scope, self.part)
+  }
+}
+
+/// OnSecondChange の Reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SecondChange {
+  /// ユーザの無操作時間（秒）
+  pub idle_secs: i32,
+}
+
+impl SecondChange {
+  pub fn from_request(req: &Request) -> Result<Self, ShioriError> {
+    let refs = References::from_request(req);
+    Ok(Self {
+      idle_secs: refs.parse(4)?,
+    })
+  }
+}
+
+/// OnSurfaceChange の Reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SurfaceChange {
+  /// \0 側のサーフェス番号
+  pub surface: i32,
+}
+
+impl SurfaceChange {
+  pub fn from_request(req: &Request) -> Result<Self, ShioriError> {
+    let refs = References::from_request(req);
+    Ok(Self {
+      surface: refs.parse(0)?,
+    })
+  }
+}
+
+/// OnAnchorSelectEx の Reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AnchorSelect {
+  /// AnchorTalk, DerivativeTalk, DerivativeTalkRequest のいずれか
+  pub anchor_type: String,
+  pub id: String,
+}
+
+impl AnchorSelect {
+  pub fn from_request(req: &Request) -> Result<Self, ShioriError> {
+    let refs = References::from_request(req);
+    Ok(Self {
+      anchor_type: refs.get(1)?.to_string(),
+      id: refs.get(2)?.to_string(),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn make_request(id: &str, references: &[&str]) -> Request {
+    let mut headers = Headers::new();
+    headers.insert_by_header_name(HeaderName::from("ID"), id.to_string());
+    for (i, r) in references.iter().enumerate() {
+      headers.insert_by_header_name(HeaderName::from(&format!("Reference{}", i)), r.to_string());
+    }
+    Request {
+      method: Method::GET,
+      version: Version::V30,
+      headers,
+    }
+  }
+
+  #[test]
+  fn test_references_are_not_capped() {
+    let values: Vec<String> = (0..15).map(|i| i.to_string()).collect();
+    let values: Vec<&str> = values.iter().map(|s| s.as_str()).collect();
+    let req = make_request("OnUpdateResultEx", &values);
+    let refs = References::from_request(&req);
+    assert_eq!(refs.len(), 15);
+    assert_eq!(refs.get(14), Ok("14"));
+  }
+
+  #[test]
+  fn test_mouse_event() {
+    let req = make_request(
+      "OnMouseClickEx",
+      &["10", "20", "0", "0", "headnade", "middle"],
+    );
+    let e = MouseEvent::from_request(&req).unwrap();
+    assert_eq!(e.target(), "0headnade");
+    assert_eq!(e.button, "middle");
+
+    let req = make_request("OnMouseDoubleClick", &["10", "20", "0", "2", "candle"]);
+    let e = MouseEvent::from_request(&req).unwrap();
+    assert_eq!(e.target(), "2candle");
+    assert_eq!(e.button, "");
+  }
+
+  #[test]
+  fn test_errors_name_event_and_index() {
+    let req = make_request("OnMouseMove", &["10", "20", "0"]);
+    assert_eq!(
+      MouseEvent::from_request(&req),
+      Err(ShioriError::MissingReference("OnMouseMove".to_string(), 3))
+    );
+
+    let req = make_request("OnSecondChange", &["0", "0", "0", "0", "abc"]);
+    assert_eq!(
+      SecondChange::from_request(&req),
+      Err(ShioriError::InvalidReference(
+        "OnSecondChange".to_string(),
+        4,
+        "abc".to_string()
+      ))
+    );
+  }
+}
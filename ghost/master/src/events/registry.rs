@@ -0,0 +1,109 @@
+use crate::system::error::ShioriError;
+use crate::system::variables::GhostState;
+use shiorust::message::{traits::*, Request, Response};
+
+/// リクエストの送信元の信頼度。SHIORIリクエストの SecurityLevel ヘッダに対応する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum SecurityLevel {
+  Local,
+  External,
+}
+
+impl SecurityLevel {
+  /// ヘッダがない場合はSSP内部からのリクエストとみなす
+  pub fn from_request(req: &Request) -> Self {
+    match req.headers.get("SecurityLevel") {
+      Some(v) if v.eq_ignore_ascii_case("external") => Self::External,
+      _ => Self::Local,
+    }
+  }
+}
+
+/// SHIORIイベントのハンドラ。
+/// ディスパッチャはメタデータをもとにリクエストを検証してから handle を呼ぶ
+pub(crate) trait Event: Send + Sync {
+  fn id(&self) -> &'static str;
+
+  /// 通知専用のイベントか。trueならメソッドに関わらずスクリプトを返さない
+  fn is_notify_only(&self) -> bool {
+    false
+  }
+
+  /// ハンドラが読む Reference の最小数
+  fn min_references(&self) -> usize {
+    0
+  }
+
+  /// 受け付ける送信元の信頼度の下限。External なら外部SSTPからのリクエストも受け付ける
+  fn security_level(&self) -> SecurityLevel {
+    SecurityLevel::Local
+  }
+
+  fn handle(&self, state: &mut GhostState, req: &Request) -> Result<Response, ShioriError>;
+}
+
+pub(crate) enum EventHandler {
+  AlwaysSuccess(fn(&mut GhostState, &Request) -> Response),
+  MayFailure(fn(&mut GhostState, &Request) -> Result<Response, ShioriError>),
+}
+
+/// 関数をハンドラとするイベント
+pub(crate) struct FnEvent {
+  id: &'static str,
+  handler: EventHandler,
+  notify_only: bool,
+  min_references: usize,
+  security_level: SecurityLevel,
+}
+
+impl FnEvent {
+  pub const fn new(id: &'static str, handler: EventHandler) -> Self {
+    Self {
+      id,
+      handler,
+      notify_only: false,
+      min_references: 0,
+      security_level: SecurityLevel::Local,
+    }
+  }
+
+  pub const fn notify_only(mut self) -> Self {
+    self.notify_only = true;
+    self
+  }
+
+  pub const fn min_references(mut self, n: usize) -> Self {
+    self.min_references = n;
+    self
+  }
+
+  pub const fn allow_external(mut self) -> Self {
+    self.security_level = SecurityLevel::External;
+    self
+  }
+}
+
+impl Event for FnEvent {
+  fn id(&self) -> &'static str {
+    self.id
+  }
+
+  fn is_notify_only(&self) -> bool {
+    self.notify_only
+  }
+
+  fn min_references(&self) -> usize {
+    self.min_references
+  }
+
+  fn security_level(&self) -> SecurityLevel {
+    self.security_level
+  }
+
+  fn handle(&self, state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+    match self.handler {
+      EventHandler::AlwaysSuccess(f) => Ok(f(state, req)),
+      EventHandler::MayFailure(f) => f(state, req),
+    }
+  }
+}
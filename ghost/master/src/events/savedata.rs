@@ -0,0 +1,143 @@
+use crate::events::menu::config_submenu_response;
+use crate::events::references::References;
+use crate::system::error::ShioriError;
+use crate::system::transfer::{read_export, write_export, ImportMode, EXPORT_FILE_NAME};
+use crate::system::variables::GhostState;
+use shiorust::message::{Request, Response};
+use std::path::Path;
+
+pub(crate) fn on_savedata_export(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
+  let metadata = write_export(
+    &state.to_raw_variables(),
+    Path::new(EXPORT_FILE_NAME),
+    state.local_time(),
+  )
+  .map_err(|e| ShioriError::FileWriteError(format!("{}: {}", EXPORT_FILE_NAME, e)))?;
+
+  Ok(config_submenu_response(
+    state,
+    format!(
+      "\
+        セーブデータを書き出しました。\\n\
+        ゴーストのフォルダの {} を、引っ越し先のゴーストのフォルダに置いて読み込んでください。\\n\\n\
+        チェックサム: {}\\n\
+        ",
+      EXPORT_FILE_NAME, metadata.checksum
+    ),
+  ))
+}
+
+/// 書き出しファイルの内容を確認する。Reference0 に反映方法があれば、その方法での変更点を表示する
+pub(crate) fn on_savedata_import(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let export = match read_export(Path::new(EXPORT_FILE_NAME)) {
+    Ok(v) => v,
+    Err(e) => {
+      return Ok(config_submenu_response(
+        state,
+        format!(
+          "\
+            セーブデータを読み込めませんでした。\\n\
+            書き出した {} をゴーストのフォルダに置いてください。\\n\\n\
+            詳細: {}\\n\
+            ",
+          EXPORT_FILE_NAME, e
+        ),
+      ));
+    }
+  };
+
+  let metadata = format!(
+    "\
+      ◆{}\\n\
+      書き出し日時: {}/{:02}/{:02} {:02}:{:02}\\n\
+      バージョン: {}\\n\
+      ",
+    EXPORT_FILE_NAME,
+    export.metadata.exported_at.year,
+    export.metadata.exported_at.month,
+    export.metadata.exported_at.day,
+    export.metadata.exported_at.hour,
+    export.metadata.exported_at.minute,
+    export.metadata.ghost_version,
+  );
+  let warnings = if export.warnings.is_empty() {
+    "".to_string()
+  } else {
+    format!(
+      "読み込めなかった項目は初期値になります: {}\\n",
+      export.warnings.join(", ")
+    )
+  };
+
+  let body = match References::from_request(req)
+    .get_or_empty(0)
+    .parse::<ImportMode>()
+  {
+    Err(_) => format!(
+      "\
+        {}{}\\n\
+        トーク履歴とフラグの扱いを選んでください。\\n\
+        \\![*]\\q[置き換える,OnSaveDataImport,{}]\\n\
+        \\![*]\\q[今のデータと統合する,OnSaveDataImport,{}]\\n\
+        ",
+      metadata,
+      warnings,
+      ImportMode::Replace.as_str(),
+      ImportMode::Merge.as_str(),
+    ),
+    Ok(mode) => {
+      let diff = export.diff(&state.to_raw_variables(), mode);
+      let changes = if diff.is_empty() {
+        "変更はありません。\\n".to_string()
+      } else {
+        diff
+          .iter()
+          .map(|line| format!("・{}\\n", line))
+          .collect::<String>()
+      };
+      format!(
+        "\
+          {}{}\\n\
+          ◆変更点\\n\
+          {}\\n\
+          \\![*]\\q[反映する,OnSaveDataImportExec,{}]\\n\
+          \\![*]\\q[やめる,OnConfigMenuExec]\\n\
+          ",
+        metadata,
+        warnings,
+        changes,
+        mode.as_str(),
+      )
+    }
+  };
+  Ok(config_submenu_response(state, body))
+}
+
+pub(crate) fn on_savedata_import_exec(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let mode = References::from_request(req).parse::<ImportMode>(0)?;
+
+  // 読み取り専用などで保存できない状態なら、読み込んでも終了時に失われる
+  if state.volatile.read_only || !state.volatile.load_status.should_save() {
+    return Ok(config_submenu_response(
+      state,
+      "現在のセーブデータは保存できない状態のため、読み込めません。\\n".to_string(),
+    ));
+  }
+
+  let result = read_export(Path::new(EXPORT_FILE_NAME)).and_then(|export| {
+    let imported = export.apply(&state.to_raw_variables(), mode);
+    imported.save_to(&state.var_path(), state.local_time())?;
+    state.load_variables()
+  });
+  if let Err(e) = result {
+    return Err(ShioriError::FileWriteError(format!(
+      "{}: {}",
+      EXPORT_FILE_NAME, e
+    )));
+  }
+
+  Ok(config_submenu_response(
+    state,
+    "セーブデータを読み込みました。\\n以前のセーブデータはバックアップとして残っています。\\n".to_string(),
+  ))
+}
@@ -1,13 +1,18 @@
 pub(crate) mod anchor;
+pub(crate) mod chain;
+pub(crate) mod condition;
+pub(crate) mod content;
+pub(crate) mod effect;
 pub(crate) mod first_boot;
 pub(crate) mod randomtalk;
+pub(crate) mod validate;
 
-use crate::check_error;
+use crate::events::references::References;
 use crate::events::talk::randomtalk::random_talks;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
 use crate::system::roulette::RouletteCell;
-use crate::system::variables::{get_read, get_write, TALK_COLLECTION};
+use crate::system::variables::GhostState;
 use core::fmt::{Display, Formatter};
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -16,7 +21,9 @@ use std::collections::HashSet;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
-use self::randomtalk::{derivative_talks_per_talk_type, get_parent_talk};
+use self::condition::Condition;
+use self::effect::{apply_effects, Effect};
+use self::randomtalk::{derivative_talks_per_talk_type, get_parent_talk, talk_conditions};
 
 use super::aitalk::render_talk;
 
@@ -26,7 +33,7 @@ pub(crate) struct Talk {
   pub talk_type: Option<TalkType>,
   pub text: String,
   pub id: String,
-  pub callback: Option<fn()>,
+  pub effects: Vec<Effect>,
 }
 
 impl RouletteCell for Talk {
@@ -42,36 +49,34 @@ impl Display for Talk {
 }
 
 impl Talk {
-  pub fn consume(&self) -> String {
-    if let Some(callback) = self.callback {
-      callback();
-    }
+  pub fn consume(&self, state: &mut GhostState) -> String {
+    apply_effects(state, &self.id, &self.effects);
     self.text.clone()
   }
 }
 
 #[allow(dead_code)]
 impl Talk {
-  pub fn new(talk_type: Option<TalkType>, id: String, text: String, callback: Option<fn()>) -> Self {
+  pub fn new(talk_type: Option<TalkType>, id: String, text: String, effects: Vec<Effect>) -> Self {
     Self {
       talk_type,
       text,
       id,
-      callback,
+      effects,
     }
   }
 
-  pub fn all_talks() -> Option<Vec<Talk>> {
+  pub fn all_talks(state: &mut GhostState) -> Option<Vec<Talk>> {
     let mut v = Vec::new();
     for t in TalkType::all() {
-      let talks = random_talks(t)?;
+      let talks = random_talks(state, t)?;
       v.extend(talks);
     }
     Some(v)
   }
 
-  pub fn get_unseen_talks(talk_type: TalkType, seen: &HashSet<String>) -> Option<Vec<Talk>> {
-    let talks = random_talks(talk_type)?;
+  pub fn get_unseen_talks(state: &mut GhostState, talk_type: TalkType, seen: &HashSet<String>) -> Option<Vec<Talk>> {
+    let talks = random_talks(state, talk_type)?;
     Some(
       talks
         .into_iter()
@@ -83,7 +88,6 @@ impl Talk {
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Hash, EnumIter)]
 pub(crate) enum TalkType {
-  #[serde(alias = "SelfIntroduce")]
   AboutMe,
   WithYou,
   Servant,
@@ -116,7 +120,7 @@ impl TalkType {
   }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum TalkingPlace {
   LivingRoom,
   Library,
@@ -153,20 +157,22 @@ impl TalkingPlace {
   }
 }
 
-pub(crate) fn on_check_unseen_talks(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let talk_type_num = check_error!(refs[0].parse::<u32>(), ShioriError::ParseIntError);
-  let talk_type = TalkType::from_u32(talk_type_num).ok_or(ShioriError::BadRequest)?;
+pub(crate) fn on_check_unseen_talks(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let talk_type_num = References::from_request(req).parse::<u32>(0)?;
+  let talk_type = TalkType::from_u32(talk_type_num).ok_or_else(|| ShioriError::BadRequest(format!("unknown talk type: {}", talk_type_num)))?;
   let choosed_talk;
   {
-    let talk_collection = get_read(&TALK_COLLECTION);
-    let empty_hashset = HashSet::new();
-    let seen_talks = talk_collection.get(&talk_type).unwrap_or(&empty_hashset);
-    let talks = Talk::get_unseen_talks(talk_type, seen_talks).ok_or(ShioriError::TalkNotFound)?;
-    let derivative_talks = DerivaliveTalk::get_unseen_talks(talk_type, seen_talks)
+    let seen_talks = state
+      .persistent
+      .talk_collection
+      .get(&talk_type)
+      .cloned()
+      .unwrap_or_default();
+    let talks = Talk::get_unseen_talks(state, talk_type, &seen_talks).ok_or_else(|| ShioriError::TalkNotFound(format!("talks of {}", talk_type)))?;
+    let derivative_talks = DerivaliveTalk::get_unseen_talks(state, talk_type, &seen_talks)
       .unwrap_or_default()
       .iter()
-      .filter_map(get_parent_talk)
+      .filter_map(|t| get_parent_talk(state, t))
       .collect::<Vec<Talk>>();
     let combined_talks = talks
       .into_iter()
@@ -174,19 +180,17 @@ pub(crate) fn on_check_unseen_talks(req: &Request) -> Result<Response, ShioriErr
       .collect::<Vec<Talk>>();
     choosed_talk = combined_talks
       .choose(&mut rand::thread_rng())
-      .ok_or(ShioriError::TalkNotFound)?
+      .ok_or_else(|| ShioriError::TalkNotFound(format!("unseen talks of {}", talk_type)))?
       .clone();
   }
-  register_talk_collection(&choosed_talk.id, talk_type)?;
+  register_talk_collection(state, &choosed_talk.id, talk_type)?;
 
-  new_response_with_value_with_translate(
-    render_talk(&choosed_talk),
-    TranslateOption::with_shadow_completion(),
-  )
+  let m = render_talk(state, &choosed_talk);
+  new_response_with_value_with_translate(state, m, TranslateOption::with_shadow_completion())
 }
 
-pub(crate) fn register_talk_collection(id: &str, talk_type: TalkType) -> Result<(), ShioriError> {
-  let mut talk_collection = get_write(&TALK_COLLECTION);
+pub(crate) fn register_talk_collection(state: &mut GhostState, id: &str, talk_type: TalkType) -> Result<(), ShioriError> {
+  let talk_collection = &mut state.persistent.talk_collection;
   match talk_collection.get_mut(&talk_type) {
     Some(t) => {
       let key = id.to_string();
@@ -201,11 +205,11 @@ pub(crate) fn register_talk_collection(id: &str, talk_type: TalkType) -> Result<
   Ok(())
 }
 
-pub(crate) fn random_talks_analysis() -> String {
+pub(crate) fn random_talks_analysis(state: &mut GhostState) -> String {
   let mut s = String::new();
   let mut sum = 0;
   for talk_type in TalkType::all() {
-    let len = if let Some(v) = random_talks(talk_type) {
+    let len = if let Some(v) = random_talks(state, talk_type) {
       v.len()
     } else {
       0
@@ -214,11 +218,26 @@ pub(crate) fn random_talks_analysis() -> String {
     sum += len;
   }
 
+  // 出現条件のあるトークと、いまその条件を満たしているか
+  let conditions: String = talk_conditions(state.talk_content())
+    .iter()
+    .map(|(id, c)| {
+      format!(
+        "{} {}: {}\\n",
+        if c.evaluate(state) { "○" } else { "×" },
+        id,
+        c
+      )
+    })
+    .collect();
+
   format!(
     "\\_q{}
     ---\\n\
-    TOTAL: {}",
-    s, sum
+    TOTAL: {}\\n\
+    ---\\n\
+    {}",
+    s, sum, conditions
   )
 }
 
@@ -228,28 +247,26 @@ pub(crate) struct DerivaliveTalk {
   pub(crate) id: String,
   pub(crate) summary: String,
   pub(crate) text: String,
-  pub(crate) required_condition: Option<fn() -> bool>,
-  pub(crate) callback: Option<fn()>,
+  pub(crate) required_condition: Option<Condition>,
+  pub(crate) effects: Vec<Effect>,
 }
 
 impl DerivaliveTalk {
-  pub fn consume(&self) -> String {
-    if let Some(callback) = self.callback {
-      callback();
-    }
+  pub fn consume(&self, state: &mut GhostState) -> String {
+    apply_effects(state, &self.id, &self.effects);
     self.text.clone()
   }
 
-  pub fn get_unseen_talks(talk_type: TalkType, seen: &HashSet<String>) -> Option<Vec<DerivaliveTalk>> {
-    let talks = derivative_talks_per_talk_type()
+  pub fn get_unseen_talks(state: &mut GhostState, talk_type: TalkType, seen: &HashSet<String>) -> Option<Vec<DerivaliveTalk>> {
+    let talks = derivative_talks_per_talk_type(state)
       .get(&talk_type)
       .cloned()
       .unwrap_or_default();
     let mut result = Vec::new();
     for talk in talks {
       if !seen.contains(&talk.id) {
-        if let Some(condition) = talk.required_condition {
-          if condition() {
+        if let Some(condition) = &talk.required_condition {
+          if condition.evaluate(state) {
             result.push(talk);
           }
         } else {
@@ -269,36 +286,34 @@ impl DerivaliveTalk {
 /// dump_talks バイナリ（`cargo run --bin dump_talks`）から使う開発用ユーティリティ。
 pub fn render_all_talks() -> String {
   use crate::events::menu::QUESTIONS;
-  use crate::events::talk::first_boot::{FIRST_BOOT_TALK, FIRST_CLOSE_TALK, FIRST_RANDOMTALKS};
-  use randomtalk::{derivative_talks, get_parent_talk, RANDOMTALK_COMMENTS_LIVING_ROOM};
+  use crate::events::talk::first_boot::{first_boot_talk, first_close_talk, first_random_talks};
+  use randomtalk::{derivative_talks, get_parent_talk, randomtalk_comments_living_room};
+
+  let mut state = GhostState::default();
 
   let mut lines: Vec<String> = Vec::new();
-  lines.push(FIRST_BOOT_TALK.to_string());
-  for t in FIRST_RANDOMTALKS.iter() {
-    lines.push(t.to_string());
-  }
-  lines.push(FIRST_CLOSE_TALK.to_string());
+  lines.push(first_boot_talk(state.talk_content()));
+  lines.extend(first_random_talks(state.talk_content()));
+  lines.push(first_close_talk(state.talk_content()));
   for q in QUESTIONS.iter() {
-    lines.push(q.talk());
+    lines.push(q.talk(state.talk_content()));
   }
   for talk_type in TalkType::all() {
-    if let Some(talks) = random_talks(talk_type) {
+    if let Some(talks) = random_talks(&mut state, talk_type) {
       for t in talks {
         lines.push(t.text);
       }
     }
   }
-  for derivative_talk in derivative_talks().iter() {
-    if let Some(parent_talk) = get_parent_talk(derivative_talk) {
+  for derivative_talk in derivative_talks(state.talk_content()).iter() {
+    if let Some(parent_talk) = get_parent_talk(&mut state, derivative_talk) {
       lines.push(format!(
         "{}\\1{}{}",
         parent_talk.text, derivative_talk.summary, derivative_talk.text
       ));
     }
   }
-  for t in RANDOMTALK_COMMENTS_LIVING_ROOM.iter() {
-    lines.push(t.to_string());
-  }
+  lines.extend(randomtalk_comments_living_room(state.talk_content()));
   let mut result = lines.join("\n");
   result.push('\n');
   result
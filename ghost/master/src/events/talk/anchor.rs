@@ -1,4 +1,14 @@
-pub(crate) fn anchor_talks(id: &str) -> Option<String> {
+use crate::events::talk::content::TalkContent;
+
+/// アンカーから呼ぶトーク。外部ファイルにあればそちらを使う
+pub(crate) fn anchor_talks(content: &TalkContent, id: &str) -> Option<String> {
+  if let Some(text) = content.anchor.get(id) {
+    return Some(text.clone());
+  }
+  builtin_anchor_talks(id)
+}
+
+pub(crate) fn builtin_anchor_talks(id: &str) -> Option<String> {
   match id {
     "Misemono" => Some(
       "\
@@ -0,0 +1,80 @@
+//! チェイントーク
+//!
+//! トークや触り反応のあと、決まった部位に一定時間内に触れたときだけ話す続きのトーク。
+//! ランダムトーク・質問への答え・触り反応の副作用 Effect::ArmChainTalk(ID) で待機状態にし、
+//! 発火は mouse::check_chain_talk が行う。
+//! talks/ のファイルでも、トークと並べて定義・差し替えできる。
+//!
+//! ```toml
+//! [[chain]]
+//! id = "冷たい手を握る"
+//! target_part = "0handnade"
+//! within_secs = 30
+//! text = "h1111205\\1差し出された手を、そっと握った。……"
+//! ```
+use crate::events::talk::content::TalkContent;
+use crate::events::talk::effect::Effect;
+use crate::system::variables::{ChainTalkState, GhostState};
+use serde::Deserialize;
+
+/// 体温の話のあとに手を握る
+pub(crate) const CHAIN_HOLD_COLD_HAND: &str = "冷たい手を握る";
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ChainTalk {
+  pub id: String,
+  /// 対象部位のイベント名（例: "0handnade"）
+  pub target_part: String,
+  /// 待機する秒数
+  pub within_secs: u64,
+  pub text: String,
+  /// 発火したときの副作用
+  #[serde(default)]
+  pub effects: Vec<Effect>,
+}
+
+impl ChainTalk {
+  /// 待機状態にする。すでに待機中のものは置き換わる
+  pub fn arm(&self, state: &mut GhostState) {
+    state.volatile.chain_talk_state = Some(ChainTalkState {
+      target_part: self.target_part.clone(),
+      chain_text: self.text.clone(),
+      expires_at: state.volatile.ghost_up_time + self.within_secs,
+      effects: self.effects.clone(),
+    });
+  }
+}
+
+fn builtin_chain_talks() -> Vec<ChainTalk> {
+  vec![ChainTalk {
+    id: CHAIN_HOLD_COLD_HAND.to_string(),
+    target_part: "0handnade".to_string(),
+    within_secs: 30,
+    text: "\
+      h1111205\\1差し出された手を、そっと握った。\\n\\n[half]\
+      h1111204……ね、冷たいでしょう。\\n\
+      h1111210それでも離さないのね。\\n\\n[half]\
+      h1111205……あなたの温度が、少しだけ移ってくるわ。\\n\
+      h1111204すぐに冷めてしまうけれど。\
+      "
+    .to_string(),
+    effects: vec![],
+  }]
+}
+
+/// すべてのチェイントーク。外部ファイルのもので同じIDを差し替え、新しいものを追加する
+pub(crate) fn chain_talks(content: &TalkContent) -> Vec<ChainTalk> {
+  let mut talks = builtin_chain_talks();
+  for chain in content.chain.iter() {
+    match talks.iter_mut().find(|t| t.id == chain.id) {
+      Some(t) => *t = chain.clone(),
+      None => talks.push(chain.clone()),
+    }
+  }
+  talks
+}
+
+pub(crate) fn chain_talk(content: &TalkContent, id: &str) -> Option<ChainTalk> {
+  chain_talks(content).into_iter().find(|t| t.id == id)
+}
@@ -0,0 +1,188 @@
+//! トークの出現条件
+//!
+//! 関数ではなくデータとして持つことで、外部ファイルに書いたり、分析用に一覧表示したりできる。
+//! TOMLでは次のように書く。
+//!
+//! ```toml
+//! condition = { and = [{ hour_range = { start = 19, end = 3 } }, { not = { place = "Library" } }] }
+//! ```
+use crate::events::talk::TalkingPlace;
+use crate::system::variables::{EventFlag, GhostState};
+use core::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Condition {
+  /// start時からend時まで（両端を含む）。start > end なら日をまたぐ
+  HourRange {
+    start: u32,
+    end: u32,
+  },
+  /// いずれかの月
+  Months(Vec<u32>),
+  /// 起動からの経過秒数が未満
+  UptimeLessThan(u64),
+  /// 起動からの経過秒数が以上
+  UptimeAtLeast(u64),
+  Flag(EventFlag),
+  Place(TalkingPlace),
+  /// 没入度が以上
+  ImmersionAtLeast(u32),
+  /// そのIDのトークを見たことがある
+  TalkSeen(String),
+  And(Vec<Condition>),
+  Or(Vec<Condition>),
+  Not(Box<Condition>),
+}
+
+impl Condition {
+  pub fn evaluate(&self, state: &GhostState) -> bool {
+    match self {
+      Self::HourRange { start, end } => {
+        let hour = state.local_time().hour;
+        if start <= end {
+          (*start..=*end).contains(&hour)
+        } else {
+          hour >= *start || hour <= *end
+        }
+      }
+      Self::Months(months) => months.contains(&state.local_time().month),
+      Self::UptimeLessThan(secs) => state.volatile.ghost_up_time < *secs,
+      Self::UptimeAtLeast(secs) => state.volatile.ghost_up_time >= *secs,
+      Self::Flag(flag) => state.persistent.flags.check(flag),
+      Self::Place(place) => state.volatile.talking_place == *place,
+      Self::ImmersionAtLeast(degrees) => state.volatile.immersive_degrees >= *degrees,
+      Self::TalkSeen(id) => state
+        .persistent
+        .talk_collection
+        .values()
+        .any(|ids| ids.contains(id)),
+      Self::And(conditions) => conditions.iter().all(|c| c.evaluate(state)),
+      Self::Or(conditions) => conditions.iter().any(|c| c.evaluate(state)),
+      Self::Not(condition) => !condition.evaluate(state),
+    }
+  }
+}
+
+impl Display for Condition {
+  fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+    let join = |conditions: &[Condition], sep: &str| {
+      conditions
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(sep)
+    };
+    match self {
+      Self::HourRange { start, end } => write!(f, "{}時〜{}時", start, end),
+      Self::Months(months) => write!(
+        f,
+        "{}月",
+        months
+          .iter()
+          .map(|m| m.to_string())
+          .collect::<Vec<_>>()
+          .join("・")
+      ),
+      Self::UptimeLessThan(secs) => write!(f, "起動から{}秒未満", secs),
+      Self::UptimeAtLeast(secs) => write!(f, "起動から{}秒以上", secs),
+      Self::Flag(flag) => write!(f, "{:?}", flag),
+      Self::Place(place) => write!(f, "{}", place),
+      Self::ImmersionAtLeast(degrees) => write!(f, "没入度{}以上", degrees),
+      Self::TalkSeen(id) => write!(f, "「{}」既読", id),
+      Self::And(conditions) => write!(f, "({})", join(conditions, " かつ ")),
+      Self::Or(conditions) => write!(f, "({})", join(conditions, " または ")),
+      Self::Not(condition) => write!(f, "{}でない", condition),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::talk::TalkType;
+  use crate::system::clock::{FakeClock, LocalTime};
+  use std::sync::Arc;
+
+  fn state_at(month: u32, hour: u32) -> (GhostState, FakeClock) {
+    let clock = FakeClock::new(LocalTime::new(2026, month, 10, hour, 0, 0));
+    (GhostState::new(Arc::new(clock.clone())), clock)
+  }
+
+  #[test]
+  fn test_hour_range_wraps_midnight() {
+    let night = Condition::HourRange { start: 19, end: 3 };
+    let evening = Condition::HourRange { start: 17, end: 19 };
+    let (state, clock) = state_at(1, 19);
+    assert!(night.evaluate(&state));
+    assert!(evening.evaluate(&state));
+    clock.set(LocalTime::new(2026, 1, 10, 3, 0, 0));
+    assert!(night.evaluate(&state));
+    assert!(!evening.evaluate(&state));
+    clock.set(LocalTime::new(2026, 1, 10, 12, 0, 0));
+    assert!(!night.evaluate(&state));
+  }
+
+  #[test]
+  fn test_state_conditions() {
+    let (mut state, _) = state_at(12, 12);
+    assert!(Condition::Months(vec![12, 1, 2]).evaluate(&state));
+    assert!(!Condition::Months(vec![6]).evaluate(&state));
+
+    state.volatile.ghost_up_time = 60 * 15;
+    assert!(!Condition::UptimeLessThan(60 * 15).evaluate(&state));
+    assert!(Condition::UptimeAtLeast(60 * 15).evaluate(&state));
+
+    let unlocked = Condition::Flag(EventFlag::TalkTypeUnlock(TalkType::Servant));
+    assert!(!unlocked.evaluate(&state));
+    let now = state.local_time();
+    state
+      .persistent
+      .flags
+      .done(EventFlag::TalkTypeUnlock(TalkType::Servant), now);
+    assert!(unlocked.evaluate(&state));
+
+    assert!(Condition::Place(TalkingPlace::LivingRoom).evaluate(&state));
+    state.volatile.talking_place = TalkingPlace::Library;
+    assert!(!Condition::Place(TalkingPlace::LivingRoom).evaluate(&state));
+
+    state.volatile.immersive_degrees = 50;
+    assert!(Condition::ImmersionAtLeast(50).evaluate(&state));
+    assert!(!Condition::ImmersionAtLeast(51).evaluate(&state));
+
+    let seen = Condition::TalkSeen("中庸".to_string());
+    assert!(!seen.evaluate(&state));
+    state
+      .persistent
+      .talk_collection
+      .entry(TalkType::AboutMe)
+      .or_default()
+      .insert("中庸".to_string());
+    assert!(seen.evaluate(&state));
+  }
+
+  #[test]
+  fn test_combinators_and_serialization() {
+    let (state, _) = state_at(12, 20);
+    let c = Condition::And(vec![
+      Condition::HourRange { start: 19, end: 3 },
+      Condition::Or(vec![
+        Condition::Months(vec![6]),
+        Condition::Not(Box::new(Condition::Place(TalkingPlace::Library))),
+      ]),
+    ]);
+    assert!(c.evaluate(&state));
+    assert!(!Condition::Not(Box::new(c.clone())).evaluate(&state));
+    assert_eq!(c.to_string(), "(19時〜3時 かつ (6月 または 書斎でない))");
+
+    let toml = r#"condition = { and = [{ hour_range = { start = 19, end = 3 } }, { or = [{ months = [6] }, { not = { place = "Library" } }] }] }"#;
+    #[derive(Deserialize)]
+    struct Wrapper {
+      condition: Condition,
+    }
+    assert_eq!(toml::from_str::<Wrapper>(toml).unwrap().condition, c);
+    let json = serde_json::to_string(&c).unwrap();
+    assert_eq!(serde_json::from_str::<Condition>(&json).unwrap(), c);
+  }
+}
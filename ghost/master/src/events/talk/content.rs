@@ -0,0 +1,470 @@
+//! トークの文章を外部ファイルから読み込む
+//!
+//! ゴーストのフォルダの talks/ にある *.toml と *.json を、ファイル名の順に読み込む。
+//! 組み込みのトークと同じIDのものは文章だけを差し替え、新しいIDのものは追加する。
+//! 出現条件は condition に、話したときの副作用は effects に書ける（書式は condition, effect モジュールを参照）。
+//! 書かなければ、差し替えたトークは組み込みのものを使い、追加したトークは常に出現し副作用もない。
+//! ファイルがない項目や読めなかったファイルの分は、組み込みのトークをそのまま使う。
+//!
+//! ```toml
+//! [[random]]
+//! id = "別れの悲しみ"
+//! text = "h1111110「別れがこんなに悲しいなら、……"
+//!
+//! # 追加するときは talk_type が必要
+//! [[random]]
+//! id = "新しいトーク"
+//! talk_type = "AboutMe"
+//! text = "……"
+//! condition = { months = [12, 1, 2] }
+//!
+//! # 追加するときは parent_id, summary, text が必要
+//! [[derivative]]
+//! id = "服装へのこだわり・昔から"
+//! summary = "『つまり、その服装は昔から？』"
+//!
+//! [anchor]
+//! Misemono = "……"
+//!
+//! # キーは質問の番号
+//! [question]
+//! 5 = "……"
+//!
+//! # 質問に答えたときの副作用。キーは質問の番号。組み込みのものを丸ごと差し替える（空にすれば副作用なし）
+//! [question_effects]
+//! 23 = [{ arm_chain_talk = "冷たい手を握る" }, { adjust_immersion = 5 }]
+//!
+//! [comments]
+//! living_room = ["霧が濃い。"]
+//! library_inactive = ["薄暗い中に、彼女の声だけが響く。"]
+//!
+//! [first_boot]
+//! boot = "……"
+//! close = "……"
+//!
+//! # 初回ランダムトーク。キーは何番目か（1から）。
+//! # 進み具合を番号でセーブデータに記録するため、差し替えられるだけで数は変えられない
+//! [first_boot.randomtalk]
+//! 1 = "……"
+//!
+//! # チェイントーク（chain モジュールを参照）
+//! [[chain]]
+//! id = "冷たい手を握る"
+//! target_part = "0handnade"
+//! within_secs = 30
+//! text = "……"
+//! ```
+//!
+//! 読み込んだものは GhostState が持つ。デバッグモードでは r キーで読み込み直せる。
+use crate::events::talk::chain::ChainTalk;
+use crate::events::talk::condition::Condition;
+use crate::events::talk::effect::Effect;
+use crate::events::talk::{DerivaliveTalk, TalkType};
+use crate::system::variables::GhostState;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+pub(crate) const TALK_CONTENT_DIR: &str = "talks";
+
+#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct TalkContent {
+  pub random: Vec<RandomTalkEntry>,
+  pub derivative: Vec<DerivativeTalkEntry>,
+  pub anchor: BTreeMap<String, String>,
+  pub question: BTreeMap<String, String>,
+  pub question_effects: BTreeMap<String, Vec<Effect>>,
+  pub comments: CommentsEntry,
+  pub first_boot: FirstBootEntry,
+  /// 同じIDの組み込みのチェイントークは丸ごと差し替える
+  pub chain: Vec<ChainTalk>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RandomTalkEntry {
+  pub id: String,
+  /// 追加するトークの種類。差し替えるときは不要
+  #[serde(default)]
+  pub talk_type: Option<TalkType>,
+  pub text: String,
+  #[serde(default)]
+  pub condition: Option<Condition>,
+  #[serde(default)]
+  pub effects: Option<Vec<Effect>>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DerivativeTalkEntry {
+  pub id: String,
+  #[serde(default)]
+  pub parent_id: Option<String>,
+  #[serde(default)]
+  pub summary: Option<String>,
+  #[serde(default)]
+  pub text: Option<String>,
+  #[serde(default)]
+  pub condition: Option<Condition>,
+  #[serde(default)]
+  pub effects: Option<Vec<Effect>>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CommentsEntry {
+  pub living_room: Option<Vec<String>>,
+  pub library_inactive: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct FirstBootEntry {
+  pub boot: Option<String>,
+  pub close: Option<String>,
+  /// キーは何番目か（1から）
+  pub randomtalk: BTreeMap<String, String>,
+}
+
+/// 読み込んだファイルと、読めなかったファイルのエラー
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TalkContentReport {
+  pub files: Vec<String>,
+  pub errors: Vec<String>,
+}
+
+impl TalkContent {
+  /// 拡張子に応じてTOMLかJSONとして読む
+  pub fn parse_file(path: &Path) -> Result<Self, String> {
+    let s = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    match path.extension().and_then(|e| e.to_str()) {
+      Some("toml") => toml::from_str(&s).map_err(|e| e.to_string()),
+      Some("json") => serde_json::from_str(&s).map_err(|e| e.to_string()),
+      _ => Err("unsupported extension".to_string()),
+    }
+  }
+
+  /// dir の *.toml と *.json をファイル名の順に読んで重ねる。dir がなければ空
+  pub fn load_dir(dir: &Path) -> (Self, TalkContentReport) {
+    let mut content = Self::default();
+    let mut report = TalkContentReport::default();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+      return (content, report);
+    };
+    let mut paths: Vec<_> = entries
+      .filter_map(|e| e.ok().map(|e| e.path()))
+      .filter(|p| {
+        matches!(
+          p.extension().and_then(|e| e.to_str()),
+          Some("toml" | "json")
+        )
+      })
+      .collect();
+    paths.sort();
+    for path in paths {
+      match Self::parse_file(&path) {
+        Ok(c) => {
+          content.merge(c);
+          report.files.push(path.display().to_string());
+        }
+        Err(e) => report.errors.push(format!("{}: {}", path.display(), e)),
+      }
+    }
+    (content, report)
+  }
+
+  /// other を後から読んだものとして重ねる。同じIDは後のものが優先される
+  pub fn merge(&mut self, other: Self) {
+    self.random.extend(other.random);
+    self.derivative.extend(other.derivative);
+    self.chain.extend(other.chain);
+    self.anchor.extend(other.anchor);
+    self.question.extend(other.question);
+    self.question_effects.extend(other.question_effects);
+    if other.comments.living_room.is_some() {
+      self.comments.living_room = other.comments.living_room;
+    }
+    if other.comments.library_inactive.is_some() {
+      self.comments.library_inactive = other.comments.library_inactive;
+    }
+    if other.first_boot.boot.is_some() {
+      self.first_boot.boot = other.first_boot.boot;
+    }
+    if other.first_boot.close.is_some() {
+      self.first_boot.close = other.first_boot.close;
+    }
+    self
+      .first_boot
+      .randomtalk
+      .extend(other.first_boot.randomtalk);
+  }
+
+  /// IDごとのランダムトーク。同じIDが複数あれば後のもの
+  fn random_by_id(&self) -> BTreeMap<&str, &RandomTalkEntry> {
+    self.random.iter().map(|e| (e.id.as_str(), e)).collect()
+  }
+
+  /// 組み込みのランダムトークを差し替えるもの
+  pub fn random_entry(&self, id: &str) -> Option<&RandomTalkEntry> {
+    self.random.iter().rev().find(|e| e.id == id)
+  }
+
+  /// talk_type に追加するランダムトーク。builtin_ids は組み込みのトークのID
+  pub fn added_random_talks(&self, talk_type: TalkType, builtin_ids: &HashSet<String>) -> Vec<&RandomTalkEntry> {
+    self
+      .random_by_id()
+      .into_values()
+      .filter(|e| e.talk_type == Some(talk_type) && !builtin_ids.contains(&e.id))
+      .collect()
+  }
+
+  /// 派生トークの文章を差し替え、足りない項目のない新しいものを追加する
+  pub fn apply_derivative(&self, talks: &mut Vec<DerivaliveTalk>) {
+    let mut entries: BTreeMap<&str, &DerivativeTalkEntry> = BTreeMap::new();
+    for e in &self.derivative {
+      entries.insert(e.id.as_str(), e);
+    }
+    for (id, e) in entries {
+      if let Some(talk) = talks.iter_mut().find(|t| t.id == id) {
+        if let Some(summary) = &e.summary {
+          talk.summary = summary.clone();
+        }
+        if let Some(text) = &e.text {
+          talk.text = text.clone();
+        }
+        if let Some(condition) = &e.condition {
+          talk.required_condition = Some(condition.clone());
+        }
+        if let Some(effects) = &e.effects {
+          talk.effects = effects.clone();
+        }
+      } else if let (Some(parent_id), Some(summary), Some(text)) = (&e.parent_id, &e.summary, &e.text) {
+        talks.push(DerivaliveTalk {
+          parent_id: parent_id.clone(),
+          id: id.to_string(),
+          summary: summary.clone(),
+          text: text.clone(),
+          required_condition: e.condition.clone(),
+          effects: e.effects.clone().unwrap_or_default(),
+        });
+      }
+    }
+  }
+
+  pub fn question(&self, number: u32) -> Option<&str> {
+    self
+      .question
+      .iter()
+      .find(|(k, _)| k.parse::<u32>() == Ok(number))
+      .map(|(_, v)| v.as_str())
+  }
+
+  /// 質問に答えたときの副作用を差し替えるもの
+  pub fn question_effects(&self, number: u32) -> Option<&[Effect]> {
+    self
+      .question_effects
+      .iter()
+      .find(|(k, _)| k.parse::<u32>() == Ok(number))
+      .map(|(_, v)| v.as_slice())
+  }
+
+  /// number 番目（1から）の初回ランダムトーク
+  pub fn first_random_talk(&self, number: usize) -> Option<&str> {
+    self
+      .first_boot
+      .randomtalk
+      .iter()
+      .find(|(k, _)| k.parse::<usize>() == Ok(number))
+      .map(|(_, v)| v.as_str())
+  }
+}
+
+impl TalkContentReport {
+  /// デバッグ用の表示
+  pub fn summary(&self) -> String {
+    let mut lines = vec![format!(
+      "{} 件のファイルを読み込みました。",
+      self.files.len()
+    )];
+    lines.extend(self.files.iter().cloned());
+    if !self.errors.is_empty() {
+      lines.push(format!(
+        "{} 件のファイルが読み込めませんでした。",
+        self.errors.len()
+      ));
+      lines.extend(self.errors.iter().cloned());
+    }
+    lines.join("\\n")
+  }
+}
+
+/// talks/ を読み込み直し、state のトークの文章を置き換える
+pub(crate) fn reload_talk_content(state: &mut GhostState) -> TalkContentReport {
+  let (content, report) = TalkContent::load_dir(Path::new(TALK_CONTENT_DIR));
+  for e in &report.errors {
+    error!("トークのファイルを読み込めませんでした: {}", e);
+  }
+  if !report.files.is_empty() {
+    info!("トークのファイルを読み込みました: {:?}", report.files);
+  }
+  state.set_talk_content(content);
+  report
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::talk::first_boot::{first_random_talks, FIRST_RANDOMTALKS};
+  use tempfile::TempDir;
+
+  fn derivative(id: &str) -> DerivaliveTalk {
+    DerivaliveTalk {
+      parent_id: "parent".to_string(),
+      id: id.to_string(),
+      summary: "summary".to_string(),
+      text: "text".to_string(),
+      required_condition: None,
+      effects: vec![],
+    }
+  }
+
+  #[test]
+  fn test_load_dir_merges_in_file_order() {
+    let dir = TempDir::new().unwrap();
+    std::fs::write(
+      dir.path().join("a.toml"),
+      r#"
+        [[random]]
+        id = "既存"
+        text = "古い"
+
+        [[random]]
+        id = "追加"
+        talk_type = "Lore"
+        text = "追加した"
+        condition = { months = [12] }
+
+        [anchor]
+        Misemono = "見世物"
+
+        [question]
+        5 = "質問5"
+
+        [question_effects]
+        5 = [{ adjust_immersion = -10 }]
+
+        [comments]
+        living_room = ["コメント"]
+      "#,
+    )
+    .unwrap();
+    std::fs::write(
+      dir.path().join("b.json"),
+      r#"{"random": [{"id": "既存", "text": "新しい"}], "first_boot": {"close": "またね", "randomtalk": {"2": "二番目"}}}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("c.toml"), "[[random]]\nid = 1").unwrap();
+    std::fs::write(dir.path().join("memo.txt"), "ignored").unwrap();
+
+    let (content, report) = TalkContent::load_dir(dir.path());
+    assert_eq!(report.files.len(), 2, "{:?}", report);
+    assert_eq!(report.errors.len(), 1, "{:?}", report);
+    assert!(report.errors[0].contains("c.toml"));
+
+    assert_eq!(
+      content.random_entry("既存").map(|e| e.text.as_str()),
+      Some("新しい")
+    );
+    assert!(content.random_entry("なし").is_none());
+    let builtin = HashSet::from(["既存".to_string()]);
+    let added = content.added_random_talks(TalkType::Lore, &builtin);
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0].id, "追加");
+    assert_eq!(added[0].condition, Some(Condition::Months(vec![12])));
+    assert!(content
+      .added_random_talks(TalkType::AboutMe, &builtin)
+      .is_empty());
+    assert_eq!(
+      content.anchor.get("Misemono").map(|s| s.as_str()),
+      Some("見世物")
+    );
+    assert_eq!(content.question(5), Some("質問5"));
+    assert_eq!(
+      content.question_effects(5),
+      Some(&[Effect::AdjustImmersion(-10)][..])
+    );
+    assert_eq!(content.question_effects(6), None);
+    assert_eq!(
+      content.comments.living_room,
+      Some(vec!["コメント".to_string()])
+    );
+    assert_eq!(content.first_boot.close.as_deref(), Some("またね"));
+    assert_eq!(content.first_boot.boot, None);
+    assert_eq!(content.first_random_talk(2), Some("二番目"));
+    assert_eq!(content.first_random_talk(1), None);
+  }
+
+  #[test]
+  fn test_talk_content_is_per_state() {
+    let content: TalkContent = toml::from_str(
+      r#"
+        [first_boot.randomtalk]
+        2 = "差し替え"
+        99 = "範囲外"
+      "#,
+    )
+    .unwrap();
+    let mut state = GhostState::default();
+    state.set_talk_content(content);
+
+    // 差し替えても数は変わらない
+    let talks = first_random_talks(state.talk_content());
+    assert_eq!(talks.len(), FIRST_RANDOMTALKS.len());
+    assert_eq!(talks[0], FIRST_RANDOMTALKS[0]);
+    assert_eq!(talks[1], "差し替え");
+
+    // 他のインスタンスには影響しない
+    assert_eq!(
+      first_random_talks(GhostState::default().talk_content()),
+      *FIRST_RANDOMTALKS
+    );
+  }
+
+  #[test]
+  fn test_missing_dir_is_empty() {
+    let dir = TempDir::new().unwrap();
+    let (content, report) = TalkContent::load_dir(&dir.path().join(TALK_CONTENT_DIR));
+    assert_eq!(content, TalkContent::default());
+    assert_eq!(report, TalkContentReport::default());
+  }
+
+  #[test]
+  fn test_apply_derivative() {
+    let content: TalkContent = toml::from_str(
+      r#"
+        [[derivative]]
+        id = "既存"
+        summary = "新しい選択肢"
+
+        [[derivative]]
+        id = "追加"
+        parent_id = "parent"
+        summary = "追加した選択肢"
+        text = "追加した"
+
+        [[derivative]]
+        id = "不完全"
+        text = "親がない"
+      "#,
+    )
+    .unwrap();
+    let mut talks = vec![derivative("既存")];
+    content.apply_derivative(&mut talks);
+    assert_eq!(talks.len(), 2);
+    assert_eq!(talks[0].summary, "新しい選択肢");
+    assert_eq!(talks[0].text, "text");
+    assert_eq!(talks[1].id, "追加");
+    assert_eq!(talks[1].parent_id, "parent");
+  }
+}
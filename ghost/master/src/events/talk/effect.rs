@@ -0,0 +1,141 @@
+//! トークを話したときの副作用
+//!
+//! 関数ではなくデータとして持つことで、外部ファイルに書いたり、何が起きるかを一覧したりできる。
+//! 適用は apply_effects だけが行い、適用した内容はログに残る。
+//! TOMLでは次のように書く。
+//!
+//! ```toml
+//! effects = [
+//!   { set_flag = "FirstClose" },
+//!   { adjust_immersion = -10 },
+//!   { arm_chain_talk = "冷たい手を握る" },
+//! ]
+//! ```
+use crate::events::aitalk::IMMERSIVE_RATE_MAX;
+use crate::events::talk::chain::chain_talk;
+use crate::system::variables::{EventFlag, GhostState, PendingEvent};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Effect {
+  SetFlag(EventFlag),
+  ClearFlag(EventFlag),
+  /// 没入度を増減する。0〜IMMERSIVE_RATE_MAX に収める
+  AdjustImmersion(i32),
+  /// 次のイベントトークとして予約する
+  QueuePendingEvent(PendingEvent),
+  /// そのIDのチェイントークを待機状態にする
+  ArmChainTalk(String),
+  /// 候補から1つ選んで {last_selftalk_phrase} に入れる
+  SetSelftalkPhrase(Vec<String>),
+}
+
+impl Effect {
+  fn apply(&self, state: &mut GhostState) {
+    match self {
+      Self::SetFlag(flag) => {
+        let now = state.local_time();
+        state.persistent.flags.done(flag.clone(), now);
+      }
+      Self::ClearFlag(flag) => state.persistent.flags.delete(flag.clone()),
+      Self::AdjustImmersion(delta) => {
+        let degrees = state.volatile.immersive_degrees as i64 + *delta as i64;
+        state.volatile.immersive_degrees = degrees.clamp(0, IMMERSIVE_RATE_MAX as i64) as u32;
+      }
+      Self::QueuePendingEvent(event) => state.persistent.pending_event_talk = Some(event.clone()),
+      Self::ArmChainTalk(id) => match chain_talk(state.talk_content(), id) {
+        Some(chain) => chain.arm(state),
+        None => error!("チェイントーク {} がありません", id),
+      },
+      Self::SetSelftalkPhrase(phrases) => {
+        let phrase = phrases
+          .choose(&mut thread_rng())
+          .cloned()
+          .unwrap_or_default();
+        state.volatile.last_selftalk_phrase = phrase;
+      }
+    }
+  }
+}
+
+/// source（トークのIDなど）の副作用を順に適用する
+pub(crate) fn apply_effects(state: &mut GhostState, source: &str, effects: &[Effect]) {
+  for effect in effects {
+    info!("effect of {}: {:?}", source, effect);
+    effect.apply(state);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::talk::chain::CHAIN_HOLD_COLD_HAND;
+
+  #[test]
+  fn test_apply_effects() {
+    let mut state = GhostState::default();
+    state.volatile.ghost_up_time = 100;
+    state.volatile.immersive_degrees = 95;
+    apply_effects(
+      &mut state,
+      "test",
+      &[
+        Effect::SetFlag(EventFlag::FirstClose),
+        Effect::AdjustImmersion(10),
+        Effect::QueuePendingEvent(PendingEvent::FirstPlaceChange),
+        Effect::ArmChainTalk(CHAIN_HOLD_COLD_HAND.to_string()),
+        Effect::SetSelftalkPhrase(vec!["ペン先".to_string()]),
+      ],
+    );
+    assert!(state.persistent.flags.check(&EventFlag::FirstClose));
+    assert_eq!(state.volatile.immersive_degrees, IMMERSIVE_RATE_MAX);
+    assert_eq!(
+      state.persistent.pending_event_talk,
+      Some(PendingEvent::FirstPlaceChange)
+    );
+    let chain = state.volatile.chain_talk_state.clone().unwrap();
+    assert_eq!(chain.target_part, "0handnade");
+    assert_eq!(chain.expires_at, 130);
+    assert_eq!(state.volatile.last_selftalk_phrase, "ペン先");
+
+    apply_effects(
+      &mut state,
+      "test",
+      &[
+        Effect::AdjustImmersion(-200),
+        Effect::ClearFlag(EventFlag::FirstClose),
+        Effect::ArmChainTalk("存在しない".to_string()),
+      ],
+    );
+    assert_eq!(state.volatile.immersive_degrees, 0);
+    assert!(!state.persistent.flags.check(&EventFlag::FirstClose));
+    assert!(state.volatile.chain_talk_state.is_some());
+  }
+
+  #[test]
+  fn test_deserialize_effects() {
+    #[derive(Deserialize)]
+    struct Wrapper {
+      effects: Vec<Effect>,
+    }
+    let toml = r#"
+      effects = [
+        { set_flag = "FirstClose" },
+        { adjust_immersion = -10 },
+        { arm_chain_talk = "冷たい手を握る" },
+      ]
+    "#;
+    let effects = toml::from_str::<Wrapper>(toml).unwrap().effects;
+    assert_eq!(
+      effects,
+      vec![
+        Effect::SetFlag(EventFlag::FirstClose),
+        Effect::AdjustImmersion(-10),
+        Effect::ArmChainTalk(CHAIN_HOLD_COLD_HAND.to_string()),
+      ]
+    );
+  }
+}
@@ -1,8 +1,27 @@
+use crate::events::talk::content::TalkContent;
 use crate::events::IMMERSIVE_ICON_COUNT;
 use std::sync::LazyLock;
 
 pub(crate) const FIRST_BOOT_MARKER: &str = "邂逅";
 
+/// 初回起動時のトーク。外部ファイルにあればそちらを使う
+pub(crate) fn first_boot_talk(content: &TalkContent) -> String {
+  content
+    .first_boot
+    .boot
+    .clone()
+    .unwrap_or_else(|| FIRST_BOOT_TALK.clone())
+}
+
+/// 初回終了時のトーク。外部ファイルにあればそちらを使う
+pub(crate) fn first_close_talk(content: &TalkContent) -> String {
+  content
+    .first_boot
+    .close
+    .clone()
+    .unwrap_or_else(|| FIRST_CLOSE_TALK.to_string())
+}
+
 pub(crate) static FIRST_BOOT_TALK: LazyLock<String> = LazyLock::new(|| {
   format!(
     "\\t\\*\
@@ -346,6 +365,20 @@ pub(crate) static FIRST_BOOT_TALK: LazyLock<String> = LazyLock::new(|| {
   )
 });
 
+/// 初回ランダムトーク。外部ファイルにあるものはそちらを使う。
+/// 数は組み込みのものから変わらない（進み具合を番号で記録しているため）
+pub(crate) fn first_random_talks(content: &TalkContent) -> Vec<String> {
+  FIRST_RANDOMTALKS
+    .iter()
+    .enumerate()
+    .map(|(i, t)| {
+      content
+        .first_random_talk(i + 1)
+        .map_or_else(|| t.clone(), |s| s.to_string())
+    })
+    .collect()
+}
+
 pub(crate) static FIRST_RANDOMTALKS: LazyLock<Vec<String>> = LazyLock::new(|| {
   vec![
     "\
@@ -1,12 +1,10 @@
-use crate::get_write;
-use crate::system::windows::get_local_time;
-use crate::LAST_SELFTALK_PHRASE;
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::system::variables::{get_read, GHOST_UP_TIME};
+use crate::system::variables::GhostState;
 
+use crate::events::talk::condition::Condition;
+use crate::events::talk::content::TalkContent;
+use crate::events::talk::effect::Effect;
 use crate::events::talk::{Talk, TalkType};
 
 use super::DerivaliveTalk;
@@ -38,33 +36,99 @@ pub(crate) const RANDOMTALK_COMMENTS_LIBRARY_INACTIVE: [&str; 6] = [
   "",
 ];
 
-fn is_near_night() -> bool {
-  let st = get_local_time();
-  let hour = st.wHour;
-  (17..=19).contains(&hour)
+/// 居間でのトークに添えるコメント。外部ファイルにあればそちらを使う
+pub(crate) fn randomtalk_comments_living_room(content: &TalkContent) -> Vec<String> {
+  content.comments.living_room.clone().unwrap_or_else(|| {
+    RANDOMTALK_COMMENTS_LIVING_ROOM
+      .iter()
+      .map(|s| s.to_string())
+      .collect()
+  })
 }
 
-fn is_night() -> bool {
-  let st = get_local_time();
-  let hour = st.wHour;
-  hour <= 3 || hour >= 19
+/// 書斎でのトークに添えるコメント。外部ファイルにあればそちらを使う
+pub(crate) fn randomtalk_comments_library_inactive(content: &TalkContent) -> Vec<String> {
+  content
+    .comments
+    .library_inactive
+    .clone()
+    .unwrap_or_else(|| {
+      RANDOMTALK_COMMENTS_LIBRARY_INACTIVE
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    })
+}
+
+fn is_near_night() -> Condition {
+  Condition::HourRange { start: 17, end: 19 }
 }
 
-fn is_winter() -> bool {
-  let st = get_local_time();
-  let month = st.wMonth;
-  month == 12 || month <= 2
+fn is_night() -> Condition {
+  Condition::HourRange { start: 19, end: 3 }
+}
+
+fn is_winter() -> Condition {
+  Condition::Months(vec![12, 1, 2])
 }
 
 struct RandomTalk {
   id: String,
   text: String,
-  required_condition: Option<fn() -> bool>,
-  callback: Option<fn()>,
+  required_condition: Option<Condition>,
+  effects: Vec<Effect>,
+}
+
+pub(crate) fn random_talks(state: &mut GhostState, talk_type: TalkType) -> Option<Vec<Talk>> {
+  let mut talks = Vec::new();
+  for st in all_random_talks(state.talk_content(), talk_type) {
+    if let Some(condition) = &st.required_condition {
+      if !condition.evaluate(state) {
+        continue;
+      }
+    }
+    talks.push(Talk::new(
+      Some(talk_type),
+      st.id,
+      st.text.to_string(),
+      st.effects,
+    ));
+  }
+  Some(talks)
+}
+
+/// 出現条件にかかわらず、talk_type のすべてのランダムトークの (ID, 文章)
+pub(crate) fn random_talk_texts(content: &TalkContent, talk_type: TalkType) -> Vec<(String, String)> {
+  all_random_talks(content, talk_type)
+    .into_iter()
+    .map(|st| (st.id, st.text))
+    .collect()
+}
+
+/// 出現条件にかかわらず、TalkType ごとのすべてのトークID（派生トークは親のTalkTypeに含める）。
+/// 今は条件を満たさないトークの履歴や抽選の偏りを、読み込み時に捨てないために使う
+pub(crate) fn all_talk_ids_per_type(content: &TalkContent) -> HashMap<TalkType, HashSet<String>> {
+  let derivatives = derivative_talks(content);
+  TalkType::all()
+    .into_iter()
+    .map(|talk_type| {
+      let mut ids: HashSet<String> = all_random_talks(content, talk_type)
+        .into_iter()
+        .map(|t| t.id)
+        .collect();
+      let children: Vec<String> = derivatives
+        .iter()
+        .filter(|d| ids.contains(&d.parent_id))
+        .map(|d| d.id.clone())
+        .collect();
+      ids.extend(children);
+      (talk_type, ids)
+    })
+    .collect()
 }
 
-pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
-  let strings: Vec<RandomTalk> = match talk_type {
+fn all_random_talks(content: &TalkContent, talk_type: TalkType) -> Vec<RandomTalk> {
+  let mut strings: Vec<RandomTalk> = match talk_type {
     TalkType::AboutMe => vec![
       RandomTalk {
         id: "別れの悲しみ".to_string(),
@@ -78,7 +142,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - 霊は姿を変えることはできない
       // - ハイネは人目を気にして外出を避けている
@@ -96,7 +160,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは服装には無頓着
       RandomTalk {
@@ -110,7 +174,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは恋愛とは無縁の人生だった
       RandomTalk {
@@ -129,7 +193,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは強い霊
       // - ハイネは霊たちに慕われている
@@ -150,7 +214,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - この街には霊が集まりやすい
       RandomTalk {
@@ -165,7 +229,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ここはハイネの生家
       RandomTalk {
@@ -179,7 +243,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは生家からあまり離れられない
       RandomTalk {
@@ -194,7 +258,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - 家はいとこの子孫が管理している
       // - いとこは帰っていない
@@ -208,7 +272,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは生前から内向的だった
       // - 社交の場でも本を読んでいた
@@ -225,7 +289,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // ハイネはインターネットにあえて触れていない
       RandomTalk {
@@ -241,7 +305,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // 泳げないまま死んだ、今さら溺れないのに気になる
       RandomTalk {
@@ -255,7 +319,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // 毎朝窓を開ける儀式的な習慣
       RandomTalk {
@@ -271,7 +335,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // 一人の時間が長いと声に出して考える癖がつく
       RandomTalk {
@@ -287,14 +351,14 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           h1121304……言っていなかったわよね？\
           "
         .to_string(),
-        required_condition: Some(|| {
-          let a: [&str; 3] = ["それは死人の", "ペン先", "違う、それは"];
-          let mut rng = thread_rng();
-          let choosed = a.choose(&mut rng).unwrap_or(&"");
-          *get_write(&LAST_SELFTALK_PHRASE) = choosed.to_string();
-          !choosed.is_empty()
-        }),
-        callback: None,
+        required_condition: None,
+        // 話す直前に独り言の内容を決める。{last_selftalk_phrase} は翻訳時に置き換わる
+        effects: vec![Effect::SetSelftalkPhrase(
+          ["それは死人の", "ペン先", "違う、それは"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        )],
       },
       RandomTalk {
         id: "館の静寂".to_string(),
@@ -309,8 +373,8 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           h1111204……近頃は、あまり聞こえないの。\
           "
         .to_string(),
-        required_condition: Some(is_night),
-        callback: None,
+        required_condition: Some(is_night()),
+        effects: vec![],
       },
       RandomTalk {
         id: "記憶の整理".to_string(),
@@ -326,7 +390,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "科学への興味".to_string(),
@@ -344,7 +408,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // 外から帰ってきたタイミングのトークに使えそう
       // RandomTalk {
@@ -361,7 +425,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
       //     まだ疲れるのよ。\
       //   ".to_string(),
       //   required_condition: None,
-      //   callback: None,
+      //   effects: vec![],
       // },
       RandomTalk {
         id: "刺繍のハンカチ".to_string(),
@@ -377,7 +441,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは虫が苦手（幼少時に極彩色の毛虫に刺された）
       RandomTalk {
@@ -392,7 +456,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
     ],
     TalkType::WithYou => vec![
@@ -410,7 +474,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "舌やけど".to_string(),
@@ -424,7 +488,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "リップクリーム".to_string(),
@@ -441,7 +505,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "ピアス".to_string(),
@@ -457,7 +521,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "くしゃみ".to_string(),
@@ -474,7 +538,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "食べ物の好き嫌い".to_string(),
@@ -490,7 +554,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "蝋燭の交換".to_string(),
@@ -504,7 +568,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - 幽霊は写真に写らない
       // - ハイネは現代の知識を持っている
@@ -522,7 +586,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - この街の霧は霊的なもの
       // - この街では霊が活発になる
@@ -540,7 +604,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ユーザはゴスファッションをしている
       // - ハイネは個性的なファッションを重んじる
@@ -557,7 +621,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ハイネは生前食が細かった(作業に没頭していると食事を忘れる)
       // - ハイネは生前家政婦を雇っていた
@@ -575,7 +639,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       // - ユーザは絵が得意
       // - ハイネの生きていた時代には肖像画は珍しかった
@@ -593,7 +657,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "振り子時計の調整".to_string(),
@@ -609,7 +673,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "菓子の切り分け".to_string(),
@@ -623,7 +687,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "匂い".to_string(),
@@ -637,8 +701,8 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           h1111210……今日は、その必要もなさそうね。\
           "
         .to_string(),
-        required_condition: Some(|| *get_read(&GHOST_UP_TIME) < 60 * 15), // 起動から15分以内限定のトーク
-        callback: None,
+        required_condition: Some(Condition::UptimeLessThan(60 * 15)), // 起動から15分以内限定のトーク
+        effects: vec![],
       },
       RandomTalk {
         id: "袖のほつれ".to_string(),
@@ -653,7 +717,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "携帯電話を使わない".to_string(),
@@ -670,7 +734,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "生きた字".to_string(),
@@ -684,7 +748,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "白くない息".to_string(),
@@ -699,8 +763,8 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           \\1白い息が、急に生々しく見えた。\
           "
         .to_string(),
-        required_condition: Some(is_winter),
-        callback: None,
+        required_condition: Some(is_winter()),
+        effects: vec![],
       },
       RandomTalk {
         id: "夜の灯り".to_string(),
@@ -716,8 +780,8 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           散々叱られたものよ。h1111205……さあ、続けて。\
           "
         .to_string(),
-        required_condition: Some(is_near_night),
-        callback: None,
+        required_condition: Some(is_near_night()),
+        effects: vec![],
       },
       // RandomTalk {
       //   id: "".to_string(),
@@ -736,7 +800,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
       //     "
       //   .to_string(),
       //   required_condition: None,
-      //   callback: None,
+      //   effects: vec![],
       // },
     ],
     TalkType::Lore => vec![
@@ -756,7 +820,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死体のうめき声".to_string(),
@@ -773,7 +837,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "屍蝋".to_string(),
@@ -796,7 +860,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死後の温かさ".to_string(),
@@ -814,7 +878,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "生長する死体".to_string(),
@@ -826,7 +890,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "土葬の空洞".to_string(),
@@ -840,7 +904,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "永遠の夢".to_string(),
@@ -854,7 +918,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "生体電気".to_string(),
@@ -866,7 +930,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死者の埋葬".to_string(),
@@ -882,7 +946,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "黒死病".to_string(),
@@ -900,7 +964,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "鏡を覆う".to_string(),
@@ -917,7 +981,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "人魂の正体".to_string(),
@@ -931,7 +995,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "満月と狂気".to_string(),
@@ -947,7 +1011,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死者の名前".to_string(),
@@ -963,7 +1027,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "鐘の音".to_string(),
@@ -980,7 +1044,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "動物磁気".to_string(),
@@ -996,7 +1060,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "髪の装身具".to_string(),
@@ -1011,7 +1075,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "デスマスク".to_string(),
@@ -1029,7 +1093,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "蜂に告げる".to_string(),
@@ -1049,7 +1113,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
     ],
     TalkType::Servant => vec![
@@ -1069,7 +1133,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "低級霊との契約".to_string(),
@@ -1087,7 +1151,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "あなたの価値".to_string(),
@@ -1100,7 +1164,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "従者の記憶".to_string(),
@@ -1119,7 +1183,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
     ],
     TalkType::Past => vec![
@@ -1132,7 +1196,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死体損壊".to_string(),
@@ -1146,7 +1210,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "惨めな人生".to_string(),
@@ -1155,7 +1219,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "行き場のない苦しみ".to_string(),
@@ -1170,7 +1234,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死の瞬間".to_string(),
@@ -1183,7 +1247,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "助けは遂げられず".to_string(),
@@ -1193,7 +1257,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死なない理由".to_string(),
@@ -1204,7 +1268,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "ふつうになりたかった".to_string(),
@@ -1220,7 +1284,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "人と本".to_string(),
@@ -1231,7 +1295,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "今度こそ無へ".to_string(),
@@ -1242,7 +1306,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "魂は消える".to_string(),
@@ -1255,7 +1319,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "人生の無意味".to_string(),
@@ -1266,7 +1330,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
     ],
     TalkType::Abstract => vec![
@@ -1282,7 +1346,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "感動と倦み".to_string(),
@@ -1298,7 +1362,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "納得のための因果".to_string(),
@@ -1311,7 +1375,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "怖いものを見るということ".to_string(),
@@ -1321,7 +1385,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "停滞を終わらせるために".to_string(),
@@ -1335,7 +1399,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "停滞の破壊".to_string(),
@@ -1352,7 +1416,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "極限の変化としての死".to_string(),
@@ -1364,7 +1428,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "死の向こう側".to_string(),
@@ -1373,7 +1437,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "沈んでいく".to_string(),
@@ -1390,7 +1454,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "人を解体したい".to_string(),
@@ -1404,7 +1468,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "わがままな祈り".to_string(),
@@ -1415,7 +1479,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "生者にとっての慰め".to_string(),
@@ -1428,7 +1492,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "不可逆な崩壊".to_string(),
@@ -1439,7 +1503,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "中途半端な助け".to_string(),
@@ -1449,7 +1513,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "レンズの歪み".to_string(),
@@ -1463,7 +1527,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "先の見えない苦しみ".to_string(),
@@ -1475,7 +1539,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "唯一の視点".to_string(),
@@ -1489,7 +1553,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "一つの個としての限界".to_string(),
@@ -1504,7 +1568,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "自己同一性の仮定".to_string(),
@@ -1519,7 +1583,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "自分の理解者は自分だけ".to_string(),
@@ -1533,7 +1597,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "得ることは失うこと".to_string(),
@@ -1544,7 +1608,7 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
       RandomTalk {
         id: "中庸".to_string(),
@@ -1557,29 +1621,72 @@ pub(crate) fn random_talks(talk_type: TalkType) -> Option<Vec<Talk>> {
           "
         .to_string(),
         required_condition: None,
-        callback: None,
+        effects: vec![],
       },
     ],
   };
 
-  let mut talks = Vec::new();
-  for st in strings {
-    if let Some(expr) = st.required_condition {
-      if !expr() {
-        continue;
+  // 外部ファイルのトークで文章を差し替え、新しいものを追加する
+  let builtin_ids: HashSet<String> = strings.iter().map(|st| st.id.clone()).collect();
+  for st in strings.iter_mut() {
+    if let Some(e) = content.random_entry(&st.id) {
+      st.text = e.text.clone();
+      if let Some(condition) = &e.condition {
+        st.required_condition = Some(condition.clone());
+      }
+      if let Some(effects) = &e.effects {
+        st.effects = effects.clone();
       }
     }
-    talks.push(Talk::new(
-      Some(talk_type),
-      st.id,
-      st.text.to_string(),
-      st.callback,
-    ));
   }
-  Some(talks)
+  strings.extend(
+    content
+      .added_random_talks(talk_type, &builtin_ids)
+      .into_iter()
+      .map(|e| RandomTalk {
+        id: e.id.clone(),
+        text: e.text.clone(),
+        required_condition: e.condition.clone(),
+        effects: e.effects.clone().unwrap_or_default(),
+      }),
+  );
+  strings
+}
+
+/// 出現条件のあるランダムトーク・派生トークの (ID, 条件)
+pub(crate) fn talk_conditions(content: &TalkContent) -> Vec<(String, Condition)> {
+  let random = TalkType::all()
+    .into_iter()
+    .flat_map(|t| all_random_talks(content, t))
+    .filter_map(|st| st.required_condition.map(|c| (st.id, c)));
+  let derivative = derivative_talks(content)
+    .into_iter()
+    .filter_map(|t| t.required_condition.map(|c| (t.id, c)));
+  random.chain(derivative).collect()
+}
+
+/// 副作用のあるランダムトーク・派生トークの (ID, 副作用)
+pub(crate) fn talk_effects(content: &TalkContent) -> Vec<(String, Vec<Effect>)> {
+  let random = TalkType::all()
+    .into_iter()
+    .flat_map(|t| all_random_talks(content, t))
+    .filter(|st| !st.effects.is_empty())
+    .map(|st| (st.id, st.effects));
+  let derivative = derivative_talks(content)
+    .into_iter()
+    .filter(|t| !t.effects.is_empty())
+    .map(|t| (t.id, t.effects));
+  random.chain(derivative).collect()
+}
+
+/// 派生トーク。外部ファイルのもので文章を差し替え、新しいものを追加する
+pub(crate) fn derivative_talks(content: &TalkContent) -> Vec<DerivaliveTalk> {
+  let mut talks = builtin_derivative_talks();
+  content.apply_derivative(&mut talks);
+  talks
 }
 
-pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
+pub(crate) fn builtin_derivative_talks() -> Vec<DerivaliveTalk> {
   vec![
     DerivaliveTalk {
       parent_id: "生前の記録".to_string(),
@@ -1597,7 +1704,7 @@ pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
         "
       .to_string(),
       required_condition: None,
-      callback: None,
+      effects: vec![],
     },
     DerivaliveTalk {
       parent_id: "服装へのこだわり".to_string(),
@@ -1610,7 +1717,7 @@ pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
         "
       .to_string(),
       required_condition: None,
-      callback: None,
+      effects: vec![],
     },
     DerivaliveTalk {
       parent_id: "服装へのこだわり".to_string(),
@@ -1627,7 +1734,7 @@ pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
         "
       .to_string(),
       required_condition: None,
-      callback: None,
+      effects: vec![],
     },
     DerivaliveTalk {
       parent_id: "生家の広さ".to_string(),
@@ -1645,7 +1752,7 @@ pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
         "
       .to_string(),
       required_condition: None,
-      callback: None,
+      effects: vec![],
     },
     DerivaliveTalk {
       parent_id: "生前の食事事情".to_string(),
@@ -1666,7 +1773,7 @@ pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
         "
       .to_string(),
       required_condition: None,
-      callback: None,
+      effects: vec![],
     },
     DerivaliveTalk {
       parent_id: "身体が弱い".to_string(),
@@ -1688,19 +1795,19 @@ pub(crate) fn derivative_talks() -> Vec<DerivaliveTalk> {
         "
       .to_string(),
       required_condition: None,
-      callback: None,
+      effects: vec![],
     },
   ]
 }
 
-pub(crate) fn derivative_talks_per_talk_type() -> HashMap<TalkType, Vec<DerivaliveTalk>> {
+pub(crate) fn derivative_talks_per_talk_type(state: &mut GhostState) -> HashMap<TalkType, Vec<DerivaliveTalk>> {
   let all_talks = TalkType::all()
     .iter()
-    .map(|t| random_talks(*t))
+    .map(|t| random_talks(state, *t))
     .flat_map(|t| t.unwrap_or_default())
     .collect::<Vec<_>>();
   let mut talks: HashMap<TalkType, Vec<DerivaliveTalk>> = HashMap::new();
-  for talk in derivative_talks() {
+  for talk in derivative_talks(state.talk_content()) {
     let parent_talk = match all_talks.iter().find(|t| t.id == talk.parent_id) {
       Some(t) => t,
       None => {
@@ -1720,12 +1827,12 @@ pub(crate) fn derivative_talks_per_talk_type() -> HashMap<TalkType, Vec<Derivali
   talks
 }
 
-pub(crate) fn derivative_talk_by_id(parent_id: &str) -> Option<Vec<DerivaliveTalk>> {
-  derivative_talks()
+pub(crate) fn derivative_talk_by_id(state: &mut GhostState, parent_id: &str) -> Option<Vec<DerivaliveTalk>> {
+  derivative_talks(state.talk_content())
     .into_iter()
     .filter(|t| {
       let condition_ok = match &t.required_condition {
-        Some(condition) => condition(),
+        Some(condition) => condition.evaluate(state),
         None => true,
       };
       t.parent_id == parent_id && condition_ok
@@ -1734,10 +1841,10 @@ pub(crate) fn derivative_talk_by_id(parent_id: &str) -> Option<Vec<DerivaliveTal
     .into()
 }
 
-pub(crate) fn get_parent_talk(derivative_talk: &DerivaliveTalk) -> Option<Talk> {
+pub(crate) fn get_parent_talk(state: &mut GhostState, derivative_talk: &DerivaliveTalk) -> Option<Talk> {
   let all_talks = TalkType::all()
     .iter()
-    .map(|t| random_talks(*t))
+    .map(|t| random_talks(state, *t))
     .flat_map(|t| t.unwrap_or_default())
     .collect::<Vec<_>>();
   let result = all_talks
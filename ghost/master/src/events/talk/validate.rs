@@ -0,0 +1,383 @@
+//! 全トークの検証。validate_talks バイナリから使う
+//!
+//! ランダムトーク・派生トーク・アンカーのトーク・質問・初回起動/終了のトーク・チェイントークを集め、
+//! IDの重複や親のない派生トーク、存在しないチェイントークの参照、描画や翻訳で問題になる記述を探す。
+use crate::events::menu::QUESTIONS;
+use crate::events::talk::anchor::anchor_talks;
+use crate::events::talk::chain::chain_talks;
+use crate::events::talk::content::{TalkContent, TalkContentReport, TALK_CONTENT_DIR};
+use crate::events::talk::effect::Effect;
+use crate::events::talk::first_boot::{first_boot_talk, first_close_talk, first_random_talks, FIRST_RANDOMTALKS};
+use crate::events::talk::randomtalk::{derivative_talks, random_talk_texts, talk_effects};
+use crate::events::talk::TalkType;
+use crate::events::translate::{check_scope, PLACEHOLDERS};
+use crate::lazy_regex;
+use crate::system::response::invalid_surface_parts;
+use core::fmt::{Display, Formatter};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// 親のトークがないと承知している派生トーク (ID, 理由)。親のトークを書いたら外す
+const KNOWN_ORPHAN_DERIVATIVES: [(&str, &str); 2] = [
+  (
+    "生前の記録・過去",
+    "親のトーク「生前の記録」がまだ書かれていない",
+  ),
+  (
+    "身体が弱い・お使い",
+    "親のトーク「身体が弱い」を出す場面が決まるまで保留している",
+  ),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TalkIssue {
+  /// 問題のあるトーク（"random/AboutMe/別れの悲しみ" など）
+  pub location: String,
+  pub message: String,
+}
+
+impl Display for TalkIssue {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.location, self.message)
+  }
+}
+
+impl TalkIssue {
+  fn new(location: &str, message: String) -> Self {
+    Self {
+      location: location.to_string(),
+      message,
+    }
+  }
+}
+
+/// talks/ を読み込んでから、全トークを検証する
+pub fn validate_talks() -> Vec<TalkIssue> {
+  let (content, report) = TalkContent::load_dir(Path::new(TALK_CONTENT_DIR));
+  check_all_talks(&content, &report)
+}
+
+fn check_all_talks(content: &TalkContent, report: &TalkContentReport) -> Vec<TalkIssue> {
+  let mut issues: Vec<TalkIssue> = report
+    .errors
+    .iter()
+    .map(|e| TalkIssue::new("talks", e.clone()))
+    .collect();
+  // (場所, 文章)
+  let mut texts: Vec<(String, String)> = Vec::new();
+
+  // ランダムトークと派生トークはトーク履歴でIDを共有する
+  let mut ids: HashMap<String, String> = HashMap::new();
+  let mut check_duplicate = |issues: &mut Vec<TalkIssue>, id: &str, location: &str| {
+    if let Some(first) = ids.insert(id.to_string(), location.to_string()) {
+      issues.push(TalkIssue::new(
+        location,
+        format!("IDが {} と重複しています", first),
+      ));
+    }
+  };
+
+  let mut random_ids = HashSet::new();
+  for talk_type in TalkType::all() {
+    for (id, text) in random_talk_texts(content, talk_type) {
+      let location = format!("random/{:?}/{}", talk_type, id);
+      check_duplicate(&mut issues, &id, &location);
+      random_ids.insert(id);
+      texts.push((location, text));
+    }
+  }
+
+  let derivatives = derivative_talks(content);
+  for talk in derivatives.iter() {
+    let location = format!("derivative/{}", talk.id);
+    check_duplicate(&mut issues, &talk.id, &location);
+    let known_orphan = KNOWN_ORPHAN_DERIVATIVES
+      .iter()
+      .any(|(id, _)| *id == talk.id);
+    if !random_ids.contains(&talk.parent_id) && !known_orphan {
+      issues.push(TalkIssue::new(
+        &location,
+        format!("親のトーク {} がありません", talk.parent_id),
+      ));
+    }
+    texts.push((format!("{}/summary", location), talk.summary.clone()));
+    texts.push((location, talk.text.clone()));
+  }
+
+  for q in QUESTIONS.iter() {
+    texts.push((format!("question/{}", q.0), q.talk(content)));
+  }
+
+  texts.push(("first_boot/boot".to_string(), first_boot_talk(content)));
+  for (i, t) in first_random_talks(content).into_iter().enumerate() {
+    texts.push((format!("first_boot/randomtalk/{}", i + 1), t));
+  }
+  texts.push(("first_boot/close".to_string(), first_close_talk(content)));
+
+  // 副作用から待機状態にするチェイントーク
+  let chains = chain_talks(content);
+  let mut effects: Vec<(String, Vec<Effect>)> = talk_effects(content)
+    .into_iter()
+    .map(|(id, e)| (format!("talk/{}", id), e))
+    .collect();
+  effects.extend(
+    QUESTIONS
+      .iter()
+      .map(|q| (format!("question/{}", q.0), q.effects(content))),
+  );
+  for chain in chains.iter() {
+    let location = format!("chain/{}", chain.id);
+    effects.push((location.clone(), chain.effects.clone()));
+    texts.push((location, chain.text.clone()));
+  }
+  for (location, effects) in effects.iter() {
+    for effect in effects {
+      if let Effect::ArmChainTalk(id) = effect {
+        if !chains.iter().any(|c| c.id == *id) {
+          issues.push(TalkIssue::new(
+            location,
+            format!("チェイントーク {} がありません", id),
+          ));
+        }
+      }
+    }
+  }
+
+  // アンカーのトークは、トークから参照されているものと外部ファイルにあるものをたどる
+  let mut anchors: BTreeSet<String> = content.anchor.keys().cloned().collect();
+  let mut visited = HashSet::new();
+  let mut i = 0;
+  while i < texts.len() || !anchors.is_empty() {
+    if i < texts.len() {
+      let (location, text) = &texts[i];
+      for id in anchor_references(text) {
+        if anchor_talks(content, &id).is_none() {
+          issues.push(TalkIssue::new(
+            location,
+            format!("アンカー {} のトークがありません", id),
+          ));
+        } else {
+          anchors.insert(id);
+        }
+      }
+      i += 1;
+    } else if let Some(id) = anchors.pop_first() {
+      if visited.insert(id.clone()) {
+        if let Some(text) = anchor_talks(content, &id) {
+          texts.push((format!("anchor/{}", id), text));
+        }
+      }
+    }
+  }
+
+  issues.extend(check_content_entries(content, &random_ids, &derivatives));
+
+  for (location, text) in texts.iter() {
+    issues.extend(check_text(location, text));
+  }
+  issues
+}
+
+/// 外部ファイルのうち、差し替え先も追加に必要な項目もなく使われないもの
+fn check_content_entries(content: &TalkContent, random_ids: &HashSet<String>, derivatives: &[crate::events::talk::DerivaliveTalk]) -> Vec<TalkIssue> {
+  let mut issues = Vec::new();
+  for e in content.random.iter() {
+    if e.talk_type.is_none() && !random_ids.contains(&e.id) {
+      issues.push(TalkIssue::new(
+        &format!("talks/random/{}", e.id),
+        "同じIDのトークがなく、追加するための talk_type もありません".to_string(),
+      ));
+    }
+  }
+  for e in content.derivative.iter() {
+    if !derivatives.iter().any(|d| d.id == e.id) {
+      issues.push(TalkIssue::new(
+        &format!("talks/derivative/{}", e.id),
+        "同じIDのトークがなく、追加するための parent_id, summary, text が揃っていません".to_string(),
+      ));
+    }
+  }
+  let questions = content.question.keys().map(|n| ("question", n)).chain(
+    content
+      .question_effects
+      .keys()
+      .map(|n| ("question_effects", n)),
+  );
+  for (table, number) in questions {
+    if !QUESTIONS.iter().any(|q| number.parse() == Ok(q.0)) {
+      issues.push(TalkIssue::new(
+        &format!("talks/{}/{}", table, number),
+        "この番号の質問はありません".to_string(),
+      ));
+    }
+  }
+  for number in content.first_boot.randomtalk.keys() {
+    if !(1..=FIRST_RANDOMTALKS.len()).any(|n| number.parse() == Ok(n)) {
+      issues.push(TalkIssue::new(
+        &format!("talks/first_boot/randomtalk/{}", number),
+        "この番号の初回ランダムトークはありません".to_string(),
+      ));
+    }
+  }
+  issues
+}
+
+fn anchor_references(text: &str) -> Vec<String> {
+  static RE_ANCHOR: LazyLock<Regex> = lazy_regex!(r"\\_a\[AnchorTalk,([^,\]]+)");
+  RE_ANCHOR
+    .captures_iter(text)
+    .map(|c| c[1].to_string())
+    .collect()
+}
+
+/// 1つのトークの文章を検証する
+fn check_text(location: &str, text: &str) -> Vec<TalkIssue> {
+  static RE_SURFACE_SNIPPET: LazyLock<Regex> = lazy_regex!(r"h(r)?([0-9]{7})");
+  static RE_PLACEHOLDER: LazyLock<Regex> = lazy_regex!(r"\{([^{}]*)\}");
+
+  let mut issues = Vec::new();
+
+  for caps in RE_SURFACE_SNIPPET.captures_iter(text) {
+    let Ok(surface) = caps[2].parse::<i32>() else {
+      continue;
+    };
+    let parts = invalid_surface_parts(surface);
+    if !parts.is_empty() {
+      issues.push(TalkIssue::new(
+        location,
+        format!(
+          "{}: {} のコードに対応するパーツがありません",
+          &caps[0],
+          parts.join(", ")
+        ),
+      ));
+    }
+  }
+
+  if let Err(e) = check_scope(text) {
+    issues.push(TalkIssue::new(location, e.to_string()));
+  }
+
+  if let Some(message) = check_quicksection(text) {
+    issues.push(TalkIssue::new(location, message));
+  }
+
+  for caps in RE_PLACEHOLDER.captures_iter(text) {
+    if !PLACEHOLDERS.contains(&&caps[1]) {
+      issues.push(TalkIssue::new(
+        location,
+        format!("不明なプレースホルダ {}", &caps[0]),
+      ));
+    }
+  }
+
+  issues
+}
+
+/// \_q と \![quicksection,...] の対応がとれていなければ、その内容
+fn check_quicksection(text: &str) -> Option<String> {
+  static RE_QUICKSECTION: LazyLock<Regex> = lazy_regex!(r"\\_q|\\!\[quicksection,(true|1|false|0)\]");
+
+  let mut in_quicksection = false;
+  for m in RE_QUICKSECTION.find_iter(text) {
+    match m.as_str() {
+      "\\_q" => in_quicksection = !in_quicksection,
+      "\\![quicksection,true]" | "\\![quicksection,1]" => {
+        if in_quicksection {
+          return Some(format!(
+            "クイックセクションの中で {} があります",
+            m.as_str()
+          ));
+        }
+        in_quicksection = true;
+      }
+      _ => {
+        if !in_quicksection {
+          return Some(format!(
+            "クイックセクションの外で {} があります",
+            m.as_str()
+          ));
+        }
+        in_quicksection = false;
+      }
+    }
+  }
+  if in_quicksection {
+    Some("クイックセクションが閉じられていません".to_string())
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_builtin_talks_are_valid() {
+    let issues = check_all_talks(&TalkContent::default(), &TalkContentReport::default());
+    assert!(
+      issues.is_empty(),
+      "{}",
+      issues
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+    );
+  }
+
+  #[test]
+  fn test_known_orphan_derivatives_are_still_orphans() {
+    // 親のトークができたら、許可の一覧から外す
+    let content = TalkContent::default();
+    let random_ids: HashSet<String> = TalkType::all()
+      .into_iter()
+      .flat_map(|t| random_talk_texts(&content, t))
+      .map(|(id, _)| id)
+      .collect();
+    for (id, reason) in KNOWN_ORPHAN_DERIVATIVES {
+      let talk = derivative_talks(&content)
+        .into_iter()
+        .find(|t| t.id == id)
+        .unwrap_or_else(|| panic!("{} がありません", id));
+      assert!(
+        !random_ids.contains(&talk.parent_id),
+        "{} の親のトークがあります（{}）",
+        id,
+        reason
+      );
+    }
+  }
+
+  #[test]
+  fn test_check_text() {
+    assert!(check_text("ok", "h1111201こんにちは、{user_name}。\\_q……\\_q").is_empty());
+
+    let messages = |text: &str| {
+      check_text("t", text)
+        .into_iter()
+        .map(|i| i.message)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      messages("h1111216こんにちは"),
+      vec!["h1111216: 目 のコードに対応するパーツがありません"]
+    );
+    assert_eq!(
+      messages("\\_q……"),
+      vec!["クイックセクションが閉じられていません"]
+    );
+    assert_eq!(
+      messages("\\![quicksection,0]……"),
+      vec!["クイックセクションの外で \\![quicksection,0] があります"]
+    );
+    assert_eq!(
+      messages("{username}"),
+      vec!["不明なプレースホルダ {username}"]
+    );
+    assert_eq!(messages("\\0あ@@@@@い@@@@@").len(), 1);
+  }
+}
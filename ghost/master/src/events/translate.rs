@@ -41,23 +41,25 @@ fn extract_scope(text: &str) -> Option<usize> {
 // 翻訳処理
 // ============================================================
 
-pub(crate) fn on_translate(text: String, complete_shadow: bool) -> Result<String, ShioriError> {
+pub(crate) fn on_translate(state: &mut GhostState, text: String, complete_shadow: bool) -> Result<String, ShioriError> {
   if text.is_empty() {
     return Ok(text);
   }
 
-  let translated = translate(text, complete_shadow)?;
+  let translated = translate(state, text, complete_shadow)?;
 
   Ok(format!("{}{}", REMOVE_BALLOON_NUM, translated))
 }
 
-pub fn translate(text: String, complete_shadow: bool) -> Result<String, ShioriError> {
-  static IGNORING_TRANSLATE_RANGE: LazyLock<Regex> = lazy_regex!(r"@@@@@(.*?)@@@@@");
+static IGNORING_TRANSLATE_RANGE: LazyLock<Regex> = lazy_regex!(r"@@@@@(.*?)@@@@@");
+
+/// 翻訳しない範囲（@@@@@で囲んだ部分）があるとき、前後のテキストがそれぞれスコープ指定で始まっているか
+pub(crate) fn check_scope(text: &str) -> Result<(), ShioriError> {
   static CHANGE_SCOPE_RE_PREFIX: LazyLock<FancyRegex> = lazy_fancy_regex!(r"^(\\[01])(?!w)|(\\p\[\d+\])");
 
-  let translate_targets = IGNORING_TRANSLATE_RANGE.split(&text).collect::<Vec<&str>>();
+  let translate_targets = IGNORING_TRANSLATE_RANGE.split(text).collect::<Vec<&str>>();
   let ignoring_ranges = IGNORING_TRANSLATE_RANGE
-    .captures_iter(&text)
+    .captures_iter(text)
     .filter_map(|c| c.get(1).map(|m| m.as_str()))
     .collect::<Vec<&str>>();
 
@@ -74,10 +76,21 @@ pub fn translate(text: String, complete_shadow: bool) -> Result<String, ShioriEr
       }
     }
   }
+  Ok(())
+}
+
+pub(crate) fn translate(state: &mut GhostState, text: String, complete_shadow: bool) -> Result<String, ShioriError> {
+  check_scope(&text)?;
+
+  let translate_targets = IGNORING_TRANSLATE_RANGE.split(&text).collect::<Vec<&str>>();
+  let ignoring_ranges = IGNORING_TRANSLATE_RANGE
+    .captures_iter(&text)
+    .filter_map(|c| c.get(1).map(|m| m.as_str()))
+    .collect::<Vec<&str>>();
 
   let mut results = String::new();
   for (i, target) in translate_targets.iter().enumerate() {
-    results.push_str(&translate_core(target.to_string(), complete_shadow)?);
+    results.push_str(&translate_core(state, target.to_string(), complete_shadow)?);
     if let Some(v) = ignoring_ranges.get(i) {
       results.push_str(&v.replace("@@@@@", ""));
     }
@@ -85,12 +98,12 @@ pub fn translate(text: String, complete_shadow: bool) -> Result<String, ShioriEr
   Ok(results)
 }
 
-fn translate_core(text: String, complete_shadow: bool) -> Result<String, ShioriError> {
+fn translate_core(state: &mut GhostState, text: String, complete_shadow: bool) -> Result<String, ShioriError> {
   static RE_SURFACE_SNIPPET: LazyLock<Regex> = lazy_regex!(r"h(r)?([0-9]{7})");
 
   // 変数を translate 時点で1回だけ読み取り（副作用を最小化）
-  let current_surface = *get_read(&CURRENT_SURFACE);
-  let shadow_script = render_shadow(complete_shadow);
+  let current_surface = state.volatile.current_surface;
+  let shadow_script = render_shadow(state, complete_shadow);
 
   // 全サーフェス記法を検出し、位置情報付きで収集
   let matches: Vec<_> = RE_SURFACE_SNIPPET.captures_iter(&text).collect();
@@ -123,8 +136,8 @@ fn translate_core(text: String, complete_shadow: bool) -> Result<String, ShioriE
       last_end = full_match.end();
     }
 
-    // CURRENT_SURFACEを手動更新（bind方式ではOnSurfaceChangeが発火しないため）
-    *get_write(&CURRENT_SURFACE) = prev_surface;
+    // current_surfaceを手動更新（bind方式ではOnSurfaceChangeが発火しないため）
+    state.volatile.current_surface = prev_surface;
 
     // 残りのテキストを追加
     result.push_str(&text[last_end..]);
@@ -138,6 +151,7 @@ fn translate_core(text: String, complete_shadow: bool) -> Result<String, ShioriE
   }
 
   translate_whole(
+    state,
     dialogs
       .iter()
       .enumerate()
@@ -220,18 +234,22 @@ fn translate_dialog(dialog: &mut Dialog) {
   dialog.text = result;
 }
 
-fn translate_whole(text: String) -> Result<String, ShioriError> {
+/// translate_whole で置き換えるプレースホルダ
+pub(crate) const PLACEHOLDERS: [&str; 2] = ["user_name", "last_selftalk_phrase"];
+
+fn translate_whole(state: &GhostState, text: String) -> Result<String, ShioriError> {
   static RE_LAST_WAIT: LazyLock<Regex> = lazy_regex!(r"\\_w\[([0-9]+)\]$");
 
   let mut translated = text.clone();
 
   translated = RE_LAST_WAIT.replace(&translated, "").to_string();
 
-  let user_name = get_read(&USER_NAME).clone();
-  translated = translated.replace("{user_name}", &user_name);
-
-  let last_selftalk_phrase = get_read(&LAST_SELFTALK_PHRASE).clone();
-  translated = translated.replace("{last_selftalk_phrase}", &last_selftalk_phrase);
+  // PLACEHOLDERS と揃える
+  translated = translated.replace("{user_name}", &state.persistent.user_name);
+  translated = translated.replace(
+    "{last_selftalk_phrase}",
+    &state.volatile.last_selftalk_phrase,
+  );
 
   Ok(translated)
 }
@@ -425,35 +443,6 @@ fn replace_with_check(text_part: &str, scope: usize, replaces: &[Replacee], in_q
   translated
 }
 
-// 1文字ずつ\\_qで囲めば口パクしなくなる
-pub(crate) fn replace_dialog_for_nomouthmove(text: String) -> Result<String, ShioriError> {
-  let text = translate(text, true)?;
-  let split_parts: Vec<&str> = RE_TEXT_ONLY.split(&text).collect();
-  let mut matches: Vec<String> = Vec::new();
-  for cap in RE_TEXT_ONLY.find_iter(&text) {
-    matches.push(cap.as_str().to_string());
-  }
-
-  let mut result = String::new();
-  for (i, splitted) in split_parts.iter().enumerate() {
-    result.push_str(
-      splitted
-        .chars()
-        .map(|c| c.to_string())
-        .collect::<Vec<_>>()
-        .join("\\_w[50]\\![quicksection,0]\\![quicksection,1]")
-        .as_str(),
-    );
-    if let Some(m) = matches.get(i) {
-      result.push_str(m);
-    }
-  }
-  Ok(format!(
-    "\\![quicksection,1]@@@@@{}\\![quicksection,0]@@@@@",
-    result
-  ))
-}
-
 #[cfg(test)]
 mod tests {
   use super::*;
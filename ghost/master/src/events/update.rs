@@ -1,23 +1,23 @@
+use crate::events::references::References;
 use crate::system::response::*;
+use crate::system::variables::GhostState;
 use shiorust::message::{Request, Response};
 
-pub(crate) fn on_update_begin(req: &Request) -> Response {
-  let refs = get_references(req);
-  if refs.len() < 5 {
-    return new_response_nocontent();
-  }
-  if refs[0] != "Crave The Grave" || refs[4] != "manual" {
+pub(crate) fn on_update_begin(state: &mut GhostState, req: &Request) -> Response {
+  let refs = References::from_request(req);
+  if refs.get_or_empty(0) != "Crave The Grave" || refs.get_or_empty(4) != "manual" {
     new_response_nocontent()
   } else {
     new_response_with_value_with_notranslate(
+      state,
       "\\1\\_qゴーストの更新を確認中……".to_string(),
       TranslateOption::none(),
     )
   }
 }
 
-pub(crate) fn on_update_result_ex(req: &Request) -> Response {
-  let refs = get_references(req);
+pub(crate) fn on_update_result_ex(state: &mut GhostState, req: &Request) -> Response {
+  let refs = References::from_request(req);
   let mut m = String::new();
   if refs.is_empty() {
     return new_response_nocontent();
@@ -53,7 +53,7 @@ pub(crate) fn on_update_result_ex(req: &Request) -> Response {
         _ => "更新成功",
       },
       "NG" => &format!("更新失敗({})", results[3]),
-      _ => &format!("不明なステータス({})", refs[0]),
+      _ => &format!("不明なステータス({})", refs.get_or_empty(0)),
     };
     m.push_str(&format!("{}({}): {}\\n", item_name, item_type, status));
     no_update = false;
@@ -62,6 +62,7 @@ pub(crate) fn on_update_result_ex(req: &Request) -> Response {
     return new_response_nocontent();
   }
   new_response_with_value_with_notranslate(
+    state,
     format!(
       "\\1\\_q{}{}",
       m, "\\n\\![*]\\q[更新履歴,https://apxxxxxxe.dev/works]\\x"
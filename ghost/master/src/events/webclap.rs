@@ -1,60 +1,59 @@
+use crate::events::references::References;
 use crate::system::error::ShioriError;
 use crate::system::response::*;
+use crate::system::variables::GhostState;
 use shiorust::message::{Request, Response};
 
 use super::talk::Talk;
 
-pub(crate) fn derivative_talk_request_open(event_id: &str) -> Result<Response, ShioriError> {
-  let last_talk = match Talk::all_talks()
-    .ok_or(ShioriError::TalkNotFound)?
+pub(crate) fn derivative_talk_request_open(state: &mut GhostState, event_id: &str) -> Result<Response, ShioriError> {
+  let last_talk = match Talk::all_talks(state)
+    .ok_or_else(|| ShioriError::TalkNotFound(event_id.to_string()))?
     .iter()
     .find(|t| t.id == event_id)
   {
     Some(t) => t.text.clone(),
     None => "".to_string(),
   };
-  new_response_with_value_with_translate(
-    format!(
-      "\\1\\![open,inputbox,OnDerivativeTalkRequestInput,0,このトークに対するリアクションの要望を送信できます。,--reference={}]{}",
-      event_id, last_talk
-    ),
-    TranslateOption::simple_translate(),
-  )
+  let m = format!(
+    "\\1\\![open,inputbox,OnDerivativeTalkRequestInput,0,このトークに対するリアクションの要望を送信できます。,--reference={}]{}",
+    event_id, last_talk
+  );
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
-pub(crate) fn on_derivative_talk_request_input(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  let text = refs[0];
-  let event_id = refs[2];
+pub(crate) fn on_derivative_talk_request_input(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let refs = References::from_request(req);
+  let text = refs.get(0)?;
+  let event_id = refs.get(2)?;
   let m = format!(
     "\\1\\![execute,http-post,https://webclap.apxxxxxxe.dev/clap,--param=Haine:{}:{},--async=webclap]",
     event_id, text
   );
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
-pub(crate) fn on_web_clap_open(_req: &Request) -> Result<Response, ShioriError> {
+pub(crate) fn on_web_clap_open(state: &mut GhostState, _req: &Request) -> Result<Response, ShioriError> {
   let m = "\
     \\1\\![open,inputbox,OnWebClapInput,0]Web拍手を送ります。\\n\
     感想やバグ報告、要望などをお送り下さい。\
     "
   .to_string();
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
-pub(crate) fn on_web_clap_input(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
+pub(crate) fn on_web_clap_input(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
   let m = format!(
     "\\1\\![execute,http-post,https://webclap.apxxxxxxe.dev/clap,--param=Haine:{},--async=webclap]",
-    refs[0]
+    References::from_request(req).get(0)?
   );
-  new_response_with_value_with_translate(m, TranslateOption::simple_translate())
+  new_response_with_value_with_translate(state, m, TranslateOption::simple_translate())
 }
 
-pub(crate) fn on_execute_http_complete(req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(req);
-  if refs[1] == "webclap" {
+pub(crate) fn on_execute_http_complete(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  if References::from_request(req).get(1)? == "webclap" {
     new_response_with_value_with_translate(
+      state,
       "\\1送信しました。".to_string(),
       TranslateOption::simple_translate(),
     )
@@ -63,11 +62,12 @@ pub(crate) fn on_execute_http_complete(req: &Request) -> Result<Response, Shiori
   }
 }
 
-pub(crate) fn on_execute_http_failure(_req: &Request) -> Result<Response, ShioriError> {
-  let refs = get_references(_req);
-  if refs[1] == "webclap" {
+pub(crate) fn on_execute_http_failure(state: &mut GhostState, req: &Request) -> Result<Response, ShioriError> {
+  let refs = References::from_request(req);
+  if refs.get(1)? == "webclap" {
     new_response_with_value_with_translate(
-      format!("\\1送信に失敗しました: {}", refs[4]),
+      state,
+      format!("\\1送信に失敗しました: {}", refs.get(4)?),
       TranslateOption::simple_translate(),
     )
   } else {
@@ -0,0 +1,134 @@
+// SSPから呼ばれるDLLエクスポート関数。HGLOBALの受け渡しだけを行い、処理は Shiori に任せる
+use crate::shiori::Shiori;
+use crate::system::response::{add_error_description, new_response_nocontent};
+use crate::system::variables::get_write;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{LazyLock, RwLock};
+
+use shiori_hglobal::*;
+
+use winapi::ctypes::c_long;
+use winapi::shared::minwindef::{BOOL, FALSE, HGLOBAL, TRUE};
+
+static SHIORI: LazyLock<RwLock<Option<Shiori>>> = LazyLock::new(|| RwLock::new(None));
+
+fn load_shiori(path: &str) -> BOOL {
+  match Shiori::load(path) {
+    Ok(shiori) => {
+      *get_write(&SHIORI) = Some(shiori);
+      TRUE
+    }
+    Err(e) => {
+      error!("error while loading: {}", e);
+      FALSE
+    }
+  }
+}
+
+#[no_mangle]
+pub extern "cdecl" fn loadu(h: HGLOBAL, len: c_long) -> BOOL {
+  match catch_unwind(AssertUnwindSafe(|| {
+    let v = GStr::capture(h, len as usize);
+    let s = match v.to_utf8_str() {
+      Ok(st) => {
+        // UTF-8に変換
+        st.to_string()
+      }
+      Err(e) => {
+        eprintln!("Failed to convert HGLOBAL to UTF-8: {:?}", e);
+        return FALSE;
+      }
+    };
+    load_shiori(&s)
+  })) {
+    Ok(result) => result,
+    Err(_) => {
+      eprintln!("loadu: panic caught at FFI boundary");
+      FALSE
+    }
+  }
+}
+
+#[no_mangle]
+pub extern "cdecl" fn load(h: HGLOBAL, len: c_long) -> BOOL {
+  match catch_unwind(AssertUnwindSafe(|| {
+    let v = GStr::capture(h, len as usize);
+    let s: String;
+    match v.to_utf8_str() {
+      Ok(st) => {
+        // UTF-8に変換
+        s = st.to_string();
+      }
+      Err(e) => {
+        eprintln!("Failed to convert HGLOBAL to UTF-8: {:?}", e);
+        match v.to_ansi_str() {
+          Ok(st) => {
+            // ANSIに変換
+            s = st.to_string_lossy().to_string();
+          }
+          Err(e) => {
+            eprintln!("Failed to convert HGLOBAL to ANSI: {:?}", e);
+            return FALSE;
+          }
+        }
+      }
+    };
+    load_shiori(&s)
+  })) {
+    Ok(result) => result,
+    Err(_) => {
+      eprintln!("load: panic caught at FFI boundary");
+      FALSE
+    }
+  }
+}
+
+#[no_mangle]
+pub extern "cdecl" fn unload() -> BOOL {
+  match catch_unwind(AssertUnwindSafe(|| {
+    if let Some(shiori) = get_write(&SHIORI).take() {
+      shiori.unload();
+    }
+    TRUE
+  })) {
+    Ok(result) => result,
+    Err(_) => {
+      eprintln!("unload: panic caught at FFI boundary");
+      TRUE
+    }
+  }
+}
+
+fn to_hglobal(bytes: Vec<u8>, len: &mut c_long) -> HGLOBAL {
+  let response_gstr = GStr::clone_from_slice_nofree(&bytes);
+  *len = response_gstr.len() as c_long;
+  response_gstr.handle()
+}
+
+#[allow(clippy::missing_safety_doc)]
+#[no_mangle]
+pub extern "cdecl" fn request(h: HGLOBAL, len: &mut c_long) -> HGLOBAL {
+  match catch_unwind(AssertUnwindSafe(|| {
+    // リクエストの取得。文字コードは Charset ヘッダに従って Shiori 側で解釈する
+    let v = GStr::capture(h, *len as usize);
+
+    let response = match get_write(&SHIORI).as_mut() {
+      Some(shiori) => shiori.request_bytes(v.as_bytes()),
+      None => {
+        let err = "request before load";
+        error!("{}", err);
+        let mut res = new_response_nocontent();
+        add_error_description(&mut res, err);
+        res.to_string().into_bytes()
+      }
+    };
+    to_hglobal(response, len)
+  })) {
+    Ok(result) => result,
+    Err(_) => {
+      eprintln!("request: panic caught at FFI boundary");
+      to_hglobal(new_response_nocontent().to_string().into_bytes(), len)
+    }
+  }
+}
@@ -0,0 +1,250 @@
+//! シナリオテストの実行器。
+//!
+//! `scenarios/*.toml` に書かれたイベント列を `events::handle_request` に順に流し、
+//! 各ステップの後でフラグ・保留中のストーリーイベント・会話場所・Valueの内容を検証する。
+//! 時刻は FakeClock で制御されるため、日付や正時に依存する挙動もテストできる。
+//!
+//! ```toml
+//! name = "初回起動"
+//! user_name = "test"
+//! start = { year = 2030, month = 4, day = 1, hour = 12, minute = 0, second = 0 }
+//!
+//! [[step]]
+//! event = "OnBoot"
+//! expect = { flags = ["FirstBoot"], value_contains = ["……"] }
+//!
+//! [[step]]
+//! wait = 60 # 60秒分のOnSecondChange（分が変わればOnMinuteChangeも）を送る
+//! ```
+use crate::events::handle_request;
+use crate::events::talk::TalkingPlace;
+use crate::system::clock::{Clock, FakeClock, LocalTime};
+use crate::system::variables::*;
+use serde::Deserialize;
+use shiorust::message::parts::*;
+use shiorust::message::Request;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// until_talk_count で無限ループしないための上限
+const MAX_ITERATIONS: u32 = 10000;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+  name: String,
+  user_name: Option<String>,
+  start: Option<LocalTime>,
+  #[serde(default, rename = "step")]
+  steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Step {
+  /// 送信するイベントID
+  event: Option<String>,
+  #[serde(default)]
+  references: Vec<String>,
+  /// event を送る回数（省略時1回）
+  repeat: Option<u32>,
+  /// 累計トーク数がこの値に達するまで event を送り続ける
+  until_talk_count: Option<u64>,
+  /// 指定秒数だけ時間を進める。1秒ごとにOnSecondChangeを送る
+  wait: Option<u64>,
+  /// wait 中のOnSecondChangeで通知するアイドル秒数
+  #[serde(default)]
+  idle_seconds: i32,
+  /// イベント送信前に立てる/消すフラグ
+  #[serde(default)]
+  set_flags: Vec<EventFlag>,
+  #[serde(default)]
+  clear_flags: Vec<EventFlag>,
+  #[serde(default)]
+  expect: Expect,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Expect {
+  #[serde(default)]
+  flags: Vec<EventFlag>,
+  #[serde(default)]
+  not_flags: Vec<EventFlag>,
+  pending_event: Option<PendingEvent>,
+  #[serde(default)]
+  no_pending_event: bool,
+  talking_place: Option<TalkingPlace>,
+  /// ステップ中に返された全てのValueのいずれかに含まれているべき文字列
+  #[serde(default)]
+  value_contains: Vec<String>,
+  #[serde(default)]
+  value_not_contains: Vec<String>,
+}
+
+fn make_request(id: &str, references: &[String]) -> Request {
+  let mut headers = Headers::new();
+  headers.insert_by_header_name(HeaderName::from("ID"), id.to_string());
+  for (i, r) in references.iter().enumerate() {
+    headers.insert_by_header_name(HeaderName::from(&format!("Reference{}", i)), r.clone());
+  }
+  Request {
+    method: Method::GET,
+    version: Version::V30,
+    headers,
+  }
+}
+
+fn send(state: &mut GhostState, id: &str, references: &[String], values: &mut Vec<String>) -> Result<(), String> {
+  let res = handle_request(state, &make_request(id, references)).map_err(|e| e.to_string())?;
+  if let Some(v) = res.headers.get_by_header_name(&HeaderName::from("Value")) {
+    values.push(v.clone());
+  }
+  Ok(())
+}
+
+fn run_step(state: &mut GhostState, step: &Step, clock: &FakeClock) -> Result<(), String> {
+  for flag in &step.set_flags {
+    state.persistent.flags.done(flag.clone(), clock.now());
+  }
+  for flag in &step.clear_flags {
+    state.persistent.flags.delete(flag.clone());
+  }
+
+  let mut values = Vec::new();
+  if let Some(wait) = step.wait {
+    let refs: Vec<String> = ["0", "0", "0", "0"]
+      .iter()
+      .map(|s| s.to_string())
+      .chain(std::iter::once(step.idle_seconds.to_string()))
+      .collect();
+    for _ in 0..wait {
+      let before = clock.now();
+      clock.advance(Duration::from_secs(1));
+      if clock.now().minute != before.minute {
+        send(state, "OnMinuteChange", &[], &mut values)?;
+      }
+      send(state, "OnSecondChange", &refs, &mut values)?;
+    }
+  }
+
+  if let Some(event) = &step.event {
+    if let Some(target) = step.until_talk_count {
+      let mut i = 0;
+      while state.persistent.cumulative_talk_count < target {
+        if i >= MAX_ITERATIONS {
+          return Err(format!("talk count did not reach {}", target));
+        }
+        send(state, event, &step.references, &mut values)?;
+        i += 1;
+      }
+    } else {
+      for _ in 0..step.repeat.unwrap_or(1) {
+        send(state, event, &step.references, &mut values)?;
+      }
+    }
+  }
+
+  check(state, &step.expect, &values)
+}
+
+fn check(state: &GhostState, expect: &Expect, values: &[String]) -> Result<(), String> {
+  let flags = &state.persistent.flags;
+  for flag in &expect.flags {
+    if !flags.check(flag) {
+      return Err(format!("flag {:?} is not set", flag));
+    }
+  }
+  for flag in &expect.not_flags {
+    if flags.check(flag) {
+      return Err(format!("flag {:?} is set", flag));
+    }
+  }
+
+  let pending = &state.persistent.pending_event_talk;
+  if let Some(expected) = &expect.pending_event {
+    if pending.as_ref() != Some(expected) {
+      return Err(format!(
+        "pending event is {:?}, expected {:?}",
+        pending, expected
+      ));
+    }
+  }
+  if expect.no_pending_event && pending.is_some() {
+    return Err(format!("pending event is {:?}, expected none", pending));
+  }
+
+  if let Some(expected) = &expect.talking_place {
+    let place = &state.volatile.talking_place;
+    if place != expected {
+      return Err(format!(
+        "talking place is {:?}, expected {:?}",
+        place, expected
+      ));
+    }
+  }
+
+  for s in &expect.value_contains {
+    if !values.iter().any(|v| v.contains(s.as_str())) {
+      return Err(format!("no Value contains {:?}", s));
+    }
+  }
+  for s in &expect.value_not_contains {
+    if values.iter().any(|v| v.contains(s.as_str())) {
+      return Err(format!("a Value contains {:?}", s));
+    }
+  }
+  Ok(())
+}
+
+/// シナリオファイルを1つ実行する。失敗したステップの番号と理由を返す
+pub(crate) fn run_scenario_file(path: &Path) -> Result<(), String> {
+  let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+  let scenario: Scenario = toml::from_str(&text).map_err(|e| e.to_string())?;
+
+  // シナリオごとに初回起動前の状態から始める
+  let clock = FakeClock::new(
+    scenario
+      .start
+      .unwrap_or(LocalTime::new(2030, 4, 1, 12, 0, 0)),
+  );
+  let mut state = GhostState::new(Arc::new(clock.clone()));
+  if let Some(name) = &scenario.user_name {
+    state.persistent.user_name = name.clone();
+  }
+
+  scenario
+    .steps
+    .iter()
+    .enumerate()
+    .try_for_each(|(i, step)| run_step(&mut state, step, &clock).map_err(|e| format!("{} (step {}): {}", scenario.name, i + 1, e)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// scenarios/ 以下の全シナリオを実行する
+  #[test]
+  fn test_scenarios() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+    let mut paths: Vec<_> = std::fs::read_dir(&dir)
+      .expect("scenarios directory")
+      .filter_map(|e| e.ok().map(|e| e.path()))
+      .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+      .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let failures: Vec<String> = paths
+      .iter()
+      .filter_map(|p| {
+        run_scenario_file(p)
+          .err()
+          .map(|e| format!("{}: {}", p.display(), e))
+      })
+      .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+  }
+}
@@ -0,0 +1,281 @@
+use crate::events;
+use crate::events::talk::content::reload_talk_content;
+use crate::system::charset;
+use crate::system::clock::{Clock, SystemClock};
+use crate::system::error::{EventError, ShioriError};
+use crate::system::profile::ProfileIndex;
+use crate::system::response::{add_error_description, new_response_nocontent, new_response_with_value_with_translate, TranslateOption};
+use crate::system::trace::{TraceRecorder, TRACE_FILE_NAME};
+use crate::system::variables::GhostState;
+
+use encoding_rs::{Encoding, UTF_8};
+use std::fs::{metadata, File};
+use std::panic;
+use std::path::Path;
+use std::sync::Arc;
+
+use shiorust::message::{parts::*, traits::*, *};
+use simplelog::*;
+
+/// プラットフォームに依存しないSHIORI本体。
+/// DLLのエクスポート関数（Windowsのみ）はこれを薄く包んでいるだけなので、
+/// 他のRustプログラムからも同じようにゴーストを動かせる。
+///
+/// ゴーストの状態はインスタンスごとに持つが、ロガーはプロセスに1つしか設定できない。
+/// セーブデータはSSPと同様にカレントディレクトリを基準に読み書きする。
+pub struct Shiori {
+  state: GhostState,
+  trace: Option<TraceRecorder>,
+  error_fallback: bool,
+}
+
+impl Shiori {
+  /// ゴーストのディレクトリ（ghost/master）を指定して読み込む
+  pub fn load(dir: &str) -> Result<Self, ShioriError> {
+    Self::load_with_clock(dir, Arc::new(SystemClock))
+  }
+
+  /// 時刻の取得元を指定して読み込む。トレースの再生など、時刻を固定したい場合に使う
+  pub fn load_with_clock(dir: &str, clock: Arc<dyn Clock>) -> Result<Self, ShioriError> {
+    // パニックフックを最初に設定（ロガー未初期化でもeprintlnで出力可能にする）
+    panic::set_hook(Box::new(|panic_info| {
+      eprintln!("PANIC: {}", panic_info);
+      debug!("{}", panic_info);
+    }));
+
+    // ログの設定
+    // Windows(UTF-16)を想定しPathBufでパスを作成
+    let log_path = Path::new(dir).join("haine.log");
+    let log_path_str = match log_path.to_str() {
+      Some(s) => s.to_string(),
+      None => {
+        eprintln!("Log path contains non-Unicode characters");
+        return Err(ShioriError::LoadError);
+      }
+    };
+    let mut state = GhostState::new(clock);
+    state.volatile.log_path = log_path_str;
+    let fp = if let Ok(fp) = File::create(log_path) {
+      fp
+    } else {
+      error!("error while creating log file");
+      return Err(ShioriError::LoadError);
+    };
+    // ロガーはプロセスに1つしか設定できないため、再ロード時は最初のロガーを使い続ける
+    if let Err(e) = WriteLogger::init(LevelFilter::Debug, Config::default(), fp) {
+      eprintln!("{}", e);
+    }
+
+    // 追加したトークのIDがトーク履歴から消されないよう、セーブデータより先に読み込む
+    reload_talk_content(&mut state);
+
+    // 最後に使ったプロフィールで始める
+    state.volatile.profile = ProfileIndex::load(&state.volatile.save_dir).last;
+    if let Err(e) = state.load_variables() {
+      error!("{}", e);
+    }
+
+    // ./debugが存在するならデバッグモード
+    if metadata("./debug").is_ok() {
+      state.volatile.debug_mode = true;
+    }
+
+    // ./traceが存在するならリクエストとレスポンスを記録する
+    let trace = if metadata("./trace").is_ok() {
+      TraceRecorder::start(&Path::new(dir).join(TRACE_FILE_NAME))
+        .map_err(|e| error!("error while creating trace file: {}", e))
+        .ok()
+    } else {
+      None
+    };
+
+    debug!("load");
+    Ok(Self {
+      state,
+      trace,
+      error_fallback: !cfg!(debug_assertions),
+    })
+  }
+
+  /// SHIORIリクエスト文字列を処理し、レスポンス文字列を返す
+  pub fn request(&mut self, req: &str) -> String {
+    self.process(req, UTF_8)
+  }
+
+  /// バイト列のSHIORIリクエストを処理する。
+  /// Charset ヘッダの文字コードで読み、レスポンスも同じ文字コードで返す（UTF-16のリクエストにはUTF-8で返す）
+  pub fn request_bytes(&mut self, req: &[u8]) -> Vec<u8> {
+    let decoded = charset::request_encoding(req).and_then(|encoding| charset::decode(req, encoding).map(|s| (s, encoding)));
+    match decoded {
+      Ok((req, encoding)) => {
+        let encoding = encoding.output_encoding();
+        charset::encode(&self.process(&req, encoding), encoding)
+      }
+      Err(e) => {
+        let err = format!("error while decoding request: {}", e);
+        error!("{}", err);
+        let mut res = new_response_nocontent();
+        res.status = Status::BadRequest;
+        add_error_description(&mut res, err.as_str());
+        res.to_string().into_bytes()
+      }
+    }
+  }
+
+  fn process(&mut self, req: &str, encoding: &'static Encoding) -> String {
+    let mut res = Self::respond(&mut self.state, req, self.error_fallback);
+    res.headers.insert(
+      HeaderName::Standard(StandardHeaderName::Charset),
+      encoding.name().to_string(),
+    );
+    let res = res.to_string();
+    if let Some(trace) = self.trace.as_mut() {
+      trace.record(self.state.local_time(), req, &res);
+    }
+    res
+  }
+
+  /// トレースの記録を止める
+  pub fn stop_trace(&mut self) {
+    self.trace = None;
+  }
+
+  /// セーブデータやプロフィール一覧に書き込まないようにする。トレースの再生用
+  pub fn set_read_only(&mut self, read_only: bool) {
+    self.state.volatile.read_only = read_only;
+  }
+
+  /// エラー時に台詞で取り繕うかどうか。リリースビルドでは有効、デバッグビルドでは無効で始まる
+  pub fn set_error_fallback(&mut self, enabled: bool) {
+    self.error_fallback = enabled;
+  }
+
+  fn respond(state: &mut GhostState, req: &str, error_fallback: bool) -> Response {
+    let r = if let Ok(req) = Request::parse(req) {
+      req
+    } else {
+      let err = format!("error while parsing request: {}", req);
+      error!("{}", err);
+      let mut res = new_response_nocontent();
+      add_error_description(&mut res, err.as_str());
+      return res;
+    };
+
+    match events::handle_request(state, &r) {
+      Ok(res) => res,
+      Err(e) => {
+        // NOTIFYにはスクリプトを返さない
+        let is_get = matches!(r.method, Method::GET);
+        error_response(state, &e, error_fallback && is_get)
+      }
+    }
+  }
+
+  /// セーブデータを保存する。揮発性の変数はインスタンスとともに破棄される
+  pub fn unload(self) {
+    debug!("unload");
+
+    if self.state.volatile.load_status.should_save() {
+      if let Err(e) = self.state.save_variables() {
+        error!("{}", e);
+      }
+    } else {
+      warn!("セーブデータのロードに失敗したため、保存をスキップしました");
+    }
+  }
+}
+
+/// エラーのレスポンス。SSPの開発者ツール向けに ErrorDescription と ErrorLevel は常に付ける
+fn error_response(state: &mut GhostState, e: &EventError, fallback: bool) -> Response {
+  let err = format!("error while making response: {}", e);
+  error!("{}", err);
+  let mut res = match e.error.fallback_talk() {
+    Some(talk) if fallback => new_response_with_value_with_translate(state, talk.to_string(), TranslateOption::simple_translate()).unwrap_or_else(|_| new_response_nocontent()),
+    _ => new_response_nocontent(),
+  };
+  add_error_description(&mut res, err.as_str());
+  res
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_request_roundtrip_without_ffi() {
+    // load はセーブデータを読むため、ここではリクエスト処理だけを確かめる
+    let mut shiori = Shiori {
+      state: GhostState::default(),
+      trace: None,
+      error_fallback: false,
+    };
+
+    let res = shiori.request("GET SHIORI/3.0\r\nCharset: UTF-8\r\nSender: test\r\nID: version\r\n\r\n");
+    assert!(res.starts_with("SHIORI/3.0 200 OK"));
+    assert!(res.contains(env!("CARGO_PKG_VERSION")));
+
+    let res = shiori.request("this is not a shiori request");
+    assert!(res.starts_with("SHIORI/3.0 204 No Content"));
+    assert!(res.contains("ErrorDescription"));
+  }
+
+  #[test]
+  fn test_error_response() {
+    let mut state = GhostState::default();
+    let e = EventError::new("OnAiTalk", ShioriError::TalkNotFound("test".to_string()));
+
+    let res = error_response(&mut state, &e, false).to_string();
+    assert!(res.starts_with("SHIORI/3.0 204 No Content"));
+    assert!(res.contains("ErrorDescription: error while making response: E200 OnAiTalk: [TalkNotFound]"));
+    assert!(res.contains("ErrorLevel: error"));
+
+    let res = error_response(&mut state, &e, true).to_string();
+    assert!(res.starts_with("SHIORI/3.0 200 OK"));
+    assert!(res.contains("Value: "));
+    assert!(res.contains("ErrorLevel: error"));
+
+    // 台詞の用意されていないエラーは取り繕わない
+    let e = EventError::new(
+      "OnSecondChange",
+      ShioriError::MissingReference("OnSecondChange".to_string(), 4),
+    );
+    let res = error_response(&mut state, &e, true).to_string();
+    assert!(res.starts_with("SHIORI/3.0 204 No Content"));
+  }
+
+  #[test]
+  fn test_shift_jis_request() {
+    let mut shiori = Shiori {
+      state: GhostState::default(),
+      trace: None,
+      error_fallback: false,
+    };
+
+    let req = encoding_rs::SHIFT_JIS
+      .encode("NOTIFY SHIORI/3.0\r\nCharset: Shift_JIS\r\nID: OnNotifyUserInfo\r\nReference0: はいね\r\n\r\n")
+      .0;
+    shiori.request_bytes(&req);
+    assert_eq!(shiori.state.persistent.user_name, "はいね");
+
+    let req = b"GET SHIORI/3.0\r\nCharset: Shift_JIS\r\nID: craftmanw\r\n\r\n";
+    let res = shiori.request_bytes(req);
+    let (res, _, had_errors) = encoding_rs::SHIFT_JIS.decode(&res);
+    assert!(!had_errors);
+    assert!(res.contains("Charset: Shift_JIS"));
+    assert!(res.contains("Value: 日野つみ"));
+
+    // UTF-16では書き出せないので、UTF-8で返してヘッダもそれに合わせる
+    let req: Vec<u8> = "GET SHIORI/3.0\r\nCharset: UTF-16\r\nID: craftmanw\r\n\r\n"
+      .encode_utf16()
+      .flat_map(u16::to_le_bytes)
+      .collect();
+    let res = String::from_utf8(shiori.request_bytes(&req)).unwrap();
+    assert!(res.contains("Charset: UTF-8"));
+    assert!(res.contains("Value: 日野つみ"));
+
+    let req = b"GET SHIORI/3.0\r\nCharset: EBCDIC-X\r\nID: version\r\n\r\n";
+    let res = String::from_utf8(shiori.request_bytes(req)).unwrap();
+    assert!(res.starts_with("SHIORI/3.0 400 Bad Request"));
+    assert!(res.contains("unsupported charset: EBCDIC-X"));
+  }
+}
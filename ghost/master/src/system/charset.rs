@@ -0,0 +1,104 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::borrow::Cow;
+
+/// リクエストの Charset ヘッダの値を取り出す。
+/// ヘッダ行はASCIIのみで書かれ、Shift_JISの2バイト目にCR/LFは現れないため、デコード前のバイト列から探せる
+fn charset_label(req: &[u8]) -> Option<&[u8]> {
+  req
+    .split(|&b| b == b'\n')
+    .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+    .take_while(|line| !line.is_empty())
+    .find_map(|line| line.strip_prefix(b"Charset: "))
+}
+
+/// UTF-16のリクエストはASCIIの文字が0バイトと交互に並ぶ。先頭の文字（"GET" や "NOTIFY"）の0バイトの位置でLE/BEを見分ける
+fn utf16_encoding(req: &[u8]) -> Option<&'static Encoding> {
+  match req {
+    [c, 0, ..] if c.is_ascii_alphabetic() => Some(UTF_16LE),
+    [0, c, ..] if c.is_ascii_alphabetic() => Some(UTF_16BE),
+    _ => None,
+  }
+}
+
+/// リクエストの文字コード。Charset ヘッダがなければUTF-8（UTF-16で書かれていればそのUTF-16）とみなす。
+/// UTF-16のラベルはバイト順を表さないことがある（"UTF-16" はLEになる）ため、バイト順は実際のバイト列から決める
+pub fn request_encoding(req: &[u8]) -> Result<&'static Encoding, String> {
+  let resolve = |label: Option<&[u8]>, default: &'static Encoding| match label {
+    Some(label) => Encoding::for_label(label.trim_ascii()).ok_or_else(|| format!("unsupported charset: {}", String::from_utf8_lossy(label))),
+    None => Ok(default),
+  };
+  match utf16_encoding(req) {
+    Some(encoding) => {
+      let (text, _) = encoding.decode_without_bom_handling(req);
+      let labeled = resolve(charset_label(text.as_bytes()), encoding)?;
+      if labeled == UTF_16LE || labeled == UTF_16BE {
+        Ok(encoding)
+      } else {
+        Ok(labeled)
+      }
+    }
+    None => resolve(charset_label(req), UTF_8),
+  }
+}
+
+pub fn decode(req: &[u8], encoding: &'static Encoding) -> Result<String, String> {
+  encoding
+    .decode_without_bom_handling_and_without_replacement(req)
+    .map(Cow::into_owned)
+    .ok_or_else(|| format!("request is not valid {}", encoding.name()))
+}
+
+/// 文字コードで表せない文字は数値文字参照になる。
+/// UTF-16では書き出せないため、呼び出し側で Encoding::output_encoding を渡すこと
+pub fn encode(res: &str, encoding: &'static Encoding) -> Vec<u8> {
+  let (bytes, _, had_errors) = encoding.encode(res);
+  if had_errors {
+    warn!(
+      "some characters are not representable in {}",
+      encoding.name()
+    );
+  }
+  bytes.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use encoding_rs::SHIFT_JIS;
+
+  #[test]
+  fn test_request_encoding() {
+    let req = b"GET SHIORI/3.0\r\nCharset: Shift_JIS\r\nID: name\r\n\r\n";
+    assert_eq!(request_encoding(req), Ok(SHIFT_JIS));
+    let req = b"GET SHIORI/3.0\r\nID: name\r\n\r\n";
+    assert_eq!(request_encoding(req), Ok(UTF_8));
+    let req = b"GET SHIORI/3.0\r\nCharset: EBCDIC-X\r\nID: name\r\n\r\n";
+    assert!(request_encoding(req).is_err());
+
+    let utf16 = |text: &str, le: bool| -> Vec<u8> {
+      text
+        .encode_utf16()
+        .flat_map(|u| if le { u.to_le_bytes() } else { u.to_be_bytes() })
+        .collect()
+    };
+    let req = "GET SHIORI/3.0\r\nCharset: UTF-16\r\nID: name\r\n\r\n";
+    assert_eq!(request_encoding(&utf16(req, true)), Ok(UTF_16LE));
+    // ラベルがどちらでも、バイト順は実際のバイト列に従う
+    assert_eq!(request_encoding(&utf16(req, false)), Ok(UTF_16BE));
+    let req = "GET SHIORI/3.0\r\nCharset: UTF-16LE\r\nID: name\r\n\r\n";
+    assert_eq!(request_encoding(&utf16(req, false)), Ok(UTF_16BE));
+    let req = "GET SHIORI/3.0\r\nCharset: UTF-16BE\r\nID: name\r\n\r\n";
+    assert_eq!(request_encoding(&utf16(req, true)), Ok(UTF_16LE));
+    let req = "GET SHIORI/3.0\r\nID: name\r\n\r\n";
+    assert_eq!(request_encoding(&utf16(req, false)), Ok(UTF_16BE));
+  }
+
+  #[test]
+  fn test_shift_jis_roundtrip() {
+    let text = "ID: OnNotifyUserInfo\r\nReference0: はいね\r\n";
+    let bytes = encode(text, SHIFT_JIS);
+    assert_ne!(bytes, text.as_bytes());
+    assert_eq!(decode(&bytes, SHIFT_JIS).as_deref(), Ok(text));
+    assert!(decode(&bytes, UTF_8).is_err());
+  }
+}
@@ -0,0 +1,189 @@
+use crate::system::variables::{get_read, get_write};
+use serde::{Deserialize, Serialize};
+use std::ops::Add;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// タイムゾーン適用済みのローカル時刻。フィールドの順に比較するため、大小は時刻の前後と一致する
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LocalTime {
+  pub year: u32,
+  pub month: u32,
+  pub day: u32,
+  pub hour: u32,
+  pub minute: u32,
+  pub second: u32,
+}
+
+impl LocalTime {
+  pub fn new(year: u32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Self {
+    Self {
+      year,
+      month,
+      day,
+      hour,
+      minute,
+      second,
+    }
+  }
+
+  /// 0001-01-01 00:00:00 からの経過秒数（暦計算用。タイムゾーンは考慮しない）
+  fn to_seconds(self) -> i64 {
+    // 3月始まりの暦に直して日数を数える（うるう日が年末に来るため計算が単純になる）
+    let (y, m) = if self.month <= 2 {
+      (self.year as i64 - 1, self.month as i64 + 9)
+    } else {
+      (self.year as i64, self.month as i64 - 3)
+    };
+    let days = 365 * y + y / 4 - y / 100 + y / 400 + (153 * m + 2) / 5 + self.day as i64 - 1;
+    days * 86400 + self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64
+  }
+
+  fn from_seconds(seconds: i64) -> Self {
+    let days = seconds.div_euclid(86400);
+    let secs = seconds.rem_euclid(86400);
+    // to_seconds の逆変換。400年周期(146097日)単位で年を求める
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let (year, month) = if mp < 10 {
+      (era * 400 + yoe, mp + 3)
+    } else {
+      (era * 400 + yoe + 1, mp - 9)
+    };
+    Self::new(
+      year as u32,
+      month as u32,
+      day as u32,
+      (secs / 3600) as u32,
+      (secs % 3600 / 60) as u32,
+      (secs % 60) as u32,
+    )
+  }
+
+  /// earlier の日付から何日経ったか。時刻は無視し、日付が変わった回数を数える
+  pub fn days_since(self, earlier: LocalTime) -> i64 {
+    self.to_seconds().div_euclid(86400) - earlier.to_seconds().div_euclid(86400)
+  }
+}
+
+/// 指定時間だけ進めた時刻を返す。日付・月・年の繰り上がりも処理する
+impl Add<Duration> for LocalTime {
+  type Output = Self;
+
+  fn add(self, duration: Duration) -> Self {
+    Self::from_seconds(self.to_seconds() + duration.as_secs() as i64)
+  }
+}
+
+/// 現在のローカル時刻の取得元
+pub trait Clock: Send + Sync {
+  fn now(&self) -> LocalTime;
+}
+
+/// OSから実際のローカル時刻を取得する Clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  #[cfg(windows)]
+  fn now(&self) -> LocalTime {
+    let st = crate::system::windows::get_local_time();
+    LocalTime::new(
+      st.wYear as u32,
+      st.wMonth as u32,
+      st.wDay as u32,
+      st.wHour as u32,
+      st.wMinute as u32,
+      st.wSecond as u32,
+    )
+  }
+
+  #[cfg(not(windows))]
+  fn now(&self) -> LocalTime {
+    unsafe {
+      let t = libc::time(std::ptr::null_mut());
+      let mut tm: libc::tm = std::mem::zeroed();
+      libc::localtime_r(&t, &mut tm);
+      LocalTime::new(
+        (tm.tm_year + 1900) as u32,
+        (tm.tm_mon + 1) as u32,
+        tm.tm_mday as u32,
+        tm.tm_hour as u32,
+        tm.tm_min as u32,
+        tm.tm_sec as u32,
+      )
+    }
+  }
+}
+
+/// 任意の時刻を返す Clock。clone したハンドル同士で時刻を共有する
+#[derive(Clone)]
+pub struct FakeClock(Arc<RwLock<LocalTime>>);
+
+impl FakeClock {
+  pub fn new(time: LocalTime) -> Self {
+    Self(Arc::new(RwLock::new(time)))
+  }
+
+  pub fn set(&self, time: LocalTime) {
+    *get_write(&self.0) = time;
+  }
+
+  pub fn advance(&self, duration: Duration) {
+    let next = get_read(&self.0).add(duration);
+    self.set(next);
+  }
+}
+
+impl Clock for FakeClock {
+  fn now(&self) -> LocalTime {
+    *get_read(&self.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_local_time_add_carries_over() {
+    // 大晦日の23:59:59から1秒で年が変わる
+    let t = LocalTime::new(2024, 12, 31, 23, 59, 59);
+    assert_eq!(
+      t.add(Duration::from_secs(1)),
+      LocalTime::new(2025, 1, 1, 0, 0, 0)
+    );
+
+    // うるう年の2月末
+    let t = LocalTime::new(2024, 2, 28, 12, 0, 0);
+    assert_eq!(
+      t.add(Duration::from_secs(86400)),
+      LocalTime::new(2024, 2, 29, 12, 0, 0)
+    );
+    let t = LocalTime::new(2023, 2, 28, 12, 0, 0);
+    assert_eq!(
+      t.add(Duration::from_secs(86400)),
+      LocalTime::new(2023, 3, 1, 12, 0, 0)
+    );
+  }
+
+  #[test]
+  fn test_days_since_counts_date_changes() {
+    let first = LocalTime::new(2024, 2, 28, 23, 0, 0);
+    assert_eq!(LocalTime::new(2024, 2, 28, 23, 59, 0).days_since(first), 0);
+    assert_eq!(LocalTime::new(2024, 2, 29, 0, 1, 0).days_since(first), 1);
+    assert_eq!(LocalTime::new(2025, 2, 28, 0, 0, 0).days_since(first), 366);
+    assert_eq!(first.days_since(LocalTime::new(2024, 3, 1, 0, 0, 0)), -2);
+  }
+
+  #[test]
+  fn test_fake_clock_shares_time_between_handles() {
+    let clock = FakeClock::new(LocalTime::new(2024, 10, 30, 23, 30, 0));
+    let handle = clock.clone();
+    handle.advance(Duration::from_secs(60 * 30));
+    assert_eq!(clock.now(), LocalTime::new(2024, 10, 31, 0, 0, 0));
+  }
+}
@@ -3,34 +3,65 @@ use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ShioriError {
-  ParseIntError,
   SystemTimeError,
-  FieldAccessError,
   ArrayAccessError,
-  TalkNotFound,
+  /// 見つからなかったトークの説明（IDやトーク種別など）
+  TalkNotFound(String),
   ParseRequestError,
   NotSetScopeError(String),
-  BadRequest,
-  FileWriteError,
-  InvalidEvent,
-  BadRequestError,
+  /// 不正だった値の説明
+  BadRequest(String),
+  /// 書き込めなかったファイルのパス
+  FileWriteError(String),
+  /// 想定外だったイベントの指定
+  InvalidEvent(String),
+  LoadError,
+  /// イベントID, Referenceの番号
+  MissingReference(String, usize),
+  /// イベントID, Referenceの番号, 値
+  InvalidReference(String, usize, String),
+}
+
+impl ShioriError {
+  /// エラーコード。ログや問い合わせで参照されるため、一度割り当てたものは変更しない。
+  /// E1xx: リクエスト, E2xx: トーク・スクリプト, E3xx: システム
+  pub fn code(&self) -> &'static str {
+    match self {
+      ShioriError::BadRequest(_) => "E100",
+      ShioriError::MissingReference(..) => "E101",
+      ShioriError::InvalidReference(..) => "E102",
+      ShioriError::InvalidEvent(_) => "E103",
+      ShioriError::ParseRequestError => "E104",
+      ShioriError::TalkNotFound(_) => "E200",
+      ShioriError::NotSetScopeError(_) => "E201",
+      ShioriError::ArrayAccessError => "E202",
+      ShioriError::SystemTimeError => "E300",
+      ShioriError::FileWriteError(_) => "E301",
+      ShioriError::LoadError => "E302",
+    }
+  }
+
+  /// エラーの代わりに喋らせる台詞。
+  /// 話しかけられて何も返さないよりは自然に見えるものだけ用意する
+  pub fn fallback_talk(&self) -> Option<&'static str> {
+    match self {
+      ShioriError::TalkNotFound(_) | ShioriError::ArrayAccessError => Some("h1111210……何を話そうとしていたのだったかしら。h1111204いえ、なんでもないわ。"),
+      ShioriError::NotSetScopeError(_) => Some("h1111206……ごめんなさい、少し考えごとをしていたわ。"),
+      _ => None,
+    }
+  }
 }
 
 impl fmt::Display for ShioriError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      ShioriError::ParseIntError => write!(f, "[ParseIntError]文字列のパースに失敗しました"),
       ShioriError::SystemTimeError => {
         write!(f, "[SystemTimeError]システム時刻の取得に失敗しました")
       }
-      ShioriError::FieldAccessError => write!(
-        f,
-        "[FieldAccessError]変数フィールドへのアクセスに失敗しました"
-      ),
       ShioriError::ArrayAccessError => {
         write!(f, "[ArrayAccessError]配列の範囲外アクセスが発生しました")
       }
-      ShioriError::TalkNotFound => write!(f, "[TalkNotFound]指定されたトークが見つかりません"),
+      ShioriError::TalkNotFound(v) => write!(f, "[TalkNotFound]指定されたトークが見つかりません: {}", v),
       ShioriError::ParseRequestError => write!(
         f,
         "[ParseRequestError]SHIORIリクエストのパースに失敗しました"
@@ -40,12 +71,46 @@ impl fmt::Display for ShioriError {
         "[NotSetScopeError]次のスクリプトの頭にスコープ指定がありません: {}",
         v
       ),
-      ShioriError::BadRequest => write!(f, "[BadRequest]リクエストが不正です"),
-      ShioriError::FileWriteError => write!(f, "[FileWriteError]ファイルの書き込みに失敗しました"),
-      ShioriError::InvalidEvent => write!(f, "[InvalidEvent]無効なイベントが指定されました"),
-      ShioriError::BadRequestError => write!(f, "[BadRequestError]不正なリクエストが発生しました"),
+      ShioriError::BadRequest(v) => write!(f, "[BadRequest]リクエストが不正です: {}", v),
+      ShioriError::FileWriteError(v) => write!(f, "[FileWriteError]ファイルの書き込みに失敗しました: {}", v),
+      ShioriError::InvalidEvent(v) => write!(f, "[InvalidEvent]無効なイベントが指定されました: {}", v),
+      ShioriError::LoadError => write!(f, "[LoadError]ゴーストの読み込みに失敗しました"),
+      ShioriError::MissingReference(event, index) => write!(
+        f,
+        "[MissingReference]{}のReference{}がありません",
+        event, index
+      ),
+      ShioriError::InvalidReference(event, index, value) => write!(
+        f,
+        "[InvalidReference]{}のReference{}が不正です: {}",
+        event, index, value
+      ),
     }
   }
 }
 
 impl Error for ShioriError {}
+
+/// イベントの処理中に発生したエラー。どのイベントで起きたかを保持する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventError {
+  pub event_id: String,
+  pub error: ShioriError,
+}
+
+impl EventError {
+  pub fn new(event_id: &str, error: ShioriError) -> Self {
+    Self {
+      event_id: event_id.to_string(),
+      error,
+    }
+  }
+}
+
+impl fmt::Display for EventError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} {}: {}", self.error.code(), self.event_id, self.error)
+  }
+}
+
+impl Error for EventError {}
@@ -0,0 +1,251 @@
+//! セーブデータの調査・編集。ユーザの状態を再現するときに使う
+use crate::events::talk::content::{TalkContent, TALK_CONTENT_DIR};
+use crate::events::talk::randomtalk::all_talk_ids_per_type;
+use crate::events::talk::TalkType;
+use crate::system::clock::LocalTime;
+use crate::system::savefile::{verify_checksum, Integrity};
+use crate::system::variables::{EventFlag, RawVariables};
+use serde_json::Value;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// 読み込めなかったフィールドと、その理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldFailure {
+  pub field: String,
+  pub reason: String,
+}
+
+/// TalkType ごとの既読状況
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TalkCoverage {
+  pub talk_type: String,
+  pub seen: Vec<String>,
+  pub unseen: Vec<String>,
+  /// セーブデータにはあるが、現在のトークには存在しないID
+  pub unknown: Vec<String>,
+}
+
+/// 立っているフラグと、その記録
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagInfo {
+  /// JSON表記のフラグ
+  pub flag: String,
+  /// 最初に立てた日時。記録を始める前から立っていたものはなし
+  pub first_set: Option<LocalTime>,
+  pub count: u32,
+}
+
+/// 調査・編集中のセーブデータ
+pub struct SaveFile {
+  path: PathBuf,
+  raw: Value,
+  variables: RawVariables,
+  integrity: Integrity,
+  pub failures: Vec<FieldFailure>,
+}
+
+impl SaveFile {
+  pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+    let raw: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut payload = raw.clone();
+    let integrity = verify_checksum(&mut payload);
+    let (variables, failures) = RawVariables::from_value_with_reasons(payload)?;
+    Ok(Self {
+      path: path.to_path_buf(),
+      raw,
+      variables,
+      integrity,
+      failures: failures
+        .into_iter()
+        .map(|(field, reason)| FieldFailure { field, reason })
+        .collect(),
+    })
+  }
+
+  pub fn field_names() -> &'static [&'static str] {
+    RawVariables::FIELD_NAMES
+  }
+
+  /// チェックサムの検証結果の説明
+  pub fn integrity(&self) -> &'static str {
+    match self.integrity {
+      Integrity::Clean => "checksum ok",
+      Integrity::NoChecksum => "no checksum (saved by an older version)",
+      Integrity::HandEdited => "checksum mismatch (edited by hand)",
+    }
+  }
+
+  /// ファイルの内容をそのまま整形したもの
+  pub fn pretty(&self) -> String {
+    serde_json::to_string_pretty(&self.raw).unwrap_or_else(|e| e.to_string())
+  }
+
+  /// 現在のトークと照らし合わせた TalkType ごとの既読状況。
+  /// 季節や時間帯などの出現条件は見ないため、いつ実行しても同じ結果になる。
+  /// カレントディレクトリに talks/ があれば、そのトークも含める
+  pub fn talk_coverage(&self) -> Vec<TalkCoverage> {
+    let (content, _) = TalkContent::load_dir(Path::new(TALK_CONTENT_DIR));
+    let mut valid_ids = all_talk_ids_per_type(&content);
+    TalkType::all()
+      .into_iter()
+      .map(|talk_type| {
+        let valid = valid_ids.remove(&talk_type).unwrap_or_default();
+        let collected = self
+          .variables
+          .talk_collection
+          .get(&talk_type)
+          .cloned()
+          .unwrap_or_default();
+        let sorted = |ids: Vec<&String>| {
+          let mut ids: Vec<String> = ids.into_iter().cloned().collect();
+          ids.sort();
+          ids
+        };
+        TalkCoverage {
+          talk_type: format!("{:?}", talk_type),
+          seen: sorted(valid.intersection(&collected).collect()),
+          unseen: sorted(valid.difference(&collected).collect()),
+          unknown: sorted(collected.difference(&valid).collect()),
+        }
+      })
+      .collect()
+  }
+
+  /// 立っているフラグ。最初に立てた日時の順
+  pub fn flags(&self) -> Vec<FlagInfo> {
+    self
+      .variables
+      .flags
+      .records()
+      .into_iter()
+      .map(|(flag, record)| FlagInfo {
+        flag: serde_json::to_string(flag).unwrap_or_else(|e| e.to_string()),
+        first_set: record.first_set,
+        count: record.count,
+      })
+      .collect()
+  }
+
+  /// 初回起動から now までの日数。初回起動の日時が記録されていなければなし
+  pub fn days_since_first_boot(&self, now: LocalTime) -> Option<i64> {
+    self.variables.flags.days_since_first_boot(now)
+  }
+
+  /// フラグを切り替え、切り替え後に立っているかを返す。
+  /// spec はJSON表記（`{"TalkTypeUnlock":"Lore"}` など）か、引数のないフラグならその名前
+  pub fn toggle_flag(&mut self, spec: &str, now: LocalTime) -> Result<bool, String> {
+    let value = if spec.starts_with(['{', '"']) {
+      serde_json::from_str(spec).map_err(|e| e.to_string())?
+    } else {
+      Value::String(spec.to_string())
+    };
+    let flag: EventFlag = serde_json::from_value(value).map_err(|e| format!("unknown flag {}: {}", spec, e))?;
+    let flags = &mut self.variables.flags;
+    if flags.check(&flag) {
+      flags.delete(flag);
+      Ok(false)
+    } else {
+      flags.done(flag, now);
+      Ok(true)
+    }
+  }
+
+  /// フィールドを初期値に戻す。読み込めなかったフィールドも、戻せば保存できるようになる
+  pub fn reset_field(&mut self, name: &str) -> Result<(), String> {
+    if !self.variables.reset_field(name) {
+      return Err(format!("unknown field: {}", name));
+    }
+    self
+      .failures
+      .retain(|f| f.field != name && !f.field.starts_with(&format!("{}: ", name)));
+    Ok(())
+  }
+
+  /// 編集結果を保存する。読み込めなかったフィールドが残っていると、その内容が失われるため保存しない
+  pub fn save(&self, now: LocalTime) -> Result<(), Box<dyn Error>> {
+    if !self.failures.is_empty() {
+      let fields: Vec<&str> = self.failures.iter().map(|f| f.field.as_str()).collect();
+      return Err(
+        format!(
+          "読み込めなかったフィールドがあるため保存できません。reset で初期値に戻してください: {}",
+          fields.join(", ")
+        )
+        .into(),
+      );
+    }
+    self.variables.save_to(&self.path.to_string_lossy(), now)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  fn write(dir: &TempDir, json: &str) -> PathBuf {
+    let path = dir.path().join("vars.json");
+    std::fs::write(&path, json).unwrap();
+    path
+  }
+
+  #[test]
+  fn test_failures_have_reasons_and_block_saving() {
+    let dir = TempDir::new().unwrap();
+    let path = write(
+      &dir,
+      r#"{"total_boot_count": 1, "total_time": "abc", "flags": ["FirstBoot", "NoSuchFlag"]}"#,
+    );
+    let now = LocalTime::new(2026, 10, 18, 12, 0, 0);
+    let mut file = SaveFile::open(&path).unwrap();
+    assert_eq!(file.failures.len(), 2, "{:?}", file.failures);
+    assert_eq!(file.failures[0].field, "total_time");
+    assert!(!file.failures[0].reason.is_empty());
+    assert!(file.save(now).is_err());
+
+    file.reset_field("total_time").unwrap();
+    file.reset_field("flags").unwrap();
+    assert!(file.reset_field("no_such_field").is_err());
+    file.save(now).unwrap();
+    let saved = SaveFile::open(&path).unwrap();
+    assert!(saved.failures.is_empty());
+    assert_eq!(saved.integrity, Integrity::Clean);
+  }
+
+  #[test]
+  fn test_talk_coverage_ignores_conditions() {
+    let dir = TempDir::new().unwrap();
+    // 「館の静寂」は夜だけ、「白くない息」は冬だけのトーク
+    let path = write(
+      &dir,
+      r#"{"talk_collection": {"AboutMe": ["館の静寂", "削除されたトーク"]}}"#,
+    );
+    let coverage = SaveFile::open(&path).unwrap().talk_coverage();
+    let of = |talk_type: &str| coverage.iter().find(|c| c.talk_type == talk_type).unwrap();
+    assert!(of("AboutMe").seen.contains(&"館の静寂".to_string()));
+    assert_eq!(of("AboutMe").unknown, vec!["削除されたトーク"]);
+    assert!(of("WithYou").unseen.contains(&"白くない息".to_string()));
+  }
+
+  #[test]
+  fn test_toggle_flag() {
+    let dir = TempDir::new().unwrap();
+    let path = write(&dir, r#"{"flags": ["FirstBoot"]}"#);
+    let now = LocalTime::new(2026, 10, 18, 12, 0, 0);
+    let mut file = SaveFile::open(&path).unwrap();
+    assert_eq!(file.toggle_flag("FirstBoot", now), Ok(false));
+    assert_eq!(
+      file.toggle_flag(r#"{"TalkTypeUnlock":"Lore"}"#, now),
+      Ok(true)
+    );
+    assert!(file.toggle_flag("NoSuchFlag", now).is_err());
+    assert_eq!(
+      file.flags(),
+      vec![FlagInfo {
+        flag: r#"{"TalkTypeUnlock":"Lore"}"#.to_string(),
+        first_set: Some(now),
+        count: 1,
+      }]
+    );
+  }
+}
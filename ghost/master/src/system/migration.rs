@@ -0,0 +1,205 @@
+//! セーブデータ（vars.json）の形式のバージョン管理
+//!
+//! 形式を変えるときは SCHEMA_VERSION を上げ、旧形式から変換する関数を MIGRATIONS の末尾に追加する。
+//! MIGRATIONS[i] はバージョン i のJSONをバージョン i + 1 に変換する。
+use serde_json::{Map, Value};
+
+/// 現在のセーブデータ形式のバージョン
+pub(crate) const SCHEMA_VERSION: u32 = 3;
+
+pub(crate) const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+  unwrap_event_flags,
+  rename_self_introduce,
+  record_flag_counts,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum MigrationResult {
+  /// 現在の形式だった
+  UpToDate,
+  /// 旧形式から変換した
+  Migrated { from: u32 },
+  /// このゴーストより新しい形式。変換せずにそのまま読む
+  FutureVersion(u32),
+}
+
+/// 旧形式のJSONを現在の形式に変換する。
+/// schema_version のないファイルはバージョン0とみなす
+pub(crate) fn migrate(obj: &mut Map<String, Value>) -> Result<MigrationResult, String> {
+  let version = match obj.get(SCHEMA_VERSION_KEY) {
+    None => 0,
+    Some(v) => v
+      .as_u64()
+      .and_then(|v| u32::try_from(v).ok())
+      .ok_or_else(|| format!("invalid {}: {}", SCHEMA_VERSION_KEY, v))?,
+  };
+  if version > SCHEMA_VERSION {
+    return Ok(MigrationResult::FutureVersion(version));
+  }
+  if version == SCHEMA_VERSION {
+    return Ok(MigrationResult::UpToDate);
+  }
+
+  for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+    migration(obj).map_err(|e| format!("migration from v{} failed: {}", i, e))?;
+    debug!("migrated vars.json from v{} to v{}", i, i + 1);
+  }
+  obj.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(SCHEMA_VERSION));
+  Ok(MigrationResult::Migrated { from: version })
+}
+
+/// v0 -> v1: flags を {"flags": [...]} から [...] に。
+/// 中の "flags" がなければ、フラグを1つも立てていなかったものとして空の配列にする
+fn unwrap_event_flags(obj: &mut Map<String, Value>) -> Result<(), String> {
+  if let Some(Value::Object(flags)) = obj.get_mut("flags") {
+    let inner = flags.remove("flags").unwrap_or_else(|| {
+      warn!("flags.flags is missing (old format), treated as empty");
+      Value::Array(Vec::new())
+    });
+    obj.insert("flags".to_string(), inner);
+  }
+  Ok(())
+}
+
+/// v1 -> v2: トーク種別 SelfIntroduce を AboutMe に。トーク履歴とフラグ TalkTypeUnlock の両方を書き換える
+fn rename_self_introduce(obj: &mut Map<String, Value>) -> Result<(), String> {
+  if let Some(Value::Array(flags)) = obj.get_mut("flags") {
+    let old = talk_type_unlock("SelfIntroduce");
+    let new = talk_type_unlock("AboutMe");
+    if flags.contains(&new) {
+      flags.retain(|f| *f != old);
+    } else if let Some(flag) = flags.iter_mut().find(|f| **f == old) {
+      *flag = new;
+    }
+  }
+  if let Some(Value::Object(collection)) = obj.get_mut("talk_collection") {
+    if let Some(ids) = collection.remove("SelfIntroduce") {
+      match collection.get_mut("AboutMe") {
+        Some(Value::Array(about_me)) => {
+          if let Value::Array(ids) = ids {
+            about_me.extend(ids);
+          }
+        }
+        _ => {
+          collection.insert("AboutMe".to_string(), ids);
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+fn talk_type_unlock(talk_type: &str) -> Value {
+  let mut flag = Map::new();
+  flag.insert("TalkTypeUnlock".to_string(), Value::from(talk_type));
+  Value::Object(flag)
+}
+
+/// v2 -> v3: flags を EventFlag の配列から、立てた日時と回数の記録の配列に。
+/// 立てた日時は記録していなかったため不明(なし)、回数は1回とする
+fn record_flag_counts(obj: &mut Map<String, Value>) -> Result<(), String> {
+  if let Some(Value::Array(flags)) = obj.get_mut("flags") {
+    for flag in flags.iter_mut() {
+      let mut record = Map::new();
+      record.insert("flag".to_string(), flag.take());
+      record.insert("count".to_string(), Value::from(1));
+      *flag = Value::Object(record);
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn as_map(v: Value) -> Map<String, Value> {
+    v.as_object().unwrap().clone()
+  }
+
+  #[test]
+  fn test_migrate_from_v0() {
+    let mut obj = as_map(json!({
+      "total_boot_count": 1,
+      "flags": {"flags": ["FirstBoot"]},
+      "talk_collection": {"SelfIntroduce": ["a"], "AboutMe": ["b"]}
+    }));
+    assert_eq!(migrate(&mut obj), Ok(MigrationResult::Migrated { from: 0 }));
+    assert_eq!(obj["flags"], json!([{"flag": "FirstBoot", "count": 1}]));
+    assert_eq!(obj["talk_collection"], json!({"AboutMe": ["b", "a"]}));
+    assert_eq!(obj[SCHEMA_VERSION_KEY], json!(SCHEMA_VERSION));
+
+    // 中の flags がなくても、ほかの項目は読める
+    let mut obj = as_map(json!({"total_boot_count": 1, "flags": {}}));
+    assert_eq!(migrate(&mut obj), Ok(MigrationResult::Migrated { from: 0 }));
+    assert_eq!(obj["flags"], json!([]));
+    assert_eq!(obj["total_boot_count"], json!(1));
+  }
+
+  #[test]
+  fn test_migrate_talk_type_unlock_from_v1() {
+    let mut obj = as_map(json!({
+      "schema_version": 1,
+      "flags": ["FirstBoot", {"TalkTypeUnlock": "SelfIntroduce"}, {"TalkTypeUnlock": "Lore"}]
+    }));
+    assert_eq!(migrate(&mut obj), Ok(MigrationResult::Migrated { from: 1 }));
+    assert_eq!(
+      obj["flags"],
+      json!([
+        {"flag": "FirstBoot", "count": 1},
+        {"flag": {"TalkTypeUnlock": "AboutMe"}, "count": 1},
+        {"flag": {"TalkTypeUnlock": "Lore"}, "count": 1}
+      ])
+    );
+
+    // 両方あれば1つにまとめる
+    let mut obj = as_map(json!({
+      "schema_version": 1,
+      "flags": [{"TalkTypeUnlock": "AboutMe"}, {"TalkTypeUnlock": "SelfIntroduce"}]
+    }));
+    migrate(&mut obj).unwrap();
+    assert_eq!(
+      obj["flags"],
+      json!([{"flag": {"TalkTypeUnlock": "AboutMe"}, "count": 1}])
+    );
+  }
+
+  #[test]
+  fn test_migrate_flags_from_v2() {
+    let mut obj = as_map(json!({
+      "schema_version": 2,
+      "flags": ["FirstBoot", {"TalkTypeUnlock": "Lore"}, {"SeasonEvent": [2024, 10, 31]}]
+    }));
+    assert_eq!(migrate(&mut obj), Ok(MigrationResult::Migrated { from: 2 }));
+    assert_eq!(
+      obj["flags"],
+      json!([
+        {"flag": "FirstBoot", "count": 1},
+        {"flag": {"TalkTypeUnlock": "Lore"}, "count": 1},
+        {"flag": {"SeasonEvent": [2024, 10, 31]}, "count": 1}
+      ])
+    );
+  }
+
+  #[test]
+  fn test_migrate_current_and_future() {
+    let mut obj = as_map(json!({"schema_version": SCHEMA_VERSION, "flags": []}));
+    assert_eq!(migrate(&mut obj), Ok(MigrationResult::UpToDate));
+
+    let original = json!({"schema_version": SCHEMA_VERSION + 1, "flags": {"new": "format"}});
+    let mut obj = as_map(original.clone());
+    assert_eq!(
+      migrate(&mut obj),
+      Ok(MigrationResult::FutureVersion(SCHEMA_VERSION + 1))
+    );
+    assert_eq!(Value::Object(obj), original);
+
+    let mut obj = as_map(json!({"schema_version": "two"}));
+    assert!(migrate(&mut obj).is_err());
+  }
+}
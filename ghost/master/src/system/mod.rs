@@ -1,7 +1,15 @@
+pub(crate) mod charset;
+pub mod clock;
 pub mod error;
+pub mod inspect;
+pub(crate) mod migration;
+pub(crate) mod profile;
 pub(crate) mod response;
 pub(crate) mod roulette;
+pub(crate) mod savefile;
 pub(crate) mod status;
+pub mod trace;
+pub mod transfer;
 pub(crate) mod variables;
 
 #[cfg(windows)]
@@ -0,0 +1,152 @@
+//! 利用者ごとのセーブデータ（プロフィール）
+//!
+//! 1台のPCを複数人で使う場合に、進み具合を人ごとに分ける。
+//! 名前のない既定のプロフィールは従来どおり vars.json を使い、名前付きのプロフィールは profiles/ 以下に保存する。
+//! 作ったプロフィールと最後に使ったプロフィールは profiles.json に記録する。
+use crate::system::savefile::write_atomic;
+use crate::system::variables::VAR_PATH;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+
+pub(crate) const PROFILE_INDEX_PATH: &str = "profiles.json";
+const PROFILE_DIR: &str = "profiles";
+
+/// 既定のプロフィールの表示名
+pub(crate) const DEFAULT_PROFILE_TITLE: &str = "既定";
+
+/// プロフィールの名前に使えない文字。名前はメニューのさくらスクリプトにそのまま入るため、
+/// タグ(\)や引数の区切り(, [ ])、環境変数(%)になる文字を除く
+const INVALID_NAME_CHARS: [char; 5] = ['\\', ',', '[', ']', '%'];
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProfileIndex {
+  /// 最後に使ったプロフィール。なしなら既定のプロフィール
+  pub last: Option<String>,
+  /// 作ったプロフィールの名前。既定のプロフィールは含まない
+  pub names: Vec<String>,
+}
+
+impl ProfileIndex {
+  /// save_dir（空ならカレントディレクトリ）の profiles.json を読み込む
+  pub fn load(save_dir: &Path) -> Self {
+    Self::load_from(&save_dir.join(PROFILE_INDEX_PATH))
+  }
+
+  /// 読み込む。ファイルがなければ空、読めなければ既定のプロフィールだけを使う
+  pub fn load_from(path: &Path) -> Self {
+    if !path.exists() {
+      return Self::default();
+    }
+    match std::fs::read_to_string(path)
+      .map_err(|e| e.to_string())
+      .and_then(|s| serde_json::from_str(&s).map_err(|e| e.to_string()))
+    {
+      Ok(index) => index,
+      Err(e) => {
+        warn!("{} の読み込みに失敗: {}", path.display(), e);
+        Self::default()
+      }
+    }
+  }
+
+  pub fn save(&self, save_dir: &Path) -> Result<(), Box<dyn Error>> {
+    self.save_to(&save_dir.join(PROFILE_INDEX_PATH))
+  }
+
+  pub fn save_to(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+    write_atomic(path, serde_json::to_string_pretty(self)?.as_bytes())?;
+    Ok(())
+  }
+
+  pub fn contains(&self, name: &str) -> bool {
+    self.names.iter().any(|n| n == name)
+  }
+
+  /// profile を最後に使ったプロフィールとして記録する。新しい名前ならプロフィールを作る
+  pub fn select(&mut self, profile: Option<&str>) {
+    if let Some(name) = profile {
+      if !self.contains(name) {
+        self.names.push(name.to_string());
+      }
+    }
+    self.last = profile.map(|s| s.to_string());
+  }
+}
+
+/// プロフィールのセーブデータのパス。名前はファイル名に使えない文字を含みうるため、md5にする
+pub(crate) fn var_path(profile: Option<&str>) -> String {
+  match profile {
+    None => VAR_PATH.to_string(),
+    Some(name) => format!("{}/{:x}.json", PROFILE_DIR, md5::compute(name)),
+  }
+}
+
+/// プロフィールの名前として使えるか。使えなければ、その理由（さくらスクリプトとして表示できる文）
+pub(crate) fn validate_name(name: &str) -> Result<(), String> {
+  if name.is_empty() {
+    Err("名前が空です。".to_string())
+  } else if name == DEFAULT_PROFILE_TITLE {
+    Err(format!(
+      "「{}」はプロフィールの名前に使えません。",
+      DEFAULT_PROFILE_TITLE
+    ))
+  } else if name.contains(INVALID_NAME_CHARS) {
+    Err("\\\\ , [ ] % はプロフィールの名前に使えません。".to_string())
+  } else {
+    Ok(())
+  }
+}
+
+/// メニューなどに表示する名前
+pub(crate) fn title(profile: Option<&str>) -> &str {
+  profile.unwrap_or(DEFAULT_PROFILE_TITLE)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  #[test]
+  fn test_profile_index_roundtrip() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(PROFILE_INDEX_PATH);
+    assert_eq!(ProfileIndex::load_from(&path), ProfileIndex::default());
+
+    let mut index = ProfileIndex::default();
+    index.select(Some("あかり"));
+    index.select(Some("ゆう"));
+    index.select(Some("あかり"));
+    assert_eq!(index.names, vec!["あかり", "ゆう"]);
+    assert_eq!(index.last.as_deref(), Some("あかり"));
+    index.save_to(&path).unwrap();
+    assert_eq!(ProfileIndex::load_from(&path), index);
+
+    index.select(None);
+    assert_eq!(index.last, None);
+    assert_eq!(index.names.len(), 2);
+
+    std::fs::write(&path, "broken").unwrap();
+    assert_eq!(ProfileIndex::load_from(&path), ProfileIndex::default());
+  }
+
+  #[test]
+  fn test_validate_name() {
+    assert!(validate_name("あかり").is_ok());
+    assert!(validate_name("").is_err());
+    assert!(validate_name(DEFAULT_PROFILE_TITLE).is_err());
+    for name in ["a,b", "a]b", "a[b", "\\e", "%username"] {
+      assert!(validate_name(name).is_err(), "{}", name);
+    }
+  }
+
+  #[test]
+  fn test_var_path() {
+    assert_eq!(var_path(None), VAR_PATH);
+    let path = var_path(Some("a/b:c"));
+    assert!(path.starts_with("profiles/"), "{}", path);
+    assert!(!path["profiles/".len()..].contains(['/', ':']), "{}", path);
+    assert_ne!(path, var_path(Some("a/b:d")));
+  }
+}
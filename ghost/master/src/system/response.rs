@@ -1,11 +1,11 @@
 use crate::events::aitalk::IMMERSIVE_ICON_COUNT;
 use crate::events::aitalk::IMMERSIVE_RATE_MAX;
-use crate::events::talk::TalkType;
 use crate::events::talk::TalkingPlace;
+use crate::events::talk::{Talk, TalkType};
 use crate::events::translate::on_translate;
 use crate::system::error::ShioriError;
 use crate::system::roulette::RouletteCell;
-use crate::system::variables::*;
+use crate::system::variables::{GhostState, BASE_SURFACE, TRANSPARENT_SURFACE};
 use core::fmt::{Display, Formatter};
 use std::collections::HashSet;
 
@@ -31,9 +31,9 @@ pub(crate) const STICK_SURFACE: &str = "\
   \\0\
   ";
 
-pub(crate) fn on_stick_surface(_req: &Request) -> Response {
+pub(crate) fn on_stick_surface(state: &mut GhostState, _req: &Request) -> Response {
   // \1のサーフェスを\0に重ねて固定する
-  new_response_with_value_with_notranslate(STICK_SURFACE.to_string(), TranslateOption::none())
+  new_response_with_value_with_notranslate(state, STICK_SURFACE.to_string(), TranslateOption::none())
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -109,9 +109,9 @@ pub(crate) fn new_response_nocontent() -> Response {
   r
 }
 
-pub(crate) fn new_response_with_value_with_notranslate(value: String, option: HashSet<TranslateOption>) -> Response {
+pub(crate) fn new_response_with_value_with_notranslate(state: &GhostState, value: String, option: HashSet<TranslateOption>) -> Response {
   let balloon_completion = if option.contains(&TranslateOption::CompleteBalloonSurface) {
-    format!("\\b[{}]", get_read(&TALKING_PLACE).balloon_surface())
+    format!("\\b[{}]", state.volatile.talking_place.balloon_surface())
   } else {
     String::new()
   };
@@ -127,15 +127,19 @@ pub(crate) fn new_response_with_value_with_notranslate(value: String, option: Ha
   r
 }
 
-pub(crate) fn new_response_with_value_with_translate(value: String, option: HashSet<TranslateOption>) -> Result<Response, ShioriError> {
+pub(crate) fn new_response_with_value_with_translate(state: &mut GhostState, value: String, option: HashSet<TranslateOption>) -> Result<Response, ShioriError> {
   let balloon_completion = if option.contains(&TranslateOption::CompleteBalloonSurface) {
-    format!("\\b[{}]", get_read(&TALKING_PLACE).balloon_surface())
+    format!("\\b[{}]", state.volatile.talking_place.balloon_surface())
   } else {
     String::new()
   };
 
   let v = if option.contains(&TranslateOption::DoTranslate) {
-    on_translate(value, option.contains(&TranslateOption::CompleteShadow))?
+    on_translate(
+      state,
+      value,
+      option.contains(&TranslateOption::CompleteShadow),
+    )?
   } else {
     value
   };
@@ -151,12 +155,20 @@ pub(crate) fn new_response_with_value_with_translate(value: String, option: Hash
   Ok(r)
 }
 
-pub(crate) fn choose_one(values: &[impl RouletteCell], update_weight: bool) -> Option<usize> {
+/// 台詞を1つ選ぶ。台詞そのものをキーにした偏りは、その起動の間だけ使う
+pub(crate) fn choose_one(state: &mut GhostState, values: &[impl RouletteCell], update_weight: bool) -> Option<usize> {
   if values.is_empty() {
     return None;
   }
-  let u = get_write(&TALK_BIAS).roulette(values, update_weight);
-  u
+  state.volatile.dialog_bias.roulette(values, update_weight)
+}
+
+/// トークを1つ選ぶ。トークIDをキーにした偏りは、セーブデータに保存して起動をまたいで使う
+pub(crate) fn choose_talk(state: &mut GhostState, talks: &[Talk], update_weight: bool) -> Option<usize> {
+  if talks.is_empty() {
+    return None;
+  }
+  state.persistent.talk_bias.roulette(talks, update_weight)
 }
 
 // return all combinations of values
@@ -180,30 +192,11 @@ fn all_combo_inner(values: &Vec<Vec<String>>, result: &mut Vec<Vec<String>>, cur
   }
 }
 
-pub(crate) fn get_references(req: &Request) -> Vec<&str> {
-  let mut references: Vec<&str> = Vec::new();
-  const MAX_REF: usize = 10; // とりあえず10個まで取得
-  for i in 0..MAX_REF {
-    if let Some(value) = req
-      .headers
-      .get(&HeaderName::from(&format!("Reference{}", i)))
-    {
-      references.push(value);
-    } else {
-      references.push("");
-    }
-  }
-  // 最後の空でない参照のインデックスを取得し、それ以降の要素を削除
-  let last_valid_index = references.iter().rposition(|&s| !s.is_empty()).unwrap_or(0);
-  references.truncate(last_valid_index + 1);
-  references
-}
-
-pub(crate) fn render_shadow(is_complete: bool) -> String {
+pub(crate) fn render_shadow(state: &GhostState, is_complete: bool) -> String {
   const DEFAULT_Y: i32 = -700;
   const MAX_Y: i32 = -200;
   if is_complete {
-    let degree = *get_read(&IMMERSIVE_DEGREES);
+    let degree = state.volatile.immersive_degrees;
     format!(
       "\\0\\![bind,ex,没入度用,1]\\![anim,offset,904000,0,{}]",
       ((MAX_Y - DEFAULT_Y) as f32 * (degree as f32 / (IMMERSIVE_RATE_MAX as f32))) as i32 + DEFAULT_Y,
@@ -400,27 +393,49 @@ pub(crate) fn skirt_status_name(code: i32) -> &'static str {
   }
 }
 
+/// 7桁サーフェスコードを桁ごとのパーツのコードに分けたもの。上の桁から
+/// スカート状態(1桁)・顔色(1桁)・眉(1桁)・腕(1桁)・口(1桁)・目(2桁)
+struct SurfaceCode {
+  skirt: i32,
+  face: i32,
+  eyebrow: i32,
+  arm: i32,
+  mouth: i32,
+  eyes: i32,
+}
+
+impl SurfaceCode {
+  fn decode(surface: i32) -> Self {
+    Self {
+      skirt: (surface / 1000000) % 10,
+      face: (surface / 100000) % 10,
+      eyebrow: (surface / 10000) % 10,
+      arm: (surface / 1000) % 10,
+      mouth: (surface / 100) % 10,
+      eyes: surface % 100,
+    }
+  }
+
+  /// スカート状態以外の全パーツが0のコード（h1000000）は非表示指定
+  fn is_hidden(&self) -> bool {
+    [self.face, self.eyebrow, self.arm, self.mouth, self.eyes]
+      .iter()
+      .all(|c| *c == 0)
+  }
+}
+
 /// 7桁サーフェスコードをデコードし、bind命令群を生成する
 pub(crate) fn generate_bind_script(from_surface: i32, dest_surface: i32, shadow_script: &str, ignore_upper_completion: bool) -> String {
-  const EYE_INDEX_DIGIT: u32 = 2;
-  let eye_index_digit_pow = 10_i32.pow(EYE_INDEX_DIGIT);
-
-  let dest_eyes = dest_surface % eye_index_digit_pow;
-  let from_eyes = from_surface % eye_index_digit_pow;
-  let dest_mouth = (dest_surface / 100) % 10;
-  let dest_arm = (dest_surface / 1000) % 10;
-  let dest_eyebrow = (dest_surface / 10000) % 10;
-  let dest_face = (dest_surface / 100000) % 10;
-  let dest_skirt = (dest_surface / 1000000) % 10;
+  let dest = SurfaceCode::decode(dest_surface);
+  let from_eyes = SurfaceCode::decode(from_surface).eyes;
 
   // 同一コードの場合は話者0への切り替えのみ
   if from_surface == dest_surface {
     return "\\0".to_string();
   }
 
-  // スカート状態以外の全パーツが0のコード（h1000000）は非表示指定。
-  // 素体サーフェスにbindを重ねる方式では表現できないため、空のサーフェスを直接指定する
-  if dest_surface % 1000000 == 0 {
+  // 非表示指定は、素体サーフェスにbindを重ねる方式では表現できないため、空のサーフェスを直接指定する
+  if dest.is_hidden() {
     return format!(
       "\\0\\![lock,repaint]\\s[{}]{}\\![unlock,repaint]",
       TRANSPARENT_SURFACE, shadow_script
@@ -430,15 +445,15 @@ pub(crate) fn generate_bind_script(from_surface: i32, dest_surface: i32, shadow_
   // 非目パーツのbind命令を構築
   let non_eye_binds = format!(
     "\\![bind,スカート状態,{},1]\\![bind,眉,{},1]\\![bind,顔色,{},1]\\![bind,腕,{},1]\\![bind,口,{},1]",
-    skirt_status_name(dest_skirt),
-    eyebrow_name(dest_eyebrow),
-    face_color_name(dest_face),
-    arm_name(dest_arm),
-    mouth_name(dest_mouth),
+    skirt_status_name(dest.skirt),
+    eyebrow_name(dest.eyebrow),
+    face_color_name(dest.face),
+    arm_name(dest.arm),
+    mouth_name(dest.mouth),
   );
 
   // 目の遷移スクリプトを生成
-  let eye_script = generate_eye_transition(from_eyes, dest_eyes, ignore_upper_completion);
+  let eye_script = generate_eye_transition(from_eyes, dest.eyes, ignore_upper_completion);
 
   // 全体を組み立て
   format!(
@@ -447,6 +462,28 @@ pub(crate) fn generate_bind_script(from_surface: i32, dest_surface: i32, shadow_
   )
 }
 
+/// 7桁サーフェスコードのうち、対応するパーツがない桁のパーツ名。
+/// generate_bind_script はそのような桁を既定のパーツとして描画してしまうため、トークの検証に使う
+pub(crate) fn invalid_surface_parts(surface: i32) -> Vec<&'static str> {
+  let code = SurfaceCode::decode(surface);
+  if code.is_hidden() {
+    return vec![];
+  }
+  let parts = [
+    ("目", code.eyes, 15),
+    ("口", code.mouth, 9),
+    ("腕", code.arm, 4),
+    ("眉", code.eyebrow, 4),
+    ("顔色", code.face, 5),
+    ("スカート状態", code.skirt, 2),
+  ];
+  parts
+    .iter()
+    .filter(|(_, code, max)| !(1..=*max).contains(code))
+    .map(|(name, _, _)| *name)
+    .collect()
+}
+
 /// 目の遷移アニメーション（まばたき補完）をbind方式で生成
 fn generate_eye_transition(from_eyes: i32, dest_eyes: i32, ignore_upper_completion: bool) -> String {
   let transitions = BlinkTransition::all();
@@ -557,17 +594,17 @@ pub(crate) fn shake_with_notext() -> String {
     .join("")
 }
 
-pub(crate) fn render_immersive_icon() -> String {
-  let immersive_degrees = *get_read(&IMMERSIVE_DEGREES);
+pub(crate) fn render_immersive_icon(state: &mut GhostState) -> String {
+  let immersive_degrees = state.volatile.immersive_degrees;
   let icon_count_float = immersive_degrees as f32 * IMMERSIVE_ICON_COUNT as f32 / IMMERSIVE_RATE_MAX as f32;
-  let current_icon_count = if *get_read(&TALKING_PLACE) == TalkingPlace::Library {
+  let current_icon_count = if state.volatile.talking_place == TalkingPlace::Library {
     // 繰り上げ
     icon_count_float.ceil() as u32
   } else {
     // 切り捨て
     icon_count_float.floor() as u32
   };
-  let mut candles = *get_write(&CANDLES);
+  let candles = &mut state.volatile.candles;
   let mut v = String::new();
   for i in 1..=IMMERSIVE_ICON_COUNT {
     let blowed = i <= current_icon_count;
@@ -604,6 +641,18 @@ mod tests {
     assert!(result.contains("\\![bind,目,こっち目,1]"));
   }
 
+  #[test]
+  fn test_invalid_surface_parts() {
+    assert!(invalid_surface_parts(1111201).is_empty());
+    assert!(invalid_surface_parts(2541215).is_empty());
+    assert!(invalid_surface_parts(1000000).is_empty());
+    assert_eq!(invalid_surface_parts(1111216), vec!["目"]);
+    assert_eq!(
+      invalid_surface_parts(3151000),
+      vec!["目", "口", "眉", "スカート状態"]
+    );
+  }
+
   #[test]
   fn test_generate_bind_script_same_surface() {
     // 同一コードの場合は話者0への切り替えのみ
@@ -1,6 +1,7 @@
 use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 pub(crate) trait RouletteCell {
   fn key(&self) -> &str; // トークの識別子: 全体において一意である必要がある
@@ -30,6 +31,9 @@ fn calc_bias(count: u32) -> u64 {
   }
 }
 
+/// キーごとの未選択回数。セーブデータに保存され、起動をまたいで引き継がれる
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(transparent)]
 pub(crate) struct TalkBias(HashMap<String, u32>);
 
 impl TalkBias {
@@ -49,6 +53,11 @@ impl TalkBias {
     self.0.insert(digest.to_string(), self.get(digest) + 1);
   }
 
+  /// keys に含まれないキーを取り除く
+  pub fn retain_keys(&mut self, keys: &HashSet<String>) {
+    self.0.retain(|k, _| keys.contains(k));
+  }
+
   pub fn roulette(&mut self, cells: &[impl RouletteCell], is_consume: bool) -> Option<usize> {
     if cells.is_empty() {
       return None;
@@ -93,7 +102,7 @@ mod test {
 
     let talks: Vec<Talk> = ["a", "b", "c", "d", "e", "f", "g", "h"]
       .iter()
-      .map(|s| Talk::new(None, s.to_string(), s.to_string(), None))
+      .map(|s| Talk::new(None, s.to_string(), s.to_string(), vec![]))
       .collect();
 
     let mut indexes: Vec<usize> = vec![];
@@ -130,4 +139,22 @@ mod test {
     println!("indexes: {:?}", indexes);
     println!("select_count: {:?}", select_count);
   }
+
+  #[test]
+  fn test_talk_bias_persists_and_prunes() {
+    let mut bias = TalkBias::new();
+    bias.reset("a");
+    bias.increment("b");
+    bias.increment("removed");
+
+    let json = serde_json::to_value(&bias).unwrap();
+    let mut restored: TalkBias = serde_json::from_value(json).unwrap();
+    assert_eq!(restored, bias);
+
+    let keys: HashSet<String> = ["a", "b"].iter().map(|s| s.to_string()).collect();
+    restored.retain_keys(&keys);
+    assert_eq!(restored.get("a"), 0);
+    assert_eq!(restored.get("b"), 2);
+    assert!(!restored.0.contains_key("removed"));
+  }
 }
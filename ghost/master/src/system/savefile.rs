@@ -0,0 +1,312 @@
+//! セーブデータのファイル操作
+//!
+//! 書き込みは一時ファイルに書いてから rename で置き換えるため、途中で落ちても元のファイルは壊れない。
+//! 保存のたびに直前のファイルを `<path>.<日時>.bak` として残し、新しいものから BACKUP_GENERATIONS 世代だけ保持する。
+//! 同じ秒に複数回保存したときは `<path>.<日時>-1.bak` のように連番をつける。
+//! 本体には中身のチェックサムを埋め込み、読み込み時に壊れたのか手で編集されたのかを区別できるようにする。
+use crate::system::clock::LocalTime;
+use serde_json::Value;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 保持するバックアップの世代数
+pub(crate) const BACKUP_GENERATIONS: usize = 5;
+
+const BACKUP_EXTENSION: &str = "bak";
+
+/// セーブデータに埋め込むチェックサムのキー
+pub(crate) const CHECKSUM_KEY: &str = "checksum";
+
+/// チェックサムの検証結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Integrity {
+  /// 保存したときのまま
+  Clean,
+  /// チェックサムのない古い形式
+  NoChecksum,
+  /// JSONとしては正しいが、チェックサムが合わない
+  HandEdited,
+}
+
+/// 読み込めなかったファイルの壊れ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Damage {
+  /// 途中で途切れている。書き込み中に落ちた場合など
+  Truncated,
+  /// 読めない、またはJSONとして正しくない
+  Corrupt,
+  /// 本体がない。保存の途中で消えた場合など
+  Missing,
+}
+
+impl Damage {
+  /// 通知に使う説明
+  pub fn description(&self) -> &'static str {
+    match self {
+      Damage::Truncated => "途中で途切れていました。保存中にゴーストが終了した可能性があります",
+      Damage::Corrupt => "破損していました",
+      Damage::Missing => "見つかりませんでした",
+    }
+  }
+
+  pub fn from_error(e: &(dyn Error + 'static)) -> Self {
+    match e.downcast_ref::<serde_json::Error>() {
+      Some(e) if e.is_eof() => Damage::Truncated,
+      _ => Damage::Corrupt,
+    }
+  }
+}
+
+/// JSONの値のmd5。整形の違いには影響されない
+pub(crate) fn checksum(value: &Value) -> String {
+  format!("{:x}", md5::compute(value.to_string()))
+}
+
+/// value の中身のチェックサムを、CHECKSUM_KEY として埋め込む
+pub(crate) fn embed_checksum(value: &mut Value) {
+  let sum = checksum(value);
+  if let Some(obj) = value.as_object_mut() {
+    obj.insert(CHECKSUM_KEY.to_string(), Value::String(sum));
+  }
+}
+
+/// 埋め込まれたチェックサムを取り除き、中身と一致するか調べる
+pub(crate) fn verify_checksum(value: &mut Value) -> Integrity {
+  let Some(expected) = value
+    .as_object_mut()
+    .and_then(|obj| obj.remove(CHECKSUM_KEY))
+  else {
+    return Integrity::NoChecksum;
+  };
+  if expected.as_str() == Some(checksum(value).as_str()) {
+    Integrity::Clean
+  } else {
+    Integrity::HandEdited
+  }
+}
+
+/// 一時ファイルに書き込んで fsync したあと、path に rename する
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+  let tmp_path = with_suffix(path, "tmp");
+  {
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+  }
+  fs::rename(&tmp_path, path)?;
+  // rename 自体を永続化する。Windowsではディレクトリを開けないため省略
+  #[cfg(not(windows))]
+  if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+    File::open(dir)?.sync_all()?;
+  }
+  Ok(())
+}
+
+/// 既存の path を日時付きのバックアップとして残し、古い世代を削除する
+pub(crate) fn rotate_backups(path: &Path, now: LocalTime, generations: usize) -> io::Result<()> {
+  if !path.exists() {
+    return Ok(());
+  }
+  let timestamp = format!(
+    "{:04}{:02}{:02}-{:02}{:02}{:02}",
+    now.year, now.month, now.day, now.hour, now.minute, now.second
+  );
+  // 同じ秒のバックアップが残っていれば、その最大の連番の次にする
+  let seq = backup_stamps(path)
+    .iter()
+    .filter(|(t, _)| *t == timestamp)
+    .map(|(_, seq)| seq + 1)
+    .max()
+    .unwrap_or(0);
+  let backup_path = with_suffix(path, &backup_suffix(&timestamp, seq));
+  fs::copy(path, &backup_path)?;
+
+  for old in list_backups(path).into_iter().skip(generations) {
+    if let Err(e) = fs::remove_file(&old) {
+      warn!(
+        "古いバックアップ {} を削除できませんでした: {}",
+        old.display(),
+        e
+      );
+    }
+  }
+  Ok(())
+}
+
+/// path のバックアップを新しい順に返す。
+/// 日時のない旧形式のバックアップ（`<path>.bak`）は最も古いものとして扱う
+pub(crate) fn list_backups(path: &Path) -> Vec<PathBuf> {
+  let mut backups: Vec<PathBuf> = backup_stamps(path)
+    .iter()
+    .map(|(t, seq)| with_suffix(path, &backup_suffix(t, *seq)))
+    .collect();
+  let legacy = with_suffix(path, BACKUP_EXTENSION);
+  if legacy.exists() {
+    backups.push(legacy);
+  }
+  backups
+}
+
+/// 日時付きのバックアップの (日時, 連番) を新しい順に返す
+fn backup_stamps(path: &Path) -> Vec<(String, u32)> {
+  let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+    return vec![];
+  };
+  let dir = match path.parent() {
+    Some(d) if !d.as_os_str().is_empty() => d,
+    _ => Path::new("."),
+  };
+  let prefix = format!("{}.", file_name);
+  let suffix = format!(".{}", BACKUP_EXTENSION);
+
+  let mut stamps: Vec<(String, u32)> = fs::read_dir(dir)
+    .into_iter()
+    .flatten()
+    .flatten()
+    .filter_map(|entry| entry.file_name().into_string().ok())
+    .filter_map(|name| parse_stamp(name.strip_prefix(&prefix)?.strip_suffix(&suffix)?))
+    .collect();
+  // 日時は桁数固定なので文字列の順が時刻の順になる。同じ秒のものは連番の大きいほうが新しい
+  stamps.sort_unstable_by(|a, b| b.cmp(a));
+  stamps
+}
+
+/// バックアップ名の `<日時>[-<連番>].bak` の部分
+fn backup_suffix(timestamp: &str, seq: u32) -> String {
+  if seq == 0 {
+    format!("{}.{}", timestamp, BACKUP_EXTENSION)
+  } else {
+    format!("{}-{}.{}", timestamp, seq, BACKUP_EXTENSION)
+  }
+}
+
+/// `<日時>[-<連番>]` を (日時, 連番) に分ける。連番がなければ0
+fn parse_stamp(s: &str) -> Option<(String, u32)> {
+  let timestamp = s.get(..15).filter(|t| is_timestamp(t))?;
+  let seq = match &s[15..] {
+    "" => 0,
+    rest => {
+      let digits = rest.strip_prefix('-')?;
+      if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+      }
+      digits.parse().ok()?
+    }
+  };
+  Some((timestamp.to_string(), seq))
+}
+
+fn is_timestamp(s: &str) -> bool {
+  let bytes = s.as_bytes();
+  bytes.len() == 15
+    && bytes[8] == b'-'
+    && bytes
+      .iter()
+      .enumerate()
+      .all(|(i, b)| i == 8 || b.is_ascii_digit())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut s = path.as_os_str().to_owned();
+  s.push(".");
+  s.push(suffix);
+  PathBuf::from(s)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::TempDir;
+
+  fn at(second: u32) -> LocalTime {
+    LocalTime::new(2026, 10, 18, 12, 0, second)
+  }
+
+  #[test]
+  fn test_write_atomic_replaces_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("vars.json");
+    write_atomic(&path, b"old").unwrap();
+    write_atomic(&path, b"new").unwrap();
+    assert_eq!(fs::read(&path).unwrap(), b"new");
+    assert!(!with_suffix(&path, "tmp").exists());
+  }
+
+  #[test]
+  fn test_rotate_keeps_newest_generations() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("vars.json");
+    fs::write(with_suffix(&path, "bak"), "legacy").unwrap();
+
+    // ファイルがなければバックアップは作らない
+    rotate_backups(&path, at(0), 3).unwrap();
+    assert_eq!(list_backups(&path).len(), 1);
+
+    for second in 1..=4 {
+      fs::write(&path, second.to_string()).unwrap();
+      rotate_backups(&path, at(second), 3).unwrap();
+    }
+    let backups = list_backups(&path);
+    let contents: Vec<String> = backups
+      .iter()
+      .map(|p| fs::read_to_string(p).unwrap())
+      .collect();
+    assert_eq!(contents, vec!["4", "3", "2"]);
+    assert!(backups[0].ends_with("vars.json.20261018-120004.bak"));
+  }
+
+  #[test]
+  fn test_rotate_within_the_same_second() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("vars.json");
+    for i in 1..=12 {
+      fs::write(&path, i.to_string()).unwrap();
+      rotate_backups(&path, at(0), 3).unwrap();
+    }
+    let backups = list_backups(&path);
+    let contents: Vec<String> = backups
+      .iter()
+      .map(|p| fs::read_to_string(p).unwrap())
+      .collect();
+    // 連番が2桁になっても新しい順に並ぶ
+    assert_eq!(contents, vec!["12", "11", "10"]);
+    assert!(backups[0].ends_with("vars.json.20261018-120000-11.bak"));
+  }
+
+  #[test]
+  fn test_verify_checksum() {
+    let mut value = serde_json::json!({"total_boot_count": 3, "flags": ["FirstBoot"]});
+    assert_eq!(verify_checksum(&mut value.clone()), Integrity::NoChecksum);
+
+    embed_checksum(&mut value);
+    // 整形し直しても変わらない
+    let mut reformatted: Value = serde_json::from_str(&serde_json::to_string_pretty(&value).unwrap()).unwrap();
+    assert_eq!(verify_checksum(&mut reformatted), Integrity::Clean);
+    assert!(reformatted.get(CHECKSUM_KEY).is_none());
+
+    value["total_boot_count"] = Value::from(999);
+    assert_eq!(verify_checksum(&mut value), Integrity::HandEdited);
+  }
+
+  #[test]
+  fn test_damage_from_error() {
+    let truncated = serde_json::from_str::<Value>(r#"{"total_boot_count": 3, "fla"#).unwrap_err();
+    assert_eq!(Damage::from_error(&truncated), Damage::Truncated);
+    let corrupt = serde_json::from_str::<Value>("\0\0garbage").unwrap_err();
+    assert_eq!(Damage::from_error(&corrupt), Damage::Corrupt);
+  }
+
+  #[test]
+  fn test_list_backups_ignores_unrelated_files() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("vars.json");
+    fs::write(dir.path().join("vars.json.tmp"), "").unwrap();
+    fs::write(dir.path().join("vars.json.note.bak"), "").unwrap();
+    fs::write(dir.path().join("other.json.20261018-120000.bak"), "").unwrap();
+    fs::write(dir.path().join("vars.json.20261018-120000-.bak"), "").unwrap();
+    fs::write(dir.path().join("vars.json.20261018-120000-x.bak"), "").unwrap();
+    assert!(list_backups(&path).is_empty());
+  }
+}
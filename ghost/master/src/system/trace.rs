@@ -0,0 +1,96 @@
+use crate::system::clock::LocalTime;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+/// トレースファイル名。haine.log と同じディレクトリに置く
+pub const TRACE_FILE_NAME: &str = "haine_trace.jsonl";
+
+/// トレースの1行分。リクエストを処理した時刻も残し、再生時の時刻を合わせられるようにする
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+  pub time: LocalTime,
+  pub request: String,
+  pub response: String,
+}
+
+/// リクエストとレスポンスをトレースファイルに追記する
+pub(crate) struct TraceRecorder {
+  file: File,
+}
+
+impl TraceRecorder {
+  /// 記録を開始する。ログと同様に起動ごとに作り直す
+  pub fn start(path: &Path) -> Result<Self, Box<dyn Error>> {
+    Ok(Self {
+      file: File::create(path)?,
+    })
+  }
+
+  /// リクエストとレスポンスを処理した時刻とともに1行追記する
+  pub fn record(&mut self, time: LocalTime, request: &str, response: &str) {
+    let entry = TraceEntry {
+      time,
+      request: request.to_string(),
+      response: response.to_string(),
+    };
+    let result = serde_json::to_string(&entry)
+      .map_err(|e| e.to_string())
+      .and_then(|line| writeln!(self.file, "{}", line).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+      error!("error while writing trace: {}", e);
+    }
+  }
+}
+
+/// トレースファイルを読み込む
+pub fn read_trace(path: &Path) -> Result<Vec<TraceEntry>, Box<dyn Error>> {
+  let reader = BufReader::new(File::open(path)?);
+  let mut entries = Vec::new();
+  for (i, line) in reader.lines().enumerate() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    let entry = serde_json::from_str(&line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+    entries.push(entry);
+  }
+  Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_trace_roundtrip() -> Result<(), Box<dyn Error>> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join(TRACE_FILE_NAME);
+
+    let time = LocalTime::new(2030, 4, 1, 12, 0, 0);
+    let mut recorder = TraceRecorder::start(&path)?;
+    recorder.record(
+      time,
+      "GET SHIORI/3.0\r\nID: version\r\n\r\n",
+      "SHIORI/3.0 200 OK\r\n\r\n",
+    );
+    recorder.record(
+      time,
+      "GET SHIORI/3.0\r\nID: name\r\n\r\n",
+      "SHIORI/3.0 200 OK\r\nValue: haine\r\n\r\n",
+    );
+    drop(recorder);
+
+    let entries = read_trace(&path)?;
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].time, time);
+    assert_eq!(entries[1].request, "GET SHIORI/3.0\r\nID: name\r\n\r\n");
+    assert_eq!(
+      entries[1].response,
+      "SHIORI/3.0 200 OK\r\nValue: haine\r\n\r\n"
+    );
+    Ok(())
+  }
+}
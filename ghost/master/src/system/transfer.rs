@@ -0,0 +1,338 @@
+//! セーブデータの書き出しと読み込み。別のPCへ引っ越すときに使う
+//!
+//! 書き出したファイルはセーブデータ本体にゴーストのバージョン・書き出し日時・チェックサムを添えたもので、
+//! 単体で持ち運べる。読み込むときはチェックサムを検証し、変更点を確認してから反映する。
+use crate::system::clock::LocalTime;
+use crate::system::migration::SCHEMA_VERSION;
+use crate::system::profile::{self, ProfileIndex, DEFAULT_PROFILE_TITLE};
+use crate::system::savefile::{checksum, write_atomic};
+use crate::system::variables::RawVariables;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// メニューから書き出すときのファイル名。ゴーストのフォルダに置く
+pub const EXPORT_FILE_NAME: &str = "haine_savedata.json";
+
+const EXPORT_FORMAT: &str = "haine-savedata";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportMetadata {
+  pub format: String,
+  /// 書き出したゴーストのバージョン
+  pub ghost_version: String,
+  pub exported_at: LocalTime,
+  /// variables をそのまま文字列にしたもののmd5
+  pub checksum: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExportFile {
+  metadata: ExportMetadata,
+  variables: Value,
+}
+
+/// 読み込むときの反映方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+  /// すべて書き出したファイルの内容にする
+  Replace,
+  /// トーク履歴とフラグは両方の和をとり、それ以外は書き出したファイルの内容にする
+  Merge,
+}
+
+impl FromStr for ImportMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "replace" => Ok(Self::Replace),
+      "merge" => Ok(Self::Merge),
+      _ => Err(format!("unknown import mode: {}", s)),
+    }
+  }
+}
+
+impl ImportMode {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Replace => "replace",
+      Self::Merge => "merge",
+    }
+  }
+}
+
+/// 検証済みの書き出しファイル
+pub struct SaveExport {
+  pub metadata: ExportMetadata,
+  /// 読み込めずに初期値を使ったフィールド
+  pub warnings: Vec<String>,
+  variables: RawVariables,
+}
+
+impl SaveExport {
+  /// 現在のセーブデータに反映した結果
+  pub(crate) fn apply(&self, current: &RawVariables, mode: ImportMode) -> RawVariables {
+    let mut result = self.variables.clone();
+    result.schema_version = SCHEMA_VERSION;
+    if mode == ImportMode::Merge {
+      for (talk_type, ids) in &current.talk_collection {
+        result
+          .talk_collection
+          .entry(*talk_type)
+          .or_default()
+          .extend(ids.iter().cloned());
+      }
+      result.flags.extend(current.flags.clone());
+    }
+    result
+  }
+
+  /// 反映したときに変わる項目
+  pub(crate) fn diff(&self, current: &RawVariables, mode: ImportMode) -> Vec<String> {
+    current.diff(&self.apply(current, mode))
+  }
+}
+
+/// セーブデータを書き出す
+pub(crate) fn write_export(variables: &RawVariables, path: &Path, now: LocalTime) -> Result<ExportMetadata, Box<dyn Error>> {
+  let variables = serde_json::to_value(variables)?;
+  let metadata = ExportMetadata {
+    format: EXPORT_FORMAT.to_string(),
+    ghost_version: env!("CARGO_PKG_VERSION").to_string(),
+    exported_at: now,
+    checksum: checksum(&variables),
+  };
+  let file = ExportFile {
+    metadata: metadata.clone(),
+    variables,
+  };
+  write_atomic(path, serde_json::to_string_pretty(&file)?.as_bytes())?;
+  Ok(metadata)
+}
+
+/// 書き出したファイルを読み込み、形式とチェックサムを検証する
+pub fn read_export(path: &Path) -> Result<SaveExport, Box<dyn Error>> {
+  let file: ExportFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+  let metadata = file.metadata;
+  if metadata.format != EXPORT_FORMAT {
+    return Err(
+      format!(
+        "セーブデータの書き出しファイルではありません: {}",
+        metadata.format
+      )
+      .into(),
+    );
+  }
+  if checksum(&file.variables) != metadata.checksum {
+    return Err("チェックサムが一致しません。ファイルが壊れているか、書き換えられています".into());
+  }
+  let (variables, warnings) = RawVariables::from_value(file.variables)?;
+  if variables.schema_version > SCHEMA_VERSION {
+    return Err(
+      format!(
+        "新しいバージョンのゴースト({})で書き出されたため読み込めません",
+        metadata.ghost_version
+      )
+      .into(),
+    );
+  }
+  Ok(SaveExport {
+    metadata,
+    warnings,
+    variables,
+  })
+}
+
+/// ゴーストのフォルダ dir で使うプロフィールを決める。
+/// name がなければ、ゴーストが次に起動したときと同じく最後に使ったプロフィール。「既定」なら既定のプロフィール
+pub fn resolve_profile(dir: &Path, name: Option<&str>) -> Result<Option<String>, Box<dyn Error>> {
+  let index = ProfileIndex::load(dir);
+  match name {
+    None => Ok(index.last),
+    Some(DEFAULT_PROFILE_TITLE) => Ok(None),
+    Some(name) if index.contains(name) => Ok(Some(name.to_string())),
+    Some(name) => Err(format!("プロフィール「{}」はありません", name).into()),
+  }
+}
+
+/// ゴーストのフォルダ dir の、プロフィール profile のセーブデータを書き出す
+pub fn export_from_dir(dir: &Path, profile: Option<&str>, out: &Path, now: LocalTime) -> Result<ExportMetadata, Box<dyn Error>> {
+  let variables = load_from_dir(dir, profile)?;
+  write_export(&variables, out, now)
+}
+
+/// 書き出したファイルをゴーストのフォルダ dir の、プロフィール profile のセーブデータに反映したときの変更点
+pub fn preview_import_to_dir(dir: &Path, profile: Option<&str>, export: &SaveExport, mode: ImportMode) -> Result<Vec<String>, Box<dyn Error>> {
+  Ok(export.diff(&load_from_dir(dir, profile)?, mode))
+}
+
+/// 書き出したファイルをゴーストのフォルダ dir の、プロフィール profile のセーブデータに反映する。
+/// 反映前のセーブデータはバックアップとして残る
+pub fn import_to_dir(dir: &Path, profile: Option<&str>, export: &SaveExport, mode: ImportMode, now: LocalTime) -> Result<(), Box<dyn Error>> {
+  let current = load_from_dir(dir, profile)?;
+  let path = dir.join(profile::var_path(profile));
+  export
+    .apply(&current, mode)
+    .save_to(&path.to_string_lossy(), now)
+}
+
+fn load_from_dir(dir: &Path, profile: Option<&str>) -> Result<RawVariables, Box<dyn Error>> {
+  let path = dir.join(profile::var_path(profile));
+  let (variables, failed_fields) = RawVariables::load_partial_from(&path.to_string_lossy())?;
+  if !failed_fields.is_empty() {
+    return Err(
+      format!(
+        "{} の一部が読み込めません: {}",
+        path.display(),
+        failed_fields.join(", ")
+      )
+      .into(),
+    );
+  }
+  Ok(variables)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::talk::TalkType;
+  use crate::system::variables::{EventFlag, VAR_PATH};
+  use std::collections::HashSet;
+  use tempfile::TempDir;
+
+  fn now() -> LocalTime {
+    LocalTime::new(2026, 10, 18, 12, 0, 0)
+  }
+
+  fn variables(boot_count: u64, talk_ids: &[&str], flags: &[EventFlag]) -> RawVariables {
+    let mut v = RawVariables {
+      schema_version: SCHEMA_VERSION,
+      total_boot_count: boot_count,
+      ..Default::default()
+    };
+    v.talk_collection.insert(
+      TalkType::AboutMe,
+      talk_ids
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<HashSet<_>>(),
+    );
+    for f in flags {
+      v.flags.done(f.clone(), now());
+    }
+    v
+  }
+
+  #[test]
+  fn test_export_roundtrip_and_modes() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(EXPORT_FILE_NAME);
+    let exported = variables(20, &["a", "b"], &[EventFlag::FirstBoot]);
+    let metadata = write_export(&exported, &path, now()).unwrap();
+
+    let export = read_export(&path).unwrap();
+    assert_eq!(export.metadata, metadata);
+    assert!(export.warnings.is_empty());
+
+    let current = variables(5, &["c"], &[EventFlag::FirstClose]);
+    let replaced = export.apply(&current, ImportMode::Replace);
+    assert_eq!(replaced.total_boot_count, 20);
+    assert_eq!(replaced.talk_collection[&TalkType::AboutMe].len(), 2);
+    assert!(!replaced.flags.check(&EventFlag::FirstClose));
+
+    let merged = export.apply(&current, ImportMode::Merge);
+    assert_eq!(merged.total_boot_count, 20);
+    assert_eq!(merged.talk_collection[&TalkType::AboutMe].len(), 3);
+    assert!(merged.flags.check(&EventFlag::FirstBoot));
+    assert!(merged.flags.check(&EventFlag::FirstClose));
+
+    let diff = export.diff(&current, ImportMode::Merge);
+    assert!(
+      diff.contains(&"total_boot_count: 5 -> 20".to_string()),
+      "{:?}",
+      diff
+    );
+    assert!(
+      diff.contains(&"talk_collection: 1件 -> 3件".to_string()),
+      "{:?}",
+      diff
+    );
+  }
+
+  #[test]
+  fn test_read_export_rejects_tampered_file() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join(EXPORT_FILE_NAME);
+    write_export(&variables(20, &[], &[]), &path, now()).unwrap();
+
+    let tampered = std::fs::read_to_string(&path)
+      .unwrap()
+      .replace("\"total_boot_count\": 20", "\"total_boot_count\": 9999");
+    std::fs::write(&path, tampered).unwrap();
+    assert!(read_export(&path).is_err());
+  }
+
+  #[test]
+  fn test_import_to_dir_keeps_backup() {
+    let dir = TempDir::new().unwrap();
+    let vars_path = dir.path().join(VAR_PATH);
+    variables(5, &["c"], &[])
+      .save_to(&vars_path.to_string_lossy(), now())
+      .unwrap();
+    let export_path = dir.path().join(EXPORT_FILE_NAME);
+    write_export(&variables(20, &["a"], &[]), &export_path, now()).unwrap();
+
+    let export = read_export(&export_path).unwrap();
+    import_to_dir(dir.path(), None, &export, ImportMode::Merge, now()).unwrap();
+
+    let (imported, _) = RawVariables::load_partial_from(&vars_path.to_string_lossy()).unwrap();
+    assert_eq!(imported.total_boot_count, 20);
+    assert_eq!(imported.talk_collection[&TalkType::AboutMe].len(), 2);
+    assert_eq!(crate::system::savefile::list_backups(&vars_path).len(), 1);
+  }
+
+  #[test]
+  fn test_transfer_uses_profile() {
+    let dir = TempDir::new().unwrap();
+    let mut index = ProfileIndex::default();
+    index.select(Some("あかり"));
+    index.save(dir.path()).unwrap();
+    variables(5, &[], &[])
+      .save_to(&dir.path().join(VAR_PATH).to_string_lossy(), now())
+      .unwrap();
+    let profile_path = dir.path().join(profile::var_path(Some("あかり")));
+    variables(7, &[], &[])
+      .save_to(&profile_path.to_string_lossy(), now())
+      .unwrap();
+
+    // 指定がなければ、最後に使ったプロフィール
+    assert_eq!(
+      resolve_profile(dir.path(), None).unwrap().as_deref(),
+      Some("あかり")
+    );
+    assert_eq!(
+      resolve_profile(dir.path(), Some(DEFAULT_PROFILE_TITLE)).unwrap(),
+      None
+    );
+    assert!(resolve_profile(dir.path(), Some("ななし")).is_err());
+
+    let export_path = dir.path().join(EXPORT_FILE_NAME);
+    export_from_dir(dir.path(), Some("あかり"), &export_path, now()).unwrap();
+    let export = read_export(&export_path).unwrap();
+    let diff = preview_import_to_dir(dir.path(), None, &export, ImportMode::Replace).unwrap();
+    assert!(
+      diff.contains(&"total_boot_count: 5 -> 7".to_string()),
+      "{:?}",
+      diff
+    );
+
+    // 既定のプロフィールに反映しても、名前付きのプロフィールは変わらない
+    import_to_dir(dir.path(), None, &export, ImportMode::Replace, now()).unwrap();
+    let (imported, _) = RawVariables::load_partial_from(&dir.path().join(VAR_PATH).to_string_lossy()).unwrap();
+    assert_eq!(imported.total_boot_count, 7);
+    assert!(crate::system::savefile::list_backups(&profile_path).is_empty());
+  }
+}
@@ -1,14 +1,21 @@
 use crate::check_error;
 use crate::events::aitalk::IMMERSIVE_ICON_COUNT;
 use crate::events::mouse_core::Direction;
-use crate::events::talk::randomtalk::{derivative_talks, random_talks};
+use crate::events::talk::content::TalkContent;
+use crate::events::talk::effect::Effect;
+use crate::events::talk::randomtalk::all_talk_ids_per_type;
 use crate::events::talk::{TalkType, TalkingPlace};
+use crate::system::clock::{Clock, LocalTime, SystemClock};
 use crate::system::error::ShioriError;
+use crate::system::migration::{migrate, MigrationResult, SCHEMA_VERSION};
+use crate::system::profile::{self, ProfileIndex};
 use crate::system::roulette::TalkBias;
+use crate::system::savefile::{embed_checksum, list_backups, rotate_backups, verify_checksum, write_atomic, Damage, Integrity, BACKUP_GENERATIONS};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::sync::LazyLock;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -27,42 +34,32 @@ pub(crate) fn get_write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
 }
 
 pub(crate) const GHOST_NAME: &str = "Crave The Grave";
-const VAR_PATH: &str = "vars.json";
-const VAR_BACKUP_PATH: &str = "vars.json.bak";
-pub(crate) static TOTAL_BOOT_COUNT: LazyLock<RwLock<u64>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static TOTAL_TIME: LazyLock<RwLock<u64>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static RANDOM_TALK_INTERVAL: LazyLock<RwLock<u64>> = LazyLock::new(|| RwLock::new(180));
-pub(crate) static USER_NAME: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new("".to_string()));
-pub(crate) static TALK_COLLECTION: LazyLock<RwLock<HashMap<TalkType, HashSet<String>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
-pub(crate) static CUMULATIVE_TALK_COUNT: LazyLock<RwLock<u64>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static FLAGS: LazyLock<RwLock<EventFlags>> = LazyLock::new(|| RwLock::new(EventFlags::default()));
-pub(crate) static PENDING_EVENT_TALK: LazyLock<RwLock<Option<PendingEvent>>> = LazyLock::new(|| RwLock::new(None));
-pub(crate) static DERIVATIVE_TALK_REQUESTABLE: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(false));
-pub(crate) static LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX: LazyLock<RwLock<u32>> = LazyLock::new(|| RwLock::new(1000));
+pub(crate) const VAR_PATH: &str = "vars.json";
+
 #[derive(Clone, Debug, PartialEq, Eq, Default)]
 pub(crate) enum LoadStatus {
   #[default]
   NotLoaded, // まだロードしていない（初期状態）
-  FirstBoot,                   // 初回起動（セーブファイルなし）
-  Success,                     // 正常ロード（バックアップあり）
-  SuccessNoBackup,             // 正常ロード（バックアップなし）
-  RestoredFromBackup,          // メインが失敗、バックアップから復元
-  FailedNoBackup,              // ロード失敗、バックアップもなし
-  PartialSuccess(Vec<String>), // 部分成功、失敗フィールド名を保持
+  FirstBoot,                                       // 初回起動（セーブファイルなし）
+  Success,                                         // 正常ロード（バックアップあり）
+  SuccessNoBackup,                                 // 正常ロード（バックアップなし）
+  RestoredFromBackup(String, Damage, Vec<String>), // メインが失敗、バックアップから復元。復元に使ったファイル名とメインの壊れ方、バックアップで読めなかったフィールド名を保持
+  FailedNoBackup(Damage),                          // ロード失敗、読めるバックアップもなし
+  PartialSuccess(Vec<String>),                     // 部分成功、失敗フィールド名を保持
+  HandEdited(Vec<String>),                         // チェックサムが合わない（手で編集された）。失敗フィールド名を保持
+  FutureVersion(u32),                              // 新しいゴーストで保存された形式。読み取り専用で読み込んだ
 }
 
 impl LoadStatus {
   pub fn is_failed(&self) -> bool {
-    matches!(self, LoadStatus::FailedNoBackup)
+    matches!(self, LoadStatus::FailedNoBackup(_))
   }
 
   pub fn should_save(&self) -> bool {
-    !self.is_failed()
+    !self.is_failed() && !matches!(self, LoadStatus::FutureVersion(_))
   }
 }
 
-pub(crate) static LOAD_STATUS: LazyLock<RwLock<LoadStatus>> = LazyLock::new(|| RwLock::new(LoadStatus::NotLoaded));
-
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub(crate) enum PendingEvent {
   ConfessionOfSuicide,
@@ -122,49 +119,71 @@ impl PendingEvent {
   }
 }
 
-/// RawVariables 構造体と load_partial_from メソッドを生成するマクロ
+/// RawVariables 構造体と from_value メソッドを生成するマクロ
 ///
 /// フィールドを追加する場合はこのマクロ呼び出しを編集してください。
-/// 構造体定義と部分パース処理が自動的に同期されます。
+/// 構造体定義と部分パース処理、PersistentVariables との相互変換が自動的に同期されます。
+///
+/// - meta: セーブデータ自体の情報。PersistentVariables にはなく、保存時は指定した値になる
+/// - primitives: そのまま読み書きする
+/// - options: セーブデータになければ PersistentVariables の初期値を使う
+/// - custom: 独自のパーサーで、一部が壊れていても読めるものだけ読む
 macro_rules! define_raw_variables {
   (
+    meta: { $($meta_field:ident : $meta_type:ty = $meta_value:expr),* $(,)? },
     primitives: { $($prim_field:ident : $prim_type:ty),* $(,)? },
     options: { $($opt_field:ident : $opt_inner:ty),* $(,)? },
     custom: { $($custom_field:ident : $custom_type:ty => $parser:expr),* $(,)? }
   ) => {
-    #[derive(Serialize, Deserialize, Default)]
+    #[derive(Serialize, Deserialize, Default, Clone)]
     pub(crate) struct RawVariables {
-      $($prim_field: $prim_type,)*
-      $($opt_field: Option<$opt_inner>,)*
-      $($custom_field: $custom_type,)*
+      $(pub(crate) $meta_field: $meta_type,)*
+      $(pub(crate) $prim_field: $prim_type,)*
+      $(pub(crate) $opt_field: Option<$opt_inner>,)*
+      $(pub(crate) $custom_field: $custom_type,)*
     }
 
     impl RawVariables {
-      /// 部分的にロード。失敗したフィールドはデフォルト値を使用
+      /// JSONから部分的に読み込む。旧形式なら現在の形式に変換してから読む
       /// 戻り値: (RawVariables, 失敗したフィールド名のリスト)
-      pub fn load_partial_from(path: &str) -> Result<(Self, Vec<String>), Box<dyn std::error::Error>> {
-        if !std::path::Path::new(path).exists() {
-          return Ok((Self::default(), vec![]));
-        }
-
-        let json_str = std::fs::read_to_string(path)?;
-        let value: serde_json::Value = serde_json::from_str(&json_str)?;
+      pub fn from_value(value: serde_json::Value) -> Result<(Self, Vec<String>), Box<dyn std::error::Error>> {
+        let (result, failures) = Self::from_value_with_reasons(value)?;
+        Ok((result, failures.into_iter().map(|(field, _)| field).collect()))
+      }
 
+      /// from_value と同じだが、失敗したフィールドごとに理由も返す
+      /// 戻り値: (RawVariables, (失敗したフィールド名, 理由)のリスト)
+      pub fn from_value_with_reasons(mut value: serde_json::Value) -> Result<(Self, Vec<(String, String)>), Box<dyn std::error::Error>> {
         let obj = value
-          .as_object()
+          .as_object_mut()
           .ok_or("JSON root is not an object")?;
 
+        if let MigrationResult::Migrated { from } = migrate(obj)? {
+          info!("セーブデータをバージョン{}から{}に変換しました", from, SCHEMA_VERSION);
+        }
+
         let mut result = Self::default();
-        let mut failed_fields = Vec::new();
+        let mut failed_fields: Vec<(String, String)> = Vec::new();
 
         // プリミティブフィールドのパース
+        $(
+          if let Some(v) = obj.get(stringify!($meta_field)) {
+            match serde_json::from_value::<$meta_type>(v.clone()) {
+              Ok(val) => result.$meta_field = val,
+              Err(e) => {
+                warn!("{} のパースに失敗: {}", stringify!($meta_field), e);
+                failed_fields.push((stringify!($meta_field).to_string(), e.to_string()));
+              }
+            }
+          }
+        )*
         $(
           if let Some(v) = obj.get(stringify!($prim_field)) {
             match serde_json::from_value::<$prim_type>(v.clone()) {
               Ok(val) => result.$prim_field = val,
               Err(e) => {
                 warn!("{} のパースに失敗: {}", stringify!($prim_field), e);
-                failed_fields.push(stringify!($prim_field).to_string());
+                failed_fields.push((stringify!($prim_field).to_string(), e.to_string()));
               }
             }
           }
@@ -178,7 +197,7 @@ macro_rules! define_raw_variables {
                 Ok(val) => result.$opt_field = Some(val),
                 Err(e) => {
                   warn!("{} のパースに失敗: {}", stringify!($opt_field), e);
-                  failed_fields.push(stringify!($opt_field).to_string());
+                  failed_fields.push((stringify!($opt_field).to_string(), e.to_string()));
                 }
               }
             }
@@ -193,12 +212,12 @@ macro_rules! define_raw_variables {
                 result.$custom_field = val;
                 // スキップした項目があれば警告として記録
                 for w in warnings {
-                  failed_fields.push(format!("{}: {}", stringify!($custom_field), w));
+                  failed_fields.push((format!("{}: {}", stringify!($custom_field), w), w));
                 }
               }
               Err(e) => {
                 warn!("{} のパースに失敗: {}", stringify!($custom_field), e);
-                failed_fields.push(stringify!($custom_field).to_string());
+                failed_fields.push((stringify!($custom_field).to_string(), e));
               }
             }
           }
@@ -206,24 +225,131 @@ macro_rules! define_raw_variables {
 
         Ok((result, failed_fields))
       }
+
+      /// セーブデータのフィールド名
+      pub const FIELD_NAMES: &'static [&'static str] = &[
+        $(stringify!($meta_field),)*
+        $(stringify!($prim_field),)*
+        $(stringify!($opt_field),)*
+        $(stringify!($custom_field),)*
+      ];
+
+      /// 指定したフィールドを初期値に戻す。存在しないフィールド名なら false
+      pub fn reset_field(&mut self, name: &str) -> bool {
+        let default = Self::default();
+        match name {
+          $(stringify!($meta_field) => self.$meta_field = default.$meta_field,)*
+          $(stringify!($prim_field) => self.$prim_field = default.$prim_field,)*
+          $(stringify!($opt_field) => self.$opt_field = default.$opt_field,)*
+          $(stringify!($custom_field) => self.$custom_field = default.$custom_field,)*
+          _ => return false,
+        }
+        true
+      }
+
+      /// other との差分を、変更のあったフィールドごとに1行で返す
+      pub fn diff(&self, other: &Self) -> Vec<String> {
+        let mut lines = Vec::new();
+        $(
+          if self.$meta_field != other.$meta_field {
+            lines.push(describe_change(stringify!($meta_field), &self.$meta_field, &other.$meta_field));
+          }
+        )*
+        $(
+          if self.$prim_field != other.$prim_field {
+            lines.push(describe_change(stringify!($prim_field), &self.$prim_field, &other.$prim_field));
+          }
+        )*
+        $(
+          if self.$opt_field != other.$opt_field {
+            lines.push(describe_change(stringify!($opt_field), &self.$opt_field, &other.$opt_field));
+          }
+        )*
+        $(
+          if self.$custom_field != other.$custom_field {
+            lines.push(describe_change(stringify!($custom_field), &self.$custom_field, &other.$custom_field));
+          }
+        )*
+        lines
+      }
+
+      /// 読み込んだ値を vars に反映する。セーブデータになかった options の項目は vars の値のまま
+      pub fn apply_to(self, vars: &mut PersistentVariables) {
+        $(vars.$prim_field = self.$prim_field;)*
+        $(
+          if let Some(v) = self.$opt_field {
+            vars.$opt_field = v;
+          }
+        )*
+        $(vars.$custom_field = self.$custom_field;)*
+      }
+
+      /// vars をセーブデータの形式にする。
+      /// PersistentVariables のフィールドをすべて列挙するため、ここにないフィールドがあるとコンパイルエラーになる
+      #[allow(clippy::clone_on_copy)]
+      pub fn from_persistent(vars: &PersistentVariables) -> Self {
+        let PersistentVariables {
+          $($prim_field,)*
+          $($opt_field,)*
+          $($custom_field,)*
+        } = vars;
+        Self {
+          $($meta_field: $meta_value,)*
+          $($prim_field: $prim_field.clone(),)*
+          $($opt_field: Some($opt_field.clone()),)*
+          $($custom_field: $custom_field.clone(),)*
+        }
+      }
     }
   };
 }
 
+/// フィールドの変更を「名前: 変更前 -> 変更後」の形で表す。
+/// 配列や連想配列は中身を並べず件数で表す
+fn describe_change<T: Serialize>(name: &str, before: &T, after: &T) -> String {
+  fn summarize(value: serde_json::Value) -> String {
+    match value {
+      serde_json::Value::Array(a) => format!("{}件", a.len()),
+      serde_json::Value::Object(o) => {
+        // talk_collection のように値が配列なら、その合計件数
+        if o.values().all(|v| v.is_array()) {
+          let total: usize = o
+            .values()
+            .filter_map(|v| v.as_array())
+            .map(|a| a.len())
+            .sum();
+          format!("{}件", total)
+        } else {
+          format!("{}件", o.len())
+        }
+      }
+      serde_json::Value::Null => "なし".to_string(),
+      v => v.to_string(),
+    }
+  }
+  let to_summary = |v: &T| serde_json::to_value(v).map_or_else(|e| e.to_string(), summarize);
+  format!("{}: {} -> {}", name, to_summary(before), to_summary(after))
+}
+
 // RawVariables 構造体の定義
 // フィールドを追加・変更する場合はここを編集
 define_raw_variables! {
+  meta: {
+    schema_version: u32 = SCHEMA_VERSION,
+  },
   primitives: {
     total_boot_count: u64,
     cumulative_talk_count: u64,
+    pending_event_talk: Option<PendingEvent>,
   },
   options: {
     total_time: u64,
     random_talk_interval: u64,
     user_name: String,
-    pending_event_talk: PendingEvent,
     derivative_talk_requestable: bool,
     library_transition_sequense_dialog_index: u32,
+    autosave_interval: u64,
+    talk_bias: TalkBias,
   },
   custom: {
     talk_collection: HashMap<TalkType, HashSet<String>> => parse_talk_collection_lenient,
@@ -232,18 +358,108 @@ define_raw_variables! {
 }
 
 impl RawVariables {
-  pub fn save(&self) -> Result<(), Box<dyn Error>> {
-    self.save_to(VAR_PATH, VAR_BACKUP_PATH)
+  /// 既存ファイルを日時付きでバックアップしてから、一時ファイル経由で置き換える。
+  /// 中身のチェックサムを埋め込み、読み込み時に手で編集されたかを判別できるようにする
+  pub fn save_to(&self, path: &str, now: LocalTime) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(path);
+    if let Some(dir) = path.parent() {
+      std::fs::create_dir_all(dir)?;
+    }
+    rotate_backups(path, now, BACKUP_GENERATIONS)?;
+    let mut value = serde_json::to_value(self)?;
+    embed_checksum(&mut value);
+    let json_str_indent = serde_json::to_string_pretty(&value)?;
+    write_atomic(path, json_str_indent.as_bytes())?;
+    Ok(())
   }
 
-  pub fn save_to(&self, path: &str, backup_path: &str) -> Result<(), Box<dyn Error>> {
-    // 既存ファイルをバックアップ
-    if std::path::Path::new(path).exists() {
-      std::fs::copy(path, backup_path)?;
+  /// 部分的にロード。失敗したフィールドはデフォルト値を使用
+  /// 戻り値: (RawVariables, 失敗したフィールド名のリスト)
+  pub fn load_partial_from(path: &str) -> Result<(Self, Vec<String>), Box<dyn Error>> {
+    let (vars, failed_fields, _) = Self::load_checked(path)?;
+    Ok((vars, failed_fields))
+  }
+
+  /// load_partial_from と同じだが、チェックサムの検証結果も返す
+  /// 戻り値: (RawVariables, 失敗したフィールド名のリスト, チェックサムの検証結果)
+  pub fn load_checked(path: &str) -> Result<(Self, Vec<String>, Integrity), Box<dyn Error>> {
+    if !Path::new(path).exists() {
+      return Ok((Self::default(), vec![], Integrity::NoChecksum));
+    }
+
+    let json_str = std::fs::read_to_string(path)?;
+    let mut value: serde_json::Value = serde_json::from_str(&json_str)?;
+    let integrity = verify_checksum(&mut value);
+    let (vars, failed_fields) = Self::from_value(value)?;
+    Ok((vars, failed_fields, integrity))
+  }
+
+  /// path を読み込む。読めなければバックアップを新しい順に試す
+  /// 戻り値: (RawVariables, どのファイルからどう読めたか)
+  pub fn load_with_backups(path: &str) -> Result<(Self, LoadStatus), Box<dyn Error>> {
+    let main_exists = Path::new(path).exists();
+    let backups = list_backups(Path::new(path));
+
+    // メインファイルから部分パースを試行。メインがなくバックアップがあるなら、保存の途中で消えたとみてバックアップを使う
+    let main_error = if !main_exists && !backups.is_empty() {
+      warn!("メインファイルがありません");
+      None
+    } else {
+      match Self::load_checked(path) {
+        Ok((vars, failed_fields, integrity)) => {
+          let status = if !main_exists {
+            // ファイルが存在しない場合は FirstBoot
+            LoadStatus::FirstBoot
+          } else if integrity == Integrity::HandEdited {
+            warn!("チェックサムが一致しません。手で編集された可能性があります");
+            LoadStatus::HandEdited(failed_fields)
+          } else if !failed_fields.is_empty() {
+            warn!("部分パースで失敗したフィールド: {:?}", failed_fields);
+            LoadStatus::PartialSuccess(failed_fields)
+          } else if backups.is_empty() {
+            LoadStatus::SuccessNoBackup
+          } else {
+            LoadStatus::Success
+          };
+          return Ok((vars, status));
+        }
+        Err(e) => {
+          warn!("メインファイルのパースに失敗: {}", e);
+          Some(e)
+        }
+      }
+    };
+    let damage = main_error
+      .as_ref()
+      .map_or(Damage::Missing, |e| Damage::from_error(e.as_ref()));
+
+    for backup in backups {
+      let name = backup
+        .file_name()
+        .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+      match Self::load_partial_from(&backup.to_string_lossy()) {
+        Ok((vars, failed_fields)) => {
+          if failed_fields.is_empty() {
+            warn!("バックアップ {} から復元", name);
+          } else {
+            warn!(
+              "バックアップ {} から部分復元、失敗フィールド: {:?}",
+              name, failed_fields
+            );
+          }
+          return Ok((
+            vars,
+            LoadStatus::RestoredFromBackup(name, damage, failed_fields),
+          ));
+        }
+        Err(e) => warn!("バックアップ {} のパースに失敗: {}", name, e),
+      }
+    }
+    match main_error {
+      Some(e) => Err(e),
+      // メインがなくバックアップもすべて読めない。初回起動として始めると保存でバックアップが押し出されて失われるため、失敗とする
+      None => Ok((Self::default(), LoadStatus::FailedNoBackup(Damage::Missing))),
     }
-    let json_str_indent = serde_json::to_string_pretty(&self)?;
-    std::fs::write(path, json_str_indent)?;
-    Ok(())
   }
 }
 
@@ -288,30 +504,16 @@ fn parse_talk_collection_lenient(value: &serde_json::Value) -> Result<(HashMap<T
 }
 
 /// EventFlag のバリアントが一部無効でも、有効なものだけを読み込む
-/// 旧形式 {"flags": [...]} と新形式 [...] の両方に対応
 /// 戻り値: (パース結果, スキップした項目の警告リスト)
 fn parse_event_flags_lenient(value: &serde_json::Value) -> Result<(EventFlags, Vec<String>), String> {
-  // 新形式: 配列そのまま
-  // 旧形式: {"flags": [...]} からflagsを取り出す
-  let arr = if let Some(obj) = value.as_object() {
-    obj
-      .get("flags")
-      .and_then(|v| v.as_array())
-      .ok_or("flags.flags is not an array (old format)")?
-  } else if let Some(arr) = value.as_array() {
-    arr
-  } else {
-    return Err("flags is neither an object nor an array".to_string());
-  };
+  let arr = value.as_array().ok_or("flags is not an array")?;
 
-  let mut valid_flags = HashSet::new();
+  let mut valid_flags = EventFlags::default();
   let mut warnings = Vec::new();
 
   for flag_value in arr {
-    match serde_json::from_value::<EventFlag>(flag_value.clone()) {
-      Ok(flag) => {
-        valid_flags.insert(flag);
-      }
+    match serde_json::from_value::<FlagEntry>(flag_value.clone()) {
+      Ok(entry) => valid_flags.insert(entry.flag, entry.record),
       Err(_) => {
         let msg = format!("不明なEventFlag をスキップ: {:?}", flag_value);
         warn!("{}", msg);
@@ -320,7 +522,7 @@ fn parse_event_flags_lenient(value: &serde_json::Value) -> Result<(EventFlags, V
     }
   }
 
-  Ok((EventFlags { flags: valid_flags }, warnings))
+  Ok((valid_flags, warnings))
 }
 
 #[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Clone, Debug)]
@@ -337,43 +539,143 @@ pub(crate) enum EventFlag {
   SeasonEvent(u32, u32, u32),
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default)]
-#[serde(transparent)]
+/// フラグを立てた記録
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FlagRecord {
+  /// 最初に立てた日時。記録を始める前(schema v2以前)から立っていたフラグはなし
+  #[serde(default)]
+  pub first_set: Option<LocalTime>,
+  /// 立てた回数
+  pub count: u32,
+}
+
+/// セーブデータ上の形式。EventFlag はJSONのキーにできないため、記録と並べて配列にする
+#[derive(Serialize, Deserialize, Clone)]
+struct FlagEntry {
+  flag: EventFlag,
+  #[serde(flatten)]
+  record: FlagRecord,
+}
+
+/// 季節イベントのフラグを残す年数。これより古いものは読み込み時に消す
+const SEASON_EVENT_KEEP_YEARS: u32 = 5;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(from = "Vec<FlagEntry>", into = "Vec<FlagEntry>")]
 pub(crate) struct EventFlags {
-  flags: HashSet<EventFlag>,
+  flags: HashMap<EventFlag, FlagRecord>,
+}
+
+impl From<Vec<FlagEntry>> for EventFlags {
+  fn from(entries: Vec<FlagEntry>) -> Self {
+    let mut flags = Self::default();
+    for entry in entries {
+      flags.insert(entry.flag, entry.record);
+    }
+    flags
+  }
+}
+
+impl From<EventFlags> for Vec<FlagEntry> {
+  fn from(flags: EventFlags) -> Self {
+    flags
+      .flags
+      .into_iter()
+      .map(|(flag, record)| FlagEntry { flag, record })
+      .collect()
+  }
 }
 
 impl EventFlags {
-  pub fn done(&mut self, flag: EventFlag) {
-    self.flags.insert(flag);
+  /// フラグを立てる。既に立っていれば回数だけ増やす
+  pub fn done(&mut self, flag: EventFlag, now: LocalTime) {
+    let record = self.flags.entry(flag).or_insert(FlagRecord {
+      first_set: Some(now),
+      count: 0,
+    });
+    record.count += 1;
   }
 
   pub fn check(&self, flag: &EventFlag) -> bool {
-    self.flags.contains(flag)
+    self.flags.contains_key(flag)
   }
 
+  /// フラグを消す。記録も消えるため、立て直すと日時と回数は新しく数え直す
   pub fn delete(&mut self, flag: EventFlag) {
     self.flags.remove(&flag);
   }
 
+  /// 読み込んだ記録を加える。同じフラグが既にあれば、早いほうの日時と多いほうの回数をとる
+  fn insert(&mut self, flag: EventFlag, record: FlagRecord) {
+    match self.flags.get_mut(&flag) {
+      Some(existing) => {
+        existing.first_set = match (existing.first_set, record.first_set) {
+          (Some(a), Some(b)) => Some(a.min(b)),
+          (a, b) => a.or(b),
+        };
+        existing.count = existing.count.max(record.count);
+      }
+      None => {
+        self.flags.insert(flag, record);
+      }
+    }
+  }
+
+  /// other のフラグをすべて加える
+  pub fn extend(&mut self, other: EventFlags) {
+    for (flag, record) in other.flags {
+      self.insert(flag, record);
+    }
+  }
+
+  /// フラグを最初に立てた日時。立っていないか、記録がなければなし
+  pub fn first_set(&self, flag: &EventFlag) -> Option<LocalTime> {
+    self.flags.get(flag).and_then(|r| r.first_set)
+  }
+
+  /// フラグを最初に立ててから何日経ったか
+  pub fn days_since(&self, flag: &EventFlag, now: LocalTime) -> Option<i64> {
+    self.first_set(flag).map(|t| now.days_since(t))
+  }
+
+  /// 初回起動から何日経ったか
+  pub fn days_since_first_boot(&self, now: LocalTime) -> Option<i64> {
+    self.days_since(&EventFlag::FirstBoot, now)
+  }
+
+  /// 立っているフラグと記録。最初に立てた日時の順(記録がないものが先)
+  pub fn records(&self) -> Vec<(&EventFlag, &FlagRecord)> {
+    let mut entries: Vec<_> = self.flags.iter().collect();
+    entries.sort_by_key(|(_, record)| record.first_set);
+    entries
+  }
+
   /// 指定した年月日の季節イベントが既に閲覧済みかチェック
   pub fn check_season_event(&self, year: u32, month: u32, day: u32) -> bool {
-    self
-      .flags
-      .contains(&EventFlag::SeasonEvent(year, month, day))
+    self.check(&EventFlag::SeasonEvent(year, month, day))
   }
 
   /// 季節イベントを閲覧済みとしてマーク
-  pub fn mark_season_event(&mut self, year: u32, month: u32, day: u32) {
-    self.flags.insert(EventFlag::SeasonEvent(year, month, day));
+  pub fn mark_season_event(&mut self, year: u32, month: u32, day: u32, now: LocalTime) {
+    self.done(EventFlag::SeasonEvent(year, month, day), now);
   }
 
-  /// 指定した月日の季節イベントを過去に何回見たか取得
+  /// SEASON_EVENT_KEEP_YEARS 年より前の季節イベントのフラグを消す。
+  /// 閲覧済みかは今年の分しか見ないため、古いものは増えていく一方になる
+  pub fn prune_season_events(&mut self, now: LocalTime) {
+    let oldest = now.year.saturating_sub(SEASON_EVENT_KEEP_YEARS);
+    self
+      .flags
+      .retain(|flag, _| !matches!(flag, EventFlag::SeasonEvent(year, _, _) if *year < oldest));
+  }
+
+  /// 指定した月日の季節イベントを過去に何回見たか取得。
+  /// prune_season_events で消した分は数えない
   #[allow(dead_code)]
   pub fn count_season_event(&self, month: u32, day: u32) -> usize {
     self
       .flags
-      .iter()
+      .keys()
       .filter(|f| {
         if let EventFlag::SeasonEvent(_, m, d) = f {
           *m == month && *d == day
@@ -389,196 +691,303 @@ pub(crate) const TRANSPARENT_SURFACE: i32 = 1000000;
 /// bindで表情を組み立てる際の土台となる素体サーフェス
 pub(crate) const BASE_SURFACE: i32 = 1000100;
 
-pub fn load_global_variables() -> Result<(), Box<dyn Error>> {
-  let main_exists = std::path::Path::new(VAR_PATH).exists();
-  let backup_exists = std::path::Path::new(VAR_BACKUP_PATH).exists();
-
-  // メインファイルから部分パースを試行
-  let raw_vars = match RawVariables::load_partial_from(VAR_PATH) {
-    Ok((vars, failed_fields)) => {
-      if !main_exists {
-        // ファイルが存在しない場合は FirstBoot
-        *get_write(&LOAD_STATUS) = LoadStatus::FirstBoot;
-      } else if failed_fields.is_empty() {
-        // 全フィールド成功
-        if backup_exists {
-          *get_write(&LOAD_STATUS) = LoadStatus::Success;
-        } else {
-          *get_write(&LOAD_STATUS) = LoadStatus::SuccessNoBackup;
-        }
-      } else {
-        // 一部フィールドが失敗
-        warn!("部分パースで失敗したフィールド: {:?}", failed_fields);
-        *get_write(&LOAD_STATUS) = LoadStatus::PartialSuccess(failed_fields);
-      }
-      vars
+/// ゴーストの状態。各イベントハンドラに渡される
+///
+/// セーブデータに保存される永続的な変数と、起動毎にリセットされる揮発性の変数に分かれる。
+/// 時刻の取得元と外部ファイルから読み込んだトークの文章もここに持たせ、インスタンスごとに差し替えられるようにする。
+pub(crate) struct GhostState {
+  pub persistent: PersistentVariables,
+  pub volatile: VolatileVariables,
+  clock: Arc<dyn Clock>,
+  talk_content: TalkContent,
+}
+
+impl Default for GhostState {
+  fn default() -> Self {
+    Self::new(Arc::new(SystemClock))
+  }
+}
+
+impl GhostState {
+  pub fn new(clock: Arc<dyn Clock>) -> Self {
+    Self {
+      persistent: PersistentVariables::default(),
+      volatile: VolatileVariables::default(),
+      clock,
+      talk_content: TalkContent::default(),
     }
-    Err(e) => {
-      // メイン部分パースも失敗 → バックアップから部分パースを試行
-      warn!("メインファイルのパースに失敗: {}", e);
+  }
 
-      match RawVariables::load_partial_from(VAR_BACKUP_PATH) {
-        Ok((backup_vars, backup_failed_fields)) => {
-          if backup_failed_fields.is_empty() {
-            warn!("バックアップから復元");
-            *get_write(&LOAD_STATUS) = LoadStatus::RestoredFromBackup;
-          } else {
-            warn!(
-              "バックアップから部分復元、失敗フィールド: {:?}",
-              backup_failed_fields
-            );
-            *get_write(&LOAD_STATUS) = LoadStatus::PartialSuccess(backup_failed_fields);
-          }
-          backup_vars
-        }
-        Err(_) => {
-          *get_write(&LOAD_STATUS) = LoadStatus::FailedNoBackup;
-          return Err(e);
-        }
+  /// 現在のローカル時刻。時刻に依存する処理は必ずここから取得する
+  pub fn local_time(&self) -> LocalTime {
+    self.clock.now()
+  }
+
+  /// 外部ファイルから読み込んだトークの文章
+  pub fn talk_content(&self) -> &TalkContent {
+    &self.talk_content
+  }
+
+  pub fn set_talk_content(&mut self, content: TalkContent) {
+    self.talk_content = content;
+  }
+
+  /// 現在のプロフィールのセーブデータのパス
+  pub fn var_path(&self) -> String {
+    self
+      .volatile
+      .save_dir
+      .join(profile::var_path(self.volatile.profile.as_deref()))
+      .to_string_lossy()
+      .into_owned()
+  }
+
+  /// セーブデータを読み込む。結果は volatile.load_status に記録される
+  pub fn load_variables(&mut self) -> Result<(), Box<dyn Error>> {
+    let (raw_vars, status) = match RawVariables::load_with_backups(&self.var_path()) {
+      Ok(loaded) => loaded,
+      Err(e) => {
+        self.volatile.load_status = LoadStatus::FailedNoBackup(Damage::from_error(e.as_ref()));
+        return Err(e);
       }
+    };
+    self.volatile.load_status = status;
+    if self.volatile.load_status.is_failed() {
+      return Ok(());
     }
-  };
-  debug!("load status: {:?}", *get_read(&LOAD_STATUS));
-
-  *get_write(&TOTAL_BOOT_COUNT) = raw_vars.total_boot_count;
-  if let Some(time) = raw_vars.total_time {
-    *get_write(&TOTAL_TIME) = time;
-  }
-  if let Some(interval) = raw_vars.random_talk_interval {
-    *get_write(&RANDOM_TALK_INTERVAL) = interval;
-  }
-  if let Some(name) = raw_vars.user_name {
-    *get_write(&USER_NAME) = name;
-  }
-  *get_write(&CUMULATIVE_TALK_COUNT) = raw_vars.cumulative_talk_count;
-  *get_write(&FLAGS) = raw_vars.flags;
-  *get_write(&PENDING_EVENT_TALK) = raw_vars.pending_event_talk;
-  let mut raw_talk_collection: HashMap<TalkType, HashSet<String>> = HashMap::new();
-  let mut all_talk_ids = TalkType::all()
-    .into_iter()
-    .filter_map(random_talks)
-    .flatten()
-    .map(|t| t.id)
-    .collect::<Vec<_>>();
-  let derivalive_talk_ids = derivative_talks()
-    .into_iter()
-    .map(|t| t.id)
-    .collect::<Vec<_>>();
-  all_talk_ids.extend(derivalive_talk_ids);
-
-  // 各TalkTypeごとに有効なトークIDのマップを作成
-  let mut valid_talk_ids_per_type: HashMap<TalkType, HashSet<String>> = HashMap::new();
-  for talk_type in TalkType::all() {
-    let mut valid_ids = HashSet::new();
-
-    // 通常トークのIDを追加
-    if let Some(talks) = random_talks(talk_type) {
-      for talk in talks {
-        valid_ids.insert(talk.id);
-      }
+    // 未知の新しい形式は、上書きして情報を失わないよう読み取り専用にする
+    if raw_vars.schema_version > SCHEMA_VERSION {
+      warn!(
+        "セーブデータの形式(v{})がこのゴーストの対応する形式(v{})より新しいため、読み取り専用で読み込みます",
+        raw_vars.schema_version, SCHEMA_VERSION
+      );
+      self.volatile.load_status = LoadStatus::FutureVersion(raw_vars.schema_version);
     }
+    debug!("load status: {:?}", self.volatile.load_status);
 
-    // 派生トークのIDを追加
-    for derivative_talk in derivative_talks() {
-      // 派生トークの親トークがこのTalkTypeに属するかチェック
-      if let Some(parent_talks) = random_talks(talk_type) {
-        if parent_talks
-          .iter()
-          .any(|t| t.id == derivative_talk.parent_id)
-        {
-          valid_ids.insert(derivative_talk.id);
-        }
-      }
+    raw_vars.apply_to(&mut self.persistent);
+    let now = self.local_time();
+    self.persistent.flags.prune_season_events(now);
+
+    self.prune_removed_talks();
+
+    Ok(())
+  }
+
+  /// トーク履歴と抽選の偏りから、存在しなくなったトークの分を捨てる。
+  /// 季節や時間帯などの出現条件は見ないため、今は出ないトークの分も残る
+  fn prune_removed_talks(&mut self) {
+    let all_talk_ids_per_type = all_talk_ids_per_type(&self.talk_content);
+
+    for (talk_type, ids) in self.persistent.talk_collection.iter_mut() {
+      // そのTalkTypeに属するトークIDのみを残す
+      let valid_ids = all_talk_ids_per_type
+        .get(talk_type)
+        .cloned()
+        .unwrap_or_default();
+      ids.retain(|id| valid_ids.contains(id));
     }
 
-    valid_talk_ids_per_type.insert(talk_type, valid_ids);
+    // 以前の形式では触り反応の台詞なども抽選の偏りに保存していたため、今あるトークのID以外はすべて捨てる
+    let all_ids: HashSet<String> = all_talk_ids_per_type.into_values().flatten().collect();
+    self.persistent.talk_bias.retain_keys(&all_ids);
   }
 
-  for (talk_type, ids) in raw_vars.talk_collection {
-    // そのTalkTypeに属するトークIDのみを残す
-    let valid_ids = valid_talk_ids_per_type
-      .get(&talk_type)
-      .cloned()
-      .unwrap_or_default();
-    let existing_and_seen_talk_ids: HashSet<String> = ids
-      .into_iter()
-      .filter(|id| valid_ids.contains(id))
-      .collect::<HashSet<String>>();
-    raw_talk_collection.insert(talk_type, existing_and_seen_talk_ids);
-  }
-  *get_write(&TALK_COLLECTION) = raw_talk_collection;
-  if let Some(derivative_talk_requestable) = raw_vars.derivative_talk_requestable {
-    *get_write(&DERIVATIVE_TALK_REQUESTABLE) = derivative_talk_requestable;
-  } else {
-    *get_write(&DERIVATIVE_TALK_REQUESTABLE) = false;
-  }
-
-  Ok(())
-}
-
-pub fn save_global_variables() -> Result<(), Box<dyn Error>> {
-  let raw_vars = RawVariables {
-    total_boot_count: *get_read(&TOTAL_BOOT_COUNT),
-    total_time: Some(*get_read(&TOTAL_TIME)),
-    random_talk_interval: Some(*get_read(&RANDOM_TALK_INTERVAL)),
-    user_name: Some(get_read(&USER_NAME).clone()),
-    talk_collection: get_read(&TALK_COLLECTION).clone(),
-    cumulative_talk_count: *get_read(&CUMULATIVE_TALK_COUNT),
-    flags: get_read(&FLAGS).clone(),
-    pending_event_talk: get_read(&PENDING_EVENT_TALK).clone(),
-    derivative_talk_requestable: Some(*get_read(&DERIVATIVE_TALK_REQUESTABLE)),
-    library_transition_sequense_dialog_index: Some(*get_read(&LIBRARY_TRANSITION_SEQUENSE_DIALOG_INDEX)),
-  };
+  /// 保存される変数を、セーブデータの形式で取得する
+  pub fn to_raw_variables(&self) -> RawVariables {
+    RawVariables::from_persistent(&self.persistent)
+  }
+
+  /// セーブデータを保存する。read_only なら何もしない
+  pub fn save_variables(&self) -> Result<(), Box<dyn Error>> {
+    if self.volatile.read_only {
+      debug!("読み取り専用のため保存しません");
+      return Ok(());
+    }
+    let raw_vars = self.to_raw_variables();
+    raw_vars.save_to(&self.var_path(), self.local_time())?;
+
+    Ok(())
+  }
 
-  raw_vars.save()?;
-
-  Ok(())
-}
-
-pub(crate) fn reset_volatile_variables() {
-  *get_write(&DEBUG_MODE) = false;
-  *get_write(&LOG_PATH) = String::new();
-  *get_write(&GHOST_UP_TIME) = 0;
-  *get_write(&LAST_RANDOM_TALK_TIME) = 0;
-  *get_write(&NADE_COUNTER) = 0;
-  *get_write(&LAST_NADE_COUNT_UNIXTIME) = UNIX_EPOCH;
-  *get_write(&LAST_NADE_PART) = String::new();
-  *get_write(&WHEEL_DIRECTION) = Direction::Up;
-  *get_write(&WHEEL_COUNTER) = 0;
-  *get_write(&LAST_WHEEL_COUNT_UNIXTIME) = UNIX_EPOCH;
-  *get_write(&LAST_WHEEL_PART) = String::new();
-  *get_write(&LAST_TOUCH_INFO) = String::new();
-  *get_write(&CHAIN_TALK_STATE) = None;
-  *get_write(&TALK_BIAS) = TalkBias::new();
-  *get_write(&CURRENT_SURFACE) = 0;
-  *get_write(&IDLE_SECONDS) = 0;
-  *get_write(&IMMERSIVE_DEGREES) = 0;
-  *get_write(&TOUCH_INFO) = HashMap::new();
-  *get_write(&TALKING_PLACE) = TalkingPlace::LivingRoom;
-  *get_write(&LAST_ANCHOR_ID) = None;
-  *get_write(&CANDLES) = [false; IMMERSIVE_ICON_COUNT as usize];
-  *get_write(&LAST_SELFTALK_PHRASE) = String::new();
-}
-
-// ゴーストのグローバル変数のうち、揮発性(起動毎にリセットされる)のもの
-pub(crate) static DEBUG_MODE: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(false));
-pub(crate) static LOG_PATH: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new("".to_string()));
-pub(crate) static GHOST_UP_TIME: LazyLock<RwLock<u64>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static LAST_RANDOM_TALK_TIME: LazyLock<RwLock<u64>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static NADE_COUNTER: LazyLock<RwLock<i32>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static LAST_NADE_COUNT_UNIXTIME: LazyLock<RwLock<SystemTime>> = LazyLock::new(|| RwLock::new(UNIX_EPOCH));
-pub(crate) static LAST_NADE_PART: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new("".to_string()));
-pub(crate) static WHEEL_DIRECTION: LazyLock<RwLock<Direction>> = LazyLock::new(|| RwLock::new(Direction::Up));
-pub(crate) static WHEEL_COUNTER: LazyLock<RwLock<i32>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static LAST_WHEEL_COUNT_UNIXTIME: LazyLock<RwLock<SystemTime>> = LazyLock::new(|| RwLock::new(UNIX_EPOCH));
-pub(crate) static LAST_WHEEL_PART: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new("".to_string()));
-pub(crate) static FIRST_SEXIAL_TOUCH: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(false));
-pub(crate) static LAST_TOUCH_INFO: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new("".to_string()));
-pub(crate) static LAST_SELFTALK_PHRASE: LazyLock<RwLock<String>> = LazyLock::new(|| RwLock::new("".to_string()));
-
-/// チェイントーク待機状態
-/// (対象部位のイベント名, チェイントーク内容, 期限のGHOST_UP_TIME, コールバック)
-pub(crate) static CHAIN_TALK_STATE: LazyLock<RwLock<Option<ChainTalkState>>> = LazyLock::new(|| RwLock::new(None));
+  /// プロフィールを切り替える。今のプロフィールを保存してから切り替え先のセーブデータを読み込み、
+  /// 最後に使ったプロフィールとして記録する。profile がなければ既定のプロフィール
+  pub fn switch_profile(&mut self, profile: Option<String>) -> Result<(), Box<dyn Error>> {
+    if self.volatile.profile == profile {
+      return Ok(());
+    }
+    let status = &self.volatile.load_status;
+    if *status != LoadStatus::NotLoaded && status.should_save() {
+      self.save_variables()?;
+    }
+    info!(
+      "プロフィールを切り替えます: {} -> {}",
+      profile::title(self.volatile.profile.as_deref()),
+      profile::title(profile.as_deref())
+    );
+
+    if !self.volatile.read_only {
+      let mut index = ProfileIndex::load(&self.volatile.save_dir);
+      index.select(profile.as_deref());
+      index.save(&self.volatile.save_dir)?;
+    }
+
+    self.persistent = PersistentVariables::default();
+    self.volatile.profile = profile;
+    self.volatile.reset_session();
+    self.load_variables()
+  }
+
+  /// セッションの途中でセーブデータを保存する。
+  /// ロード前や、ロード失敗・読み取り専用のときは既存のデータを上書きしないよう保存しない
+  pub fn autosave(&mut self) {
+    self.volatile.minutes_since_save = 0;
+    let status = &self.volatile.load_status;
+    if *status == LoadStatus::NotLoaded || !status.should_save() {
+      return;
+    }
+    if let Err(e) = self.save_variables() {
+      error!("自動保存に失敗しました: {}", e);
+    }
+  }
+}
+
+/// ゴーストの変数のうち、セーブデータに保存されるもの。
+/// フィールドを追加したら define_raw_variables! の呼び出しにも追加する
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PersistentVariables {
+  pub total_boot_count: u64,
+  pub total_time: u64,
+  pub random_talk_interval: u64,
+  /// 自動保存の間隔（分）。0なら終了時のみ保存する
+  pub autosave_interval: u64,
+  pub user_name: String,
+  pub talk_collection: HashMap<TalkType, HashSet<String>>,
+  pub cumulative_talk_count: u64,
+  pub flags: EventFlags,
+  pub pending_event_talk: Option<PendingEvent>,
+  pub derivative_talk_requestable: bool,
+  pub library_transition_sequense_dialog_index: u32,
+  /// ランダムトークの抽選の偏り。キーはトークID。同じトークが続かないよう起動をまたいで保持する
+  pub talk_bias: TalkBias,
+}
+
+impl Default for PersistentVariables {
+  fn default() -> Self {
+    Self {
+      total_boot_count: 0,
+      total_time: 0,
+      random_talk_interval: 180,
+      autosave_interval: 10,
+      user_name: String::new(),
+      talk_collection: HashMap::new(),
+      cumulative_talk_count: 0,
+      flags: EventFlags::default(),
+      pending_event_talk: None,
+      derivative_talk_requestable: false,
+      library_transition_sequense_dialog_index: 1000,
+      talk_bias: TalkBias::new(),
+    }
+  }
+}
+
+/// ゴーストの変数のうち、揮発性(起動毎にリセットされる)のもの
+pub(crate) struct VolatileVariables {
+  pub load_status: LoadStatus,
+  /// 現在のプロフィール。なしなら既定のプロフィール
+  pub profile: Option<String>,
+  pub debug_mode: bool,
+  /// セーブデータやプロフィール一覧に書き込まない。トレースの再生など、手元のデータを変えたくないときに使う
+  pub read_only: bool,
+  /// セーブデータとプロフィール一覧を置くディレクトリ。空ならカレントディレクトリ
+  pub save_dir: PathBuf,
+  pub log_path: String,
+  /// OnBoot を処理したか。起動後にプロフィールを切り替えたとき、起動の記録をやり直すのに使う
+  pub booted: bool,
+  pub ghost_up_time: u64,
+  pub last_random_talk_time: u64,
+  /// 最後に保存してからの経過時間（分）
+  pub minutes_since_save: u64,
+  pub nade_counter: i32,
+  pub last_nade_count_unixtime: SystemTime,
+  pub last_nade_part: String,
+  pub wheel_direction: Direction,
+  pub wheel_counter: i32,
+  pub last_wheel_count_unixtime: SystemTime,
+  pub last_wheel_part: String,
+  pub first_sexial_touch: bool,
+  pub last_touch_info: String,
+  pub last_selftalk_phrase: String,
+  /// 触り反応などの台詞の抽選の偏り。台詞そのものがキーになるため保存しない
+  pub dialog_bias: TalkBias,
+  /// チェイントーク待機状態
+  pub chain_talk_state: Option<ChainTalkState>,
+  pub current_surface: i32,
+  pub idle_seconds: i32,
+  pub immersive_degrees: u32,
+  pub touch_info: HashMap<String, TouchInfo>,
+  pub talking_place: TalkingPlace,
+  pub last_anchor_id: Option<String>,
+  pub candles: [bool; IMMERSIVE_ICON_COUNT as usize],
+}
+
+impl Default for VolatileVariables {
+  fn default() -> Self {
+    Self {
+      load_status: LoadStatus::NotLoaded,
+      profile: None,
+      debug_mode: false,
+      read_only: false,
+      save_dir: PathBuf::new(),
+      log_path: String::new(),
+      booted: false,
+      ghost_up_time: 0,
+      last_random_talk_time: 0,
+      minutes_since_save: 0,
+      nade_counter: 0,
+      last_nade_count_unixtime: UNIX_EPOCH,
+      last_nade_part: String::new(),
+      wheel_direction: Direction::Up,
+      wheel_counter: 0,
+      last_wheel_count_unixtime: UNIX_EPOCH,
+      last_wheel_part: String::new(),
+      first_sexial_touch: false,
+      last_touch_info: String::new(),
+      last_selftalk_phrase: String::new(),
+      dialog_bias: TalkBias::new(),
+      chain_talk_state: None,
+      current_surface: 0,
+      idle_seconds: 0,
+      immersive_degrees: 0,
+      touch_info: HashMap::new(),
+      talking_place: TalkingPlace::LivingRoom,
+      last_anchor_id: None,
+      candles: [false; IMMERSIVE_ICON_COUNT as usize],
+    }
+  }
+}
+
+impl VolatileVariables {
+  /// プロフィールを切り替えたとき、前のプロフィールでの触り反応やチェイントーク、居場所を持ち越さないようリセットする
+  pub fn reset_session(&mut self) {
+    let defaults = Self::default();
+    self.minutes_since_save = defaults.minutes_since_save;
+    self.nade_counter = defaults.nade_counter;
+    self.last_nade_count_unixtime = defaults.last_nade_count_unixtime;
+    self.last_nade_part = defaults.last_nade_part;
+    self.wheel_direction = defaults.wheel_direction;
+    self.wheel_counter = defaults.wheel_counter;
+    self.last_wheel_count_unixtime = defaults.last_wheel_count_unixtime;
+    self.last_wheel_part = defaults.last_wheel_part;
+    self.first_sexial_touch = defaults.first_sexial_touch;
+    self.last_touch_info = defaults.last_touch_info;
+    self.touch_info = defaults.touch_info;
+    self.chain_talk_state = defaults.chain_talk_state;
+    self.immersive_degrees = defaults.immersive_degrees;
+    self.talking_place = defaults.talking_place;
+    self.last_anchor_id = defaults.last_anchor_id;
+  }
+}
 
 #[derive(Clone)]
 pub(crate) struct ChainTalkState {
@@ -586,19 +995,11 @@ pub(crate) struct ChainTalkState {
   pub target_part: String,
   /// チェイン発火時に表示するトーク内容
   pub chain_text: String,
-  /// 期限（GHOST_UP_TIME がこの値を超えたら無効）
+  /// 期限（ghost_up_time がこの値を超えたら無効）
   pub expires_at: u64,
-  /// 発火時に実行するコールバック（ゲートフラグ等）
-  pub callback: Option<fn()>,
-}
-pub(crate) static TALK_BIAS: LazyLock<RwLock<TalkBias>> = LazyLock::new(|| RwLock::new(TalkBias::new()));
-pub(crate) static CURRENT_SURFACE: LazyLock<RwLock<i32>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static IDLE_SECONDS: LazyLock<RwLock<i32>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static IMMERSIVE_DEGREES: LazyLock<RwLock<u32>> = LazyLock::new(|| RwLock::new(0));
-pub(crate) static TOUCH_INFO: LazyLock<RwLock<HashMap<String, TouchInfo>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
-pub(crate) static TALKING_PLACE: LazyLock<RwLock<TalkingPlace>> = LazyLock::new(|| RwLock::new(TalkingPlace::LivingRoom));
-pub(crate) static LAST_ANCHOR_ID: LazyLock<RwLock<Option<String>>> = LazyLock::new(|| RwLock::new(None));
-pub(crate) static CANDLES: LazyLock<RwLock<[bool; IMMERSIVE_ICON_COUNT as usize]>> = LazyLock::new(|| RwLock::new([false; IMMERSIVE_ICON_COUNT as usize]));
+  /// 発火時の副作用（ゲートフラグ等）
+  pub effects: Vec<Effect>,
+}
 
 pub(crate) const IDLE_THRESHOLD: i32 = 60 * 5;
 
@@ -651,44 +1052,41 @@ mod tests {
   use std::fs;
   use tempfile::TempDir;
 
+  fn at(second: u32) -> LocalTime {
+    LocalTime::new(2026, 10, 18, 12, 0, second)
+  }
+
   #[test]
   fn test_save_creates_backup() {
     let dir = TempDir::new().unwrap();
     let main_path = dir.path().join("vars.json");
-    let backup_path = dir.path().join("vars.json.bak");
 
     // 初回保存（バックアップなし）
     let vars = RawVariables::default();
-    vars
-      .save_to(main_path.to_str().unwrap(), backup_path.to_str().unwrap())
-      .unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
     assert!(main_path.exists());
-    assert!(!backup_path.exists()); // 初回はバックアップなし
+    assert!(list_backups(&main_path).is_empty()); // 初回はバックアップなし
 
-    // 2回目保存（バックアップ作成）
-    vars
-      .save_to(main_path.to_str().unwrap(), backup_path.to_str().unwrap())
-      .unwrap();
-    assert!(backup_path.exists()); // バックアップが作成される
+    // 2回目保存（日時付きのバックアップ作成）
+    vars.save_to(main_path.to_str().unwrap(), at(1)).unwrap();
+    assert_eq!(
+      list_backups(&main_path),
+      vec![dir.path().join("vars.json.20261018-120001.bak")]
+    );
   }
 
   #[test]
   fn test_load_backup_on_main_file_corruption() {
     let dir = TempDir::new().unwrap();
     let main_path = dir.path().join("vars.json");
-    let backup_path = dir.path().join("vars.json.bak");
 
     // 正常なデータを保存（2回保存してバックアップを作成）
     let vars = RawVariables {
       total_boot_count: 42,
       ..Default::default()
     };
-    vars
-      .save_to(main_path.to_str().unwrap(), backup_path.to_str().unwrap())
-      .unwrap();
-    vars
-      .save_to(main_path.to_str().unwrap(), backup_path.to_str().unwrap())
-      .unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(1)).unwrap();
 
     // メインファイルを破損
     fs::write(&main_path, "invalid json").unwrap();
@@ -698,9 +1096,146 @@ mod tests {
     assert!(result.is_err());
 
     // バックアップから復元
-    let (loaded, failed_fields) = RawVariables::load_partial_from(backup_path.to_str().unwrap()).unwrap();
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
     assert_eq!(loaded.total_boot_count, 42);
-    assert!(failed_fields.is_empty());
+    assert_eq!(
+      status,
+      LoadStatus::RestoredFromBackup(
+        "vars.json.20261018-120001.bak".to_string(),
+        Damage::Corrupt,
+        vec![]
+      )
+    );
+  }
+
+  #[test]
+  fn test_load_walks_backups_newest_first() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    for count in 1..=3 {
+      let vars = RawVariables {
+        total_boot_count: count,
+        ..Default::default()
+      };
+      vars
+        .save_to(main_path.to_str().unwrap(), at(count as u32))
+        .unwrap();
+    }
+    // メインと最新のバックアップ（count=2）を破損
+    fs::write(&main_path, "invalid").unwrap();
+    fs::write(dir.path().join("vars.json.20261018-120003.bak"), "invalid").unwrap();
+
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.total_boot_count, 1);
+    assert_eq!(
+      status,
+      LoadStatus::RestoredFromBackup(
+        "vars.json.20261018-120002.bak".to_string(),
+        Damage::Corrupt,
+        vec![]
+      )
+    );
+  }
+
+  #[test]
+  fn test_load_restores_when_main_file_is_missing() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    let vars = RawVariables {
+      total_boot_count: 42,
+      ..Default::default()
+    };
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(1)).unwrap();
+    // rename の前に落ちるなどしてメインファイルが消えた
+    fs::remove_file(&main_path).unwrap();
+
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.total_boot_count, 42);
+    assert_eq!(
+      status,
+      LoadStatus::RestoredFromBackup(
+        "vars.json.20261018-120001.bak".to_string(),
+        Damage::Missing,
+        vec![]
+      )
+    );
+  }
+
+  #[test]
+  fn test_load_fails_when_main_file_is_missing_and_backups_are_corrupt() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    let vars = RawVariables {
+      total_boot_count: 42,
+      ..Default::default()
+    };
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(1)).unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(2)).unwrap();
+    fs::remove_file(&main_path).unwrap();
+    for backup in list_backups(&main_path) {
+      fs::write(backup, "invalid").unwrap();
+    }
+
+    // 初回起動として始めず、保存もしない
+    let (_, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(status, LoadStatus::FailedNoBackup(Damage::Missing));
+    assert!(!status.should_save());
+
+    // メインもバックアップもなければ初回起動
+    for backup in list_backups(&main_path) {
+      fs::remove_file(backup).unwrap();
+    }
+    let (_, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(status, LoadStatus::FirstBoot);
+  }
+
+  #[test]
+  fn test_load_reports_partially_restored_backup() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    fs::write(
+      dir.path().join("vars.json.bak"),
+      r#"{"total_boot_count": 5, "flags": [], "user_name": 1}"#,
+    )
+    .unwrap();
+
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.total_boot_count, 5);
+    assert_eq!(
+      status,
+      LoadStatus::RestoredFromBackup(
+        "vars.json.bak".to_string(),
+        Damage::Missing,
+        vec!["user_name".to_string()]
+      )
+    );
+  }
+
+  #[test]
+  fn test_save_keeps_backups_within_the_same_second() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    for count in 1..=3 {
+      let vars = RawVariables {
+        total_boot_count: count,
+        ..Default::default()
+      };
+      vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    }
+    assert_eq!(
+      list_backups(&main_path),
+      vec![
+        dir.path().join("vars.json.20261018-120000-1.bak"),
+        dir.path().join("vars.json.20261018-120000.bak"),
+      ]
+    );
   }
 
   #[test]
@@ -719,33 +1254,106 @@ mod tests {
 
     let backup_result = RawVariables::load_partial_from(backup_path.to_str().unwrap());
     assert!(backup_result.is_err());
+
+    assert!(RawVariables::load_with_backups(main_path.to_str().unwrap()).is_err());
+  }
+
+  #[test]
+  fn test_load_restores_from_legacy_backup() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    // 日時のない旧形式のバックアップしかない
+    fs::write(&main_path, "invalid").unwrap();
+    fs::write(
+      dir.path().join("vars.json.bak"),
+      r#"{"total_boot_count": 5, "flags": []}"#,
+    )
+    .unwrap();
+
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.total_boot_count, 5);
+    assert_eq!(
+      status,
+      LoadStatus::RestoredFromBackup("vars.json.bak".to_string(), Damage::Corrupt, vec![])
+    );
+  }
+
+  #[test]
+  fn test_load_distinguishes_truncated_file() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+    let vars = RawVariables {
+      total_boot_count: 7,
+      ..Default::default()
+    };
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(1)).unwrap();
+
+    // 書き込みの途中で途切れたファイル
+    let saved = fs::read_to_string(&main_path).unwrap();
+    fs::write(&main_path, &saved[..saved.len() / 2]).unwrap();
+
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.total_boot_count, 7);
+    assert_eq!(
+      status,
+      LoadStatus::RestoredFromBackup(
+        "vars.json.20261018-120001.bak".to_string(),
+        Damage::Truncated,
+        vec![]
+      )
+    );
+  }
+
+  #[test]
+  fn test_load_detects_hand_edited_file() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+    let vars = RawVariables {
+      total_boot_count: 7,
+      ..Default::default()
+    };
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    let (_, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(status, LoadStatus::SuccessNoBackup);
+
+    let edited = fs::read_to_string(&main_path)
+      .unwrap()
+      .replace("\"total_boot_count\": 7", "\"total_boot_count\": 700");
+    fs::write(&main_path, edited).unwrap();
+
+    let (loaded, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(loaded.total_boot_count, 700);
+    assert_eq!(status, LoadStatus::HandEdited(vec![]));
+
+    // チェックサムのない旧形式は手で編集されたとはみなさない
+    fs::write(&main_path, r#"{"total_boot_count": 3, "flags": []}"#).unwrap();
+    let (_, status) = RawVariables::load_with_backups(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(status, LoadStatus::SuccessNoBackup);
   }
 
   #[test]
   fn test_backup_preserves_previous_data() {
     let dir = TempDir::new().unwrap();
     let main_path = dir.path().join("vars.json");
-    let backup_path = dir.path().join("vars.json.bak");
 
     // 最初のデータを保存
     let vars1 = RawVariables {
       total_boot_count: 10,
       ..Default::default()
     };
-    vars1
-      .save_to(main_path.to_str().unwrap(), backup_path.to_str().unwrap())
-      .unwrap();
+    vars1.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
 
     // 2回目のデータを保存（vars1がバックアップされる）
     let vars2 = RawVariables {
       total_boot_count: 20,
       ..Default::default()
     };
-    vars2
-      .save_to(main_path.to_str().unwrap(), backup_path.to_str().unwrap())
-      .unwrap();
+    vars2.save_to(main_path.to_str().unwrap(), at(1)).unwrap();
 
     // バックアップには前回のデータ（10）が残っている
+    let backup_path = &list_backups(&main_path)[0];
     let (backup_loaded, _) = RawVariables::load_partial_from(backup_path.to_str().unwrap()).unwrap();
     assert_eq!(backup_loaded.total_boot_count, 10);
 
@@ -833,6 +1441,181 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_prune_keeps_talks_whose_condition_is_false() {
+    use crate::system::clock::FakeClock;
+
+    // 夏の昼なので、夜のトーク「館の静寂」と冬のトーク「白くない息」は出ない
+    let clock = FakeClock::new(LocalTime::new(2026, 7, 1, 12, 0, 0));
+    let mut state = GhostState::new(Arc::new(clock));
+    let ids = |ids: &[&str]| ids.iter().map(|s| s.to_string()).collect::<HashSet<_>>();
+    state
+      .persistent
+      .talk_collection
+      .insert(TalkType::AboutMe, ids(&["館の静寂", "削除されたトーク"]));
+    state
+      .persistent
+      .talk_collection
+      .insert(TalkType::WithYou, ids(&["白くない息"]));
+    for key in [
+      "館の静寂",
+      "白くない息",
+      "記憶の整理",
+      "削除されたトーク",
+      "h1111205触り反応の台詞",
+    ] {
+      state.persistent.talk_bias.increment(key);
+    }
+
+    state.prune_removed_talks();
+    assert_eq!(
+      state.persistent.talk_collection[&TalkType::AboutMe],
+      ids(&["館の静寂"])
+    );
+    assert_eq!(
+      state.persistent.talk_collection[&TalkType::WithYou],
+      ids(&["白くない息"])
+    );
+    let bias = &state.persistent.talk_bias;
+    assert_eq!(bias.get("館の静寂"), 2);
+    assert_eq!(bias.get("白くない息"), 2);
+    // トーク履歴になくても、今あるトークの分は残る
+    assert_eq!(bias.get("記憶の整理"), 2);
+    // 消えたトークと、トーク以外の台詞の分は捨てられ、初期値に戻る
+    assert_eq!(bias.get("削除されたトーク"), 1);
+    assert_eq!(bias.get("h1111205触り反応の台詞"), 1);
+  }
+
+  #[test]
+  fn test_switch_profile_saves_and_loads() {
+    let dir = TempDir::new().unwrap();
+    let mut state = GhostState::default();
+    state.volatile.save_dir = dir.path().to_path_buf();
+    state.load_variables().unwrap();
+    assert_eq!(state.volatile.load_status, LoadStatus::FirstBoot);
+    state.persistent.total_boot_count = 3;
+
+    // 今のプロフィールを保存してから、新しいプロフィールを読み込む
+    state.switch_profile(Some("あかり".to_string())).unwrap();
+    assert_eq!(state.volatile.profile.as_deref(), Some("あかり"));
+    assert_eq!(state.volatile.load_status, LoadStatus::FirstBoot);
+    assert_eq!(state.persistent.total_boot_count, 0);
+    let (saved, _) = RawVariables::load_partial_from(dir.path().join(VAR_PATH).to_str().unwrap()).unwrap();
+    assert_eq!(saved.total_boot_count, 3);
+    let index = ProfileIndex::load(dir.path());
+    assert_eq!(index.last.as_deref(), Some("あかり"));
+    assert_eq!(index.names, vec!["あかり"]);
+
+    // 戻ると、それぞれの進み具合が残っている。前のプロフィールでの居場所や触り反応は持ち越さない
+    state.persistent.total_boot_count = 7;
+    state.volatile.talking_place = TalkingPlace::Library;
+    state.volatile.immersive_degrees = 100;
+    state.volatile.nade_counter = 3;
+    state.volatile.minutes_since_save = 4;
+    state.switch_profile(None).unwrap();
+    assert_eq!(state.persistent.total_boot_count, 3);
+    assert_eq!(state.volatile.talking_place, TalkingPlace::LivingRoom);
+    assert_eq!(state.volatile.immersive_degrees, 0);
+    assert_eq!(state.volatile.nade_counter, 0);
+    assert_eq!(state.volatile.minutes_since_save, 0);
+    assert_eq!(ProfileIndex::load(dir.path()).last, None);
+    state.switch_profile(Some("あかり".to_string())).unwrap();
+    assert_eq!(state.persistent.total_boot_count, 7);
+
+    // 読み取り専用なら、切り替えても何も書き込まない
+    state.volatile.read_only = true;
+    state.persistent.total_boot_count = 100;
+    state.switch_profile(None).unwrap();
+    assert_eq!(state.persistent.total_boot_count, 3);
+    assert_eq!(
+      ProfileIndex::load(dir.path()).last.as_deref(),
+      Some("あかり")
+    );
+    state.switch_profile(Some("あかり".to_string())).unwrap();
+    assert_eq!(state.persistent.total_boot_count, 7);
+  }
+
+  #[test]
+  fn test_every_persistent_field_roundtrips() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    let mut flags = EventFlags::default();
+    flags.done(EventFlag::FirstBoot, at(0));
+    let mut talk_bias = TalkBias::new();
+    talk_bias.increment("talk");
+    // ..Default::default() を使わず、フィールドが増えたらここで気づけるようにする
+    let vars = PersistentVariables {
+      total_boot_count: 3,
+      total_time: 120,
+      random_talk_interval: 60,
+      autosave_interval: 5,
+      user_name: "test".to_string(),
+      talk_collection: HashMap::from([(TalkType::AboutMe, HashSet::from(["talk".to_string()]))]),
+      cumulative_talk_count: 7,
+      flags,
+      pending_event_talk: Some(PendingEvent::FirstClose),
+      derivative_talk_requestable: true,
+      library_transition_sequense_dialog_index: 2,
+      talk_bias,
+    };
+    let raw = RawVariables::from_persistent(&vars);
+    // schema_version 以外のすべてのフィールドが初期値と異なる
+    let changed = RawVariables::from_persistent(&PersistentVariables::default()).diff(&raw);
+    assert_eq!(
+      changed.len(),
+      RawVariables::FIELD_NAMES.len() - 1,
+      "{:?}",
+      changed
+    );
+
+    raw.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+    let (loaded, failed_fields) = RawVariables::load_partial_from(main_path.to_str().unwrap()).unwrap();
+    assert!(failed_fields.is_empty(), "{:?}", failed_fields);
+    let mut restored = PersistentVariables::default();
+    loaded.apply_to(&mut restored);
+    assert_eq!(restored, vars);
+  }
+
+  #[test]
+  fn test_event_flags_record_first_set_and_count() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    let mut vars = RawVariables {
+      schema_version: SCHEMA_VERSION,
+      ..Default::default()
+    };
+    vars.flags.done(EventFlag::FirstBoot, at(0));
+    vars.flags.done(EventFlag::FirstBoot, at(30));
+    vars.flags.mark_season_event(2019, 10, 31, at(0));
+    vars.flags.mark_season_event(2025, 10, 31, at(0));
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
+
+    let (mut loaded, failed_fields) = RawVariables::load_partial_from(main_path.to_str().unwrap()).unwrap();
+    assert!(failed_fields.is_empty(), "{:?}", failed_fields);
+    assert_eq!(loaded.flags, vars.flags);
+    assert_eq!(loaded.flags.first_set(&EventFlag::FirstBoot), Some(at(0)));
+    assert_eq!(
+      loaded
+        .flags
+        .days_since_first_boot(LocalTime::new(2026, 10, 20, 0, 0, 0)),
+      Some(2)
+    );
+    let (_, record) = loaded
+      .flags
+      .records()
+      .into_iter()
+      .find(|(flag, _)| **flag == EventFlag::FirstBoot)
+      .unwrap();
+    assert_eq!(record.count, 2);
+
+    // 5年より前の季節イベントは消える
+    loaded.flags.prune_season_events(at(0));
+    assert!(!loaded.flags.check_season_event(2019, 10, 31));
+    assert!(loaded.flags.check_season_event(2025, 10, 31));
+  }
+
   #[test]
   fn test_partial_load_with_corrupted_field() {
     let dir = TempDir::new().unwrap();
@@ -867,12 +1650,7 @@ mod tests {
       cumulative_talk_count: 100,
       ..Default::default()
     };
-    vars
-      .save_to(
-        main_path.to_str().unwrap(),
-        dir.path().join("backup.json").to_str().unwrap(),
-      )
-      .unwrap();
+    vars.save_to(main_path.to_str().unwrap(), at(0)).unwrap();
 
     // 部分パースでも全フィールド成功
     let (loaded, failed_fields) = RawVariables::load_partial_from(main_path.to_str().unwrap()).unwrap();
@@ -881,6 +1659,64 @@ mod tests {
     assert!(failed_fields.is_empty());
   }
 
+  #[test]
+  fn test_load_migrates_old_format() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    // schema_version のない旧形式
+    let json = r#"{
+      "total_boot_count": 7,
+      "talk_collection": {"SelfIntroduce": ["id1"]},
+      "flags": {"flags": ["FirstBoot"]}
+    }"#;
+    fs::write(&main_path, json).unwrap();
+
+    let (vars, failed_fields) = RawVariables::load_partial_from(main_path.to_str().unwrap()).unwrap();
+    assert!(failed_fields.is_empty(), "{:?}", failed_fields);
+    assert_eq!(vars.schema_version, SCHEMA_VERSION);
+    assert!(vars.flags.check(&EventFlag::FirstBoot));
+    assert!(vars.talk_collection[&TalkType::AboutMe].contains("id1"));
+  }
+
+  #[test]
+  fn test_load_migrates_talk_type_unlock_from_v1() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    let json = r#"{
+      "schema_version": 1,
+      "talk_collection": {"SelfIntroduce": ["id1"]},
+      "flags": ["FirstBoot", {"TalkTypeUnlock": "SelfIntroduce"}]
+    }"#;
+    fs::write(&main_path, json).unwrap();
+
+    let (vars, failed_fields) = RawVariables::load_partial_from(main_path.to_str().unwrap()).unwrap();
+    assert!(failed_fields.is_empty(), "{:?}", failed_fields);
+    assert!(vars
+      .flags
+      .check(&EventFlag::TalkTypeUnlock(TalkType::AboutMe)));
+    assert!(vars.talk_collection[&TalkType::AboutMe].contains("id1"));
+  }
+
+  #[test]
+  fn test_load_future_version_is_read_only() {
+    let dir = TempDir::new().unwrap();
+    let main_path = dir.path().join("vars.json");
+
+    let json = format!(
+      r#"{{"schema_version": {}, "total_boot_count": 9, "flags": [], "unknown_field": true}}"#,
+      SCHEMA_VERSION + 1
+    );
+    fs::write(&main_path, json).unwrap();
+
+    // 読める項目は読み、バージョンはそのまま残る
+    let (vars, _) = RawVariables::load_partial_from(main_path.to_str().unwrap()).unwrap();
+    assert_eq!(vars.schema_version, SCHEMA_VERSION + 1);
+    assert_eq!(vars.total_boot_count, 9);
+    assert!(!LoadStatus::FutureVersion(vars.schema_version).should_save());
+  }
+
   #[test]
   fn test_partial_load_nonexistent_file_returns_default() {
     let dir = TempDir::new().unwrap();